@@ -1,18 +1,162 @@
-use std::fmt::Debug;
+use std::fmt;
+use super::tokenizer::{self, Span};
+use super::types::{ IntType, tuple };
 
-pub trait Ast: Debug {
-    fn check_semantic(&mut self);
-    fn generate_code(&mut self);
+// Top-level statements make up the body of main.
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    structs: Vec<StructDecl>,
+    enums: Vec<EnumDecl>,
+    functions: Vec<FunctionDecl>,
+    statements: Vec<Statement>,
 }
 
-#[derive(Debug)]
-pub struct Program {
+// struct Name { field: type, ... }
+#[derive(Debug, Clone)]
+pub struct StructDecl {
+    name: String,
+    fields: Vec<FieldDecl>,
+    span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldDecl {
+    name: String,
+    type_name: TypeName,
+    span: Span,
+}
+
+// enum Name { Variant, Variant(type, ...), ... }
+#[derive(Debug, Clone)]
+pub struct EnumDecl {
+    name: String,
+    variants: Vec<VariantDecl>,
+    span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct VariantDecl {
+    name: String,
+    fields: Vec<TypeName>,
+    span: Span,
+}
+
+// fn name(param: type, ...) [-> type] { ... }
+#[derive(Debug, Clone)]
+pub struct FunctionDecl {
+    name: String,
+    params: Vec<Parameter>,
+    return_type: Option<TypeName>,
+    body: Block,
+    span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    name: String,
+    type_name: TypeName,
+    span: Span,
+}
+
+// A type as it is written, resolved by the semantic pass.
+#[derive(Debug, Clone)]
+pub enum TypeName {
+    // a primitive, a struct or an enum
+    Named(String, Span),
+    // [element; len]
+    Array(Box<TypeName>, usize, Span),
+    // (element, ...)
+    Tuple(Vec<TypeName>, Span),
+}
+
+// The value of a block is its last expression when that has no `;`, otherwise `()`.
+#[derive(Debug, Clone, Default)]
+pub struct Block {
     statements: Vec<Statement>,
+    value: Option<Arithmetic>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Statement {
     Arithmetic(Arithmetic),
+    Let(Let),
+    Destructure(Destructure),
+    Assign(Assign),
+    Return(Return),
+    While(While),
+    For(For),
+    Break(Span),
+    Continue(Span),
+}
+
+// let [mut] name [: type] = value;
+#[derive(Debug, Clone)]
+pub struct Let {
+    name: String,
+    mutable: bool,
+    type_name: Option<TypeName>,
+    value: Arithmetic,
+    span: Span,
+}
+
+// let (pattern, ...) [: type] = value;
+// The pattern is a tuple pattern of bindings, `_` and more tuple patterns, so it
+// always matches. Lowered into a let of the value and a let of each binding.
+#[derive(Debug, Clone)]
+pub struct Destructure {
+    pattern: Pattern,
+    type_name: Option<TypeName>,
+    value: Arithmetic,
+    span: Span,
+}
+
+// place = value;
+// The target is a variable or a field or an element of one, like `p.x` or `a[i].x`.
+#[derive(Debug, Clone)]
+pub struct Assign {
+    target: Node,
+    value: Arithmetic,
+    span: Span,
+}
+
+// return [value];
+#[derive(Debug, Clone)]
+pub struct Return {
+    value: Option<Arithmetic>,
+    span: Span,
+}
+
+// while condition { ... }
+#[derive(Debug, Clone)]
+pub struct While {
+    condition: Arithmetic,
+    body: Block,
+    span: Span,
+}
+
+// for variable in iterable { ... }
+// Lowered into a while loop before the program runs.
+#[derive(Debug, Clone)]
+pub struct For {
+    variable: String,
+    iterable: Iterable,
+    body: Block,
+    span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum Iterable {
+    Range(Range),
+    // any other expression, whose type decides how it is iterated
+    Expression(Arithmetic),
+}
+
+// start..end, or start..=end when inclusive
+#[derive(Debug, Clone)]
+pub struct Range {
+    start: Arithmetic,
+    end: Arithmetic,
+    inclusive: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -21,28 +165,226 @@ pub enum Arithmetic {
     MultiTerm(Node, Operator, Node),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Operator {
     Plus,
     Minus,
     Mul,
     Div,
+    Rem,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    // short-circuiting
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
 }
 
 #[derive(Debug, Clone)]
 pub enum Node {
     Number(Number),
+    Boolean(Boolean),
+    Str(Str),
+    Char(Char),
+    Variable(Variable),
+    Call(Call),
+    Unary(Unary),
+    If(Box<If>),
+    StructLiteral(StructLiteral),
+    Field(FieldAccess),
+    Array(ArrayLiteral),
+    Repeat(ArrayRepeat),
+    Tuple(TupleLiteral),
+    Index(Index),
+    Variant(Box<VariantLiteral>),
+    Match(Box<Match>),
     Arithmetic(Box<Arithmetic>),
 }
 
 #[derive(Debug, Clone)]
 pub struct Number {
     inner: String,
+    span: Span,
+}
+
+// true or false
+#[derive(Debug, Clone)]
+pub struct Boolean {
+    value: bool,
+    span: Span,
+}
+
+// a string literal, with its escapes already replaced
+#[derive(Debug, Clone)]
+pub struct Str {
+    value: String,
+    span: Span,
+}
+
+// a char literal, with its escape already replaced
+#[derive(Debug, Clone)]
+pub struct Char {
+    value: char,
+    span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variable {
+    name: String,
+    span: Span,
+}
+
+// op operand, binding tighter than any binary operator
+#[derive(Debug, Clone)]
+pub struct Unary {
+    op: UnaryOperator,
+    operand: Box<Node>,
+    span: Span,
+}
+
+// if condition { ... } [else { ... }]
+// `else if` is an else block whose value is the inner if.
+#[derive(Debug, Clone)]
+pub struct If {
+    condition: Arithmetic,
+    then: Block,
+    otherwise: Option<Block>,
+    span: Span,
+}
+
+// Name { field: value, ... }
+#[derive(Debug, Clone)]
+pub struct StructLiteral {
+    name: String,
+    fields: Vec<FieldInit>,
+    span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldInit {
+    name: String,
+    value: Arithmetic,
+    span: Span,
+}
+
+// base.name, binding tighter than unary operators
+#[derive(Debug, Clone)]
+pub struct FieldAccess {
+    base: Box<Node>,
+    name: String,
+    span: Span,
+}
+
+// [element, ...]
+#[derive(Debug, Clone)]
+pub struct ArrayLiteral {
+    elements: Vec<Arithmetic>,
+    span: Span,
+}
+
+// (element, ...), with a `,` after an only element
+#[derive(Debug, Clone)]
+pub struct TupleLiteral {
+    elements: Vec<Arithmetic>,
+    span: Span,
+}
+
+// [value; len], len copies of value
+#[derive(Debug, Clone)]
+pub struct ArrayRepeat {
+    value: Box<Arithmetic>,
+    len: usize,
+    span: Span,
+}
+
+// base[index], binding like a field access
+#[derive(Debug, Clone)]
+pub struct Index {
+    base: Box<Node>,
+    index: Box<Arithmetic>,
+    span: Span,
+}
+
+// Enum::Variant or Enum::Variant(arg, ...)
+#[derive(Debug, Clone)]
+pub struct VariantLiteral {
+    enum_name: String,
+    name: String,
+    args: Vec<Arithmetic>,
+    span: Span,
+}
+
+// match value { pattern [if guard] => body, ... }
+// The arms are tried in order, and the first that matches gives the value.
+#[derive(Debug, Clone)]
+pub struct Match {
+    value: Arithmetic,
+    arms: Vec<MatchArm>,
+    span: Span,
+}
+
+// A body written as an expression is a block of only that value.
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pattern: Pattern,
+    guard: Option<Arithmetic>,
+    body: Block,
+    span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    // _
+    Wildcard(Span),
+    // a name, bound to the value
+    Binding(String, Span),
+    // an int, with its sign in the inner text
+    Number(Number),
+    Boolean(Boolean),
+    Char(Char),
+    // Enum::Variant or Enum::Variant(pattern, ...)
+    Variant(VariantPattern),
+    // (pattern, ...)
+    Tuple(TuplePattern),
+}
+
+#[derive(Debug, Clone)]
+pub struct VariantPattern {
+    enum_name: String,
+    name: String,
+    fields: Vec<Pattern>,
+    span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct TuplePattern {
+    fields: Vec<Pattern>,
+    span: Span,
+}
+
+// name(arg, ...)
+#[derive(Debug, Clone)]
+pub struct Call {
+    name: String,
+    args: Vec<Arithmetic>,
+    span: Span,
 }
 
 impl Program {
     pub fn new() -> Program {
         Program {
+            structs: Vec::new(),
+            enums: Vec::new(),
+            functions: Vec::new(),
             statements: Vec::new(),
         }
     }
@@ -50,158 +392,1049 @@ impl Program {
     pub fn push(&mut self, statement: Statement) {
         self.statements.push(statement)
     }
+
+    pub fn push_function(&mut self, function: FunctionDecl) {
+        self.functions.push(function)
+    }
+
+    pub fn push_struct(&mut self, decl: StructDecl) {
+        self.structs.push(decl)
+    }
+
+    pub fn push_enum(&mut self, decl: EnumDecl) {
+        self.enums.push(decl)
+    }
+
+    pub fn get_structs(&self) -> &[StructDecl] {
+        &self.structs
+    }
+
+    pub fn get_enums(&self) -> &[EnumDecl] {
+        &self.enums
+    }
+
+    pub fn get_functions(&self) -> &[FunctionDecl] {
+        &self.functions
+    }
+
+    pub fn get_statements(&self) -> &[Statement] {
+        &self.statements
+    }
+
+    pub fn has_statements(&self) -> bool {
+        !self.statements.is_empty()
+    }
 }
 
-impl Arithmetic {
-    pub fn insert_right(&mut self, newop: Operator, r_node: Node) -> Arithmetic {
-        match self {
-            Arithmetic::Term(term) => {
-                Arithmetic::MultiTerm(term.clone(), newop, r_node)
-            }
-            Arithmetic::MultiTerm(left, op, right) => {
-                Arithmetic::MultiTerm(left.clone(), op.clone(), Node::Arithmetic(Box::new(Arithmetic::MultiTerm(right.clone(), newop, r_node))))
-            }
+impl StructDecl {
+    pub fn new(name: String, fields: Vec<FieldDecl>, span: Span) -> StructDecl {
+        StructDecl {
+            name,
+            fields,
+            span,
         }
     }
 
-    pub fn insert_top(&mut self, newop: Operator, r_node: Node) -> Arithmetic {
-        match self {
-            Arithmetic::Term(term) => {
-                Arithmetic::MultiTerm(term.clone(), newop, r_node)
-            }
-            Arithmetic::MultiTerm(left, op, right) => {
-                Arithmetic::MultiTerm(Node::Arithmetic(Box::new(self.clone())), newop, r_node)
-            }
-        }
+    pub fn get_name(&self) -> &str {
+        &self.name
     }
 
-    pub fn get_op_type(&self) -> Option<Operator> {
-        match self {
-            Arithmetic::Term(_) => {
-                None
-            }
-            Arithmetic::MultiTerm(_, op, _) => {
-                Some(*op)
-            }
+    pub fn get_fields(&self) -> &[FieldDecl] {
+        &self.fields
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl FieldDecl {
+    pub fn new(name: String, type_name: TypeName, span: Span) -> FieldDecl {
+        FieldDecl {
+            name,
+            type_name,
+            span,
         }
     }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_type_name(&self) -> &TypeName {
+        &self.type_name
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
 }
 
-impl Operator {
-    
+impl EnumDecl {
+    pub fn new(name: String, variants: Vec<VariantDecl>, span: Span) -> EnumDecl {
+        EnumDecl {
+            name,
+            variants,
+            span,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_variants(&self) -> &[VariantDecl] {
+        &self.variants
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
 }
 
-impl Number {
-    pub fn new(inner: String) -> Number {
-        Number {
-            inner: inner
+impl VariantDecl {
+    pub fn new(name: String, fields: Vec<TypeName>, span: Span) -> VariantDecl {
+        VariantDecl {
+            name,
+            fields,
+            span,
         }
     }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_fields(&self) -> &[TypeName] {
+        &self.fields
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
 }
 
-impl Ast for Program {
-    fn check_semantic(&mut self) {
+impl FunctionDecl {
+    pub fn new(name: String, params: Vec<Parameter>, return_type: Option<TypeName>, body: Block, span: Span) -> FunctionDecl {
+        FunctionDecl {
+            name,
+            params,
+            return_type,
+            body,
+            span,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_params(&self) -> &[Parameter] {
+        &self.params
+    }
+
+    pub fn get_return_type(&self) -> Option<&TypeName> {
+        self.return_type.as_ref()
+    }
 
+    pub fn get_body(&self) -> &Block {
+        &self.body
     }
 
-    fn generate_code(&mut self) {
-        println!("  .text");
-        println!(".global _main");
-        println!("");
-        println!("_main:");
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
 
-        for statement in self.statements.iter_mut() {
-            statement.generate_code();
+impl Parameter {
+    pub fn new(name: String, type_name: TypeName, span: Span) -> Parameter {
+        Parameter {
+            name,
+            type_name,
+            span,
         }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
 
-        println!("  ret");
+    pub fn get_type_name(&self) -> &TypeName {
+        &self.type_name
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
     }
 }
 
-impl Ast for Statement {
-    fn check_semantic(&mut self) {
+impl TypeName {
+    pub fn get_span(&self) -> Span {
+        match self {
+            TypeName::Named(_, span) | TypeName::Array(_, _, span) | TypeName::Tuple(_, span) => *span,
+        }
+    }
 
+    // The names of the primitives, structs and enums the type is made of.
+    pub fn get_names(&self) -> Vec<&str> {
+        match self {
+            TypeName::Named(name, _) => vec![name.as_str()],
+            TypeName::Array(element, _, _) => element.get_names(),
+            TypeName::Tuple(elements, _) => elements.iter().flat_map(TypeName::get_names).collect(),
+        }
     }
+}
 
-    fn generate_code(&mut self) {
+// as it is written, like `[int; 3]` or `(int, bool)`
+impl fmt::Display for TypeName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Statement::Arithmetic(arithmetic) => {
-                arithmetic.generate_code();
+            TypeName::Named(name, _) => write!(f, "{}", name),
+            TypeName::Array(element, len, _) => write!(f, "[{}; {}]", element, len),
+            TypeName::Tuple(elements, _) => {
+                let elements: Vec<String> = elements.iter().map(|element| element.to_string()).collect();
+                write!(f, "{}", tuple(&elements))
             }
         }
-        println!("  pop %rax");
     }
 }
 
-impl Ast for Arithmetic {
-    fn check_semantic(&mut self) {
+impl Block {
+    pub fn new() -> Block {
+        Block {
+            statements: Vec::new(),
+            value: None,
+        }
+    }
+
+    pub fn push(&mut self, statement: Statement) {
+        self.statements.push(statement)
+    }
+
+    pub fn set_value(&mut self, value: Arithmetic) {
+        self.value = Some(value)
+    }
+
+    pub fn get_statements(&self) -> &[Statement] {
+        &self.statements
+    }
 
+    pub fn get_value(&self) -> Option<&Arithmetic> {
+        self.value.as_ref()
     }
+}
 
-    fn generate_code(&mut self) {
+impl Statement {
+    pub fn get_span(&self) -> Span {
         match self {
-            Arithmetic::Term(term) => {
-                term.generate_code();
-            }
-            Arithmetic::MultiTerm(left, op, right) => {
-                left.generate_code();
-                right.generate_code();
-                op.generate_code();
-            }
+            Statement::Arithmetic(arithmetic) => arithmetic.get_span(),
+            Statement::Let(l) => l.get_span(),
+            Statement::Destructure(d) => d.get_span(),
+            Statement::Assign(assign) => assign.get_span(),
+            Statement::Return(r) => r.get_span(),
+            Statement::While(w) => w.get_span(),
+            Statement::For(f) => f.get_span(),
+            Statement::Break(span) | Statement::Continue(span) => *span,
         }
     }
 }
 
-impl Ast for Node {
-    fn check_semantic(&mut self) {
+impl Let {
+    pub fn new(name: String, mutable: bool, type_name: Option<TypeName>, value: Arithmetic, span: Span) -> Let {
+        Let {
+            name,
+            mutable,
+            type_name,
+            value,
+            span,
+        }
+    }
 
+    pub fn get_name(&self) -> &str {
+        &self.name
     }
 
-    fn generate_code(&mut self) {
-        match self {
-            Node::Arithmetic(arithmetic) => {
-                arithmetic.generate_code();
-            }
-            Node::Number(number) => {
-                number.generate_code();
-            }
+    pub fn is_mutable(&self) -> bool {
+        self.mutable
+    }
+
+    pub fn get_type_name(&self) -> Option<&TypeName> {
+        self.type_name.as_ref()
+    }
+
+    pub fn get_value(&self) -> &Arithmetic {
+        &self.value
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Destructure {
+    pub fn new(pattern: Pattern, type_name: Option<TypeName>, value: Arithmetic, span: Span) -> Destructure {
+        Destructure {
+            pattern,
+            type_name,
+            value,
+            span,
         }
     }
+
+    pub fn get_pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    pub fn get_type_name(&self) -> Option<&TypeName> {
+        self.type_name.as_ref()
+    }
+
+    pub fn get_value(&self) -> &Arithmetic {
+        &self.value
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
 }
 
-impl Ast for Operator {
-    fn check_semantic(&mut self) {
+impl Assign {
+    pub fn new(target: Node, value: Arithmetic, span: Span) -> Assign {
+        Assign {
+            target,
+            value,
+            span,
+        }
+    }
 
+    pub fn get_target(&self) -> &Node {
+        &self.target
     }
 
-    fn generate_code(&mut self) {
-        println!("  pop  %rbx");
-        println!("  pop  %rax");
-        match self {
-            Operator::Plus => {
-                println!("  add %rbx, %rax");
-            }
-            Operator::Minus => {
-                println!("  sub %rbx, %rax");
-            }
-            Operator::Mul => {
-                println!("  mul %rbx");
-            }
-            Operator::Div => {
-                println!("  div %rbx");
-            }
+    pub fn get_value(&self) -> &Arithmetic {
+        &self.value
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Return {
+    pub fn new(value: Option<Arithmetic>, span: Span) -> Return {
+        Return {
+            value,
+            span,
         }
+    }
+
+    pub fn get_value(&self) -> Option<&Arithmetic> {
+        self.value.as_ref()
+    }
 
-        println!("  push %rax");
+    pub fn get_span(&self) -> Span {
+        self.span
     }
 }
 
-impl Ast for Number {
-    fn check_semantic(&mut self) {
+impl While {
+    pub fn new(condition: Arithmetic, body: Block, span: Span) -> While {
+        While {
+            condition,
+            body,
+            span,
+        }
+    }
+
+    pub fn get_condition(&self) -> &Arithmetic {
+        &self.condition
+    }
 
+    pub fn get_body(&self) -> &Block {
+        &self.body
     }
-    
-    fn generate_code(&mut self) {
-        println!("  push ${}", self.inner);
+
+    pub fn get_span(&self) -> Span {
+        self.span
     }
-}
\ No newline at end of file
+}
+
+impl For {
+    pub fn new(variable: String, iterable: Iterable, body: Block, span: Span) -> For {
+        For {
+            variable,
+            iterable,
+            body,
+            span,
+        }
+    }
+
+    pub fn get_variable(&self) -> &str {
+        &self.variable
+    }
+
+    pub fn get_iterable(&self) -> &Iterable {
+        &self.iterable
+    }
+
+    pub fn get_body(&self) -> &Block {
+        &self.body
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Iterable {
+    pub fn get_span(&self) -> Span {
+        match self {
+            Iterable::Range(range) => range.get_span(),
+            Iterable::Expression(expression) => expression.get_span(),
+        }
+    }
+}
+
+impl Range {
+    pub fn new(start: Arithmetic, end: Arithmetic, inclusive: bool) -> Range {
+        Range {
+            start,
+            end,
+            inclusive,
+        }
+    }
+
+    pub fn get_start(&self) -> &Arithmetic {
+        &self.start
+    }
+
+    pub fn get_end(&self) -> &Arithmetic {
+        &self.end
+    }
+
+    pub fn is_inclusive(&self) -> bool {
+        self.inclusive
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.start.get_span().to(self.end.get_span())
+    }
+}
+
+impl Arithmetic {
+    // self as an operand of a bigger expression
+    pub fn into_node(self) -> Node {
+        match self {
+            Arithmetic::Term(node) => node,
+            Arithmetic::MultiTerm(_, _, _) => Node::Arithmetic(Box::new(self)),
+        }
+    }
+
+    pub fn get_span(&self) -> Span {
+        match self {
+            Arithmetic::Term(node) => node.get_span(),
+            Arithmetic::MultiTerm(left, _, right) => left.get_span().to(right.get_span()),
+        }
+    }
+
+    pub fn get_op_type(&self) -> Option<Operator> {
+        match self {
+            Arithmetic::Term(_) => {
+                None
+            }
+            Arithmetic::MultiTerm(_, op, _) => {
+                Some(*op)
+            }
+        }
+    }
+
+    // The value of a constant expression as the bits of a register, unless computing it
+    // overflows its type or divides by zero, which is left to the runtime checks.
+    // Bools are 1 and 0 and chars their code.
+    pub fn fold(&self) -> Option<i64> {
+        self.fold_exact().map(|n| n as i64)
+    }
+
+    fn fold_exact(&self) -> Option<i128> {
+        match self {
+            Arithmetic::Term(node) => node.fold_exact(),
+            Arithmetic::MultiTerm(left, op, right) => {
+                let (left_value, right_value) = (left.fold_exact()?, right.fold_exact()?);
+                let n = match op {
+                    Operator::Plus => left_value + right_value,
+                    Operator::Minus => left_value - right_value,
+                    Operator::Mul => left_value.checked_mul(right_value)?,
+                    Operator::Div => left_value.checked_div(right_value)?,
+                    Operator::Rem => left_value.checked_rem(right_value)?,
+                    Operator::Equal => return Some((left_value == right_value) as i128),
+                    Operator::NotEqual => return Some((left_value != right_value) as i128),
+                    Operator::Less => return Some((left_value < right_value) as i128),
+                    Operator::LessEqual => return Some((left_value <= right_value) as i128),
+                    Operator::Greater => return Some((left_value > right_value) as i128),
+                    Operator::GreaterEqual => return Some((left_value >= right_value) as i128),
+                    Operator::And => return Some((left_value != 0 && right_value != 0) as i128),
+                    Operator::Or => return Some((left_value != 0 || right_value != 0) as i128),
+                };
+                let int_type = left.int_type().or_else(|| right.int_type()).unwrap_or(IntType::I64);
+                Some(n).filter(|n| int_type.contains(*n))
+            }
+        }
+    }
+
+    // The type a suffix gives a constant expression, from the first literal that has one.
+    fn int_type(&self) -> Option<IntType> {
+        match self {
+            Arithmetic::Term(node) => node.int_type(),
+            Arithmetic::MultiTerm(left, _, right) => left.int_type().or_else(|| right.int_type()),
+        }
+    }
+}
+
+impl Node {
+    pub fn get_span(&self) -> Span {
+        match self {
+            Node::Number(number) => number.get_span(),
+            Node::Boolean(boolean) => boolean.get_span(),
+            Node::Str(s) => s.get_span(),
+            Node::Char(c) => c.get_span(),
+            Node::Variable(variable) => variable.get_span(),
+            Node::Call(call) => call.get_span(),
+            Node::Unary(unary) => unary.get_span(),
+            Node::If(i) => i.get_span(),
+            Node::StructLiteral(literal) => literal.get_span(),
+            Node::Field(field) => field.get_span(),
+            Node::Array(array) => array.get_span(),
+            Node::Tuple(tuple) => tuple.get_span(),
+            Node::Repeat(repeat) => repeat.get_span(),
+            Node::Index(index) => index.get_span(),
+            Node::Variant(variant) => variant.get_span(),
+            Node::Match(m) => m.get_span(),
+            Node::Arithmetic(arithmetic) => arithmetic.get_span(),
+        }
+    }
+
+    // Whether the node can be assigned to: a variable, or a field or an element of one.
+    pub fn is_place(&self) -> bool {
+        match self {
+            Node::Variable(_) => true,
+            Node::Field(field) => field.get_base().is_place(),
+            Node::Index(index) => index.get_base().is_place(),
+            _ => false,
+        }
+    }
+
+    pub fn fold(&self) -> Option<i64> {
+        self.fold_exact().map(|n| n as i64)
+    }
+
+    fn fold_exact(&self) -> Option<i128> {
+        match self {
+            Node::Number(number) => number.get_value(),
+            Node::Boolean(b) => Some(b.get_value() as i128),
+            Node::Char(c) => Some(c.get_value() as i128),
+            Node::Unary(unary) => match unary.get_op() {
+                UnaryOperator::Negate => {
+                    let n = -unary.get_operand().fold_exact()?;
+                    Some(n).filter(|n| self.int_type().unwrap_or(IntType::I64).contains(*n))
+                }
+                UnaryOperator::Not => Some(unary.get_operand().fold_exact()? ^ 1),
+            },
+            Node::Arithmetic(arithmetic) => arithmetic.fold_exact(),
+            Node::Str(_) | Node::Variable(_) | Node::Call(_) | Node::If(_) | Node::StructLiteral(_)
+                | Node::Field(_) | Node::Array(_) | Node::Repeat(_) | Node::Tuple(_) | Node::Index(_)
+                | Node::Variant(_) | Node::Match(_) => None,
+        }
+    }
+
+    fn int_type(&self) -> Option<IntType> {
+        match self {
+            Node::Number(number) => number.get_suffix(),
+            Node::Unary(unary) => unary.get_operand().int_type(),
+            Node::Arithmetic(arithmetic) => arithmetic.int_type(),
+            _ => None,
+        }
+    }
+}
+
+impl Number {
+    pub fn new(inner: String, span: Span) -> Number {
+        Number {
+            inner,
+            span,
+        }
+    }
+
+    pub fn get_inner(&self) -> &str {
+        &self.inner
+    }
+
+    // The value written, negative for a pattern like `-1`, or None when it is more than
+    // any integer type holds.
+    pub fn get_value(&self) -> Option<i128> {
+        let (negative, text) = match self.inner.strip_prefix('-') {
+            Some(text) => (true, text),
+            None => (false, &self.inner[..]),
+        };
+        let (radix, digits, _) = tokenizer::split_number(text);
+        let digits: String = digits.chars().filter(|c| *c != '_').collect();
+        let value = u64::from_str_radix(&digits, radix).ok()? as i128;
+
+        Some(if negative { -value } else { value })
+    }
+
+    pub fn get_suffix(&self) -> Option<IntType> {
+        let (_, _, suffix) = tokenizer::split_number(self.inner.trim_start_matches('-'));
+        IntType::from_suffix(suffix)
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Boolean {
+    pub fn new(value: bool, span: Span) -> Boolean {
+        Boolean {
+            value,
+            span,
+        }
+    }
+
+    pub fn get_value(&self) -> bool {
+        self.value
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Str {
+    pub fn new(value: String, span: Span) -> Str {
+        Str {
+            value,
+            span,
+        }
+    }
+
+    pub fn get_value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Char {
+    pub fn new(value: char, span: Span) -> Char {
+        Char {
+            value,
+            span,
+        }
+    }
+
+    pub fn get_value(&self) -> char {
+        self.value
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Variable {
+    pub fn new(name: String, span: Span) -> Variable {
+        Variable {
+            name,
+            span,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Unary {
+    pub fn new(op: UnaryOperator, operand: Node, span: Span) -> Unary {
+        Unary {
+            op,
+            operand: Box::new(operand),
+            span,
+        }
+    }
+
+    pub fn get_op(&self) -> UnaryOperator {
+        self.op
+    }
+
+    pub fn get_operand(&self) -> &Node {
+        &self.operand
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl If {
+    pub fn new(condition: Arithmetic, then: Block, otherwise: Option<Block>, span: Span) -> If {
+        If {
+            condition,
+            then,
+            otherwise,
+            span,
+        }
+    }
+
+    pub fn get_condition(&self) -> &Arithmetic {
+        &self.condition
+    }
+
+    pub fn get_then(&self) -> &Block {
+        &self.then
+    }
+
+    pub fn get_otherwise(&self) -> Option<&Block> {
+        self.otherwise.as_ref()
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl StructLiteral {
+    pub fn new(name: String, fields: Vec<FieldInit>, span: Span) -> StructLiteral {
+        StructLiteral {
+            name,
+            fields,
+            span,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_fields(&self) -> &[FieldInit] {
+        &self.fields
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl FieldInit {
+    pub fn new(name: String, value: Arithmetic, span: Span) -> FieldInit {
+        FieldInit {
+            name,
+            value,
+            span,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_value(&self) -> &Arithmetic {
+        &self.value
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl FieldAccess {
+    pub fn new(base: Node, name: String, span: Span) -> FieldAccess {
+        FieldAccess {
+            base: Box::new(base),
+            name,
+            span,
+        }
+    }
+
+    pub fn get_base(&self) -> &Node {
+        &self.base
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl ArrayLiteral {
+    pub fn new(elements: Vec<Arithmetic>, span: Span) -> ArrayLiteral {
+        ArrayLiteral {
+            elements,
+            span,
+        }
+    }
+
+    pub fn get_elements(&self) -> &[Arithmetic] {
+        &self.elements
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl TupleLiteral {
+    pub fn new(elements: Vec<Arithmetic>, span: Span) -> TupleLiteral {
+        TupleLiteral {
+            elements,
+            span,
+        }
+    }
+
+    pub fn get_elements(&self) -> &[Arithmetic] {
+        &self.elements
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl ArrayRepeat {
+    pub fn new(value: Arithmetic, len: usize, span: Span) -> ArrayRepeat {
+        ArrayRepeat {
+            value: Box::new(value),
+            len,
+            span,
+        }
+    }
+
+    pub fn get_value(&self) -> &Arithmetic {
+        &self.value
+    }
+
+    pub fn get_len(&self) -> usize {
+        self.len
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Index {
+    pub fn new(base: Node, index: Arithmetic, span: Span) -> Index {
+        Index {
+            base: Box::new(base),
+            index: Box::new(index),
+            span,
+        }
+    }
+
+    pub fn get_base(&self) -> &Node {
+        &self.base
+    }
+
+    pub fn get_index(&self) -> &Arithmetic {
+        &self.index
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl VariantLiteral {
+    pub fn new(enum_name: String, name: String, args: Vec<Arithmetic>, span: Span) -> VariantLiteral {
+        VariantLiteral {
+            enum_name,
+            name,
+            args,
+            span,
+        }
+    }
+
+    pub fn get_enum_name(&self) -> &str {
+        &self.enum_name
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_args(&self) -> &[Arithmetic] {
+        &self.args
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Match {
+    pub fn new(value: Arithmetic, arms: Vec<MatchArm>, span: Span) -> Match {
+        Match {
+            value,
+            arms,
+            span,
+        }
+    }
+
+    pub fn get_value(&self) -> &Arithmetic {
+        &self.value
+    }
+
+    pub fn get_arms(&self) -> &[MatchArm] {
+        &self.arms
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl MatchArm {
+    pub fn new(pattern: Pattern, guard: Option<Arithmetic>, body: Block, span: Span) -> MatchArm {
+        MatchArm {
+            pattern,
+            guard,
+            body,
+            span,
+        }
+    }
+
+    pub fn get_pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    pub fn get_guard(&self) -> Option<&Arithmetic> {
+        self.guard.as_ref()
+    }
+
+    pub fn get_body(&self) -> &Block {
+        &self.body
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Pattern {
+    pub fn get_span(&self) -> Span {
+        match self {
+            Pattern::Wildcard(span) | Pattern::Binding(_, span) => *span,
+            Pattern::Number(number) => number.get_span(),
+            Pattern::Boolean(b) => b.get_span(),
+            Pattern::Char(c) => c.get_span(),
+            Pattern::Variant(variant) => variant.get_span(),
+            Pattern::Tuple(tuple) => tuple.get_span(),
+        }
+    }
+
+    // Whether the pattern matches every value of its type.
+    pub fn is_irrefutable(&self) -> bool {
+        match self {
+            Pattern::Wildcard(_) | Pattern::Binding(_, _) => true,
+            Pattern::Tuple(tuple) => tuple.fields.iter().all(Pattern::is_irrefutable),
+            Pattern::Number(_) | Pattern::Boolean(_) | Pattern::Char(_) | Pattern::Variant(_) => false,
+        }
+    }
+}
+
+// as it is written, like `Shape::Rect(w, _)`
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Wildcard(_) => write!(f, "_"),
+            Pattern::Binding(name, _) => write!(f, "{}", name),
+            Pattern::Number(number) => write!(f, "{}", number.get_inner()),
+            Pattern::Boolean(b) => write!(f, "{}", b.get_value()),
+            Pattern::Char(c) => write!(f, "{:?}", c.get_value()),
+            Pattern::Variant(variant) if variant.fields.is_empty() => write!(f, "{}::{}", variant.enum_name, variant.name),
+            Pattern::Variant(variant) => {
+                let fields: Vec<String> = variant.fields.iter().map(|field| field.to_string()).collect();
+                write!(f, "{}::{}({})", variant.enum_name, variant.name, fields.join(", "))
+            }
+            Pattern::Tuple(pattern) => {
+                let fields: Vec<String> = pattern.fields.iter().map(|field| field.to_string()).collect();
+                write!(f, "{}", tuple(&fields))
+            }
+        }
+    }
+}
+
+impl TuplePattern {
+    pub fn new(fields: Vec<Pattern>, span: Span) -> TuplePattern {
+        TuplePattern {
+            fields,
+            span,
+        }
+    }
+
+    pub fn get_fields(&self) -> &[Pattern] {
+        &self.fields
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl VariantPattern {
+    pub fn new(enum_name: String, name: String, fields: Vec<Pattern>, span: Span) -> VariantPattern {
+        VariantPattern {
+            enum_name,
+            name,
+            fields,
+            span,
+        }
+    }
+
+    pub fn get_enum_name(&self) -> &str {
+        &self.enum_name
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_fields(&self) -> &[Pattern] {
+        &self.fields
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Call {
+    pub fn new(name: String, args: Vec<Arithmetic>, span: Span) -> Call {
+        Call {
+            name,
+            args,
+            span,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_args(&self) -> &[Arithmetic] {
+        &self.args
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
@@ -0,0 +1,44 @@
+use std::ops::RangeInclusive;
+
+// Functions every program can call without declaring them. The interpreter
+// and each backend implement them on their own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Builtin {
+    // print(value) writes an integer of any type, a bool, a char or a str to stdout
+    Print,
+    // println(value) ends the line after it, println() only ends it
+    Println,
+    // len(value) is the number of elements of an array, or of bytes of a str
+    Len,
+}
+
+impl Builtin {
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        match name {
+            "print" => Some(Builtin::Print),
+            "println" => Some(Builtin::Println),
+            "len" => Some(Builtin::Len),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Builtin::Print => "print",
+            Builtin::Println => "println",
+            Builtin::Len => "len",
+        }
+    }
+
+    // How many arguments it takes.
+    pub fn get_arity(&self) -> RangeInclusive<usize> {
+        match self {
+            Builtin::Print | Builtin::Len => 1..=1,
+            Builtin::Println => 0..=1,
+        }
+    }
+}
+
+// The C library functions the native runtime calls. Functions are global symbols,
+// so a program defining one of these would take the place of the C library's.
+pub const RUNTIME_FUNCTIONS: [&str; 2] = ["write", "exit"];
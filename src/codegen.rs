@@ -0,0 +1,1594 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{ self, Write };
+use std::rc::Rc;
+use super::tokenizer::Span;
+use super::ast::{
+    Program, FunctionDecl, Block, Statement, While, Arithmetic, Node, Str,
+    Operator, UnaryOperator, Unary, If, StructLiteral, FieldAccess, ArrayLiteral,
+    ArrayRepeat, Index, Call, TypeName, VariantLiteral, Match, Pattern, TupleLiteral,
+};
+use super::builtins::Builtin;
+use super::types::{ Type, IntType, StructType, EnumType, Variant, Field, FunctionType, align_to };
+
+// A code generator for one target. It walks the checked and lowered AST and writes
+// the output into out, so the AST itself knows nothing about targets.
+pub trait Backend {
+    fn generate(&mut self, program: &Program, out: &mut dyn Write) -> io::Result<()>;
+}
+
+// x86-64 assembly in AT&T syntax, evaluating expressions on the stack.
+// Bools are 1 and 0. A struct is laid out like in C in a slot of the stack frame,
+// and evaluates to its address, so it is copied wherever it is stored. No struct
+// outlives its frame, so none goes on the heap: a returned one is copied into a slot
+// of the caller, whose address the caller passes as a hidden first argument.
+// A str is handled like a struct of the address of its bytes, which are in .rodata,
+// and their length, and an array like a struct of its elements. An enum value is
+// its tag, the index of its variant, in 8 bytes, followed by the fields of the
+// variant, and a match jumps on the tag through a table. A tuple is laid out like
+// a struct of its elements, but one of up to 16 bytes is returned in %rax and %rdx,
+// as System V does, and copied into a slot by the caller. Indexing checks the
+// bounds at runtime, except for constant indexes from -O1 on, which the semantic pass
+// has already checked. print writes to stdout right away, without buffering.
+// An integer or a char takes as many bytes as its type in memory, and all 64 bits of
+// a register or a stack entry, sign-extended for signed types and zero-extended for
+// the others. Narrower ones are computed in 64 bits and narrowed back to their type.
+// Integers wrap on overflow, unless overflow_checks is on, which aborts the program
+// instead. Division by zero always aborts.
+#[derive(Debug, Default)]
+pub struct X86_64 {
+    // source file named in runtime error messages
+    file: String,
+    overflow_checks: bool,
+    // 0 translates the program as it is, 1 and up compute constant expressions at compile time
+    optimization: u8,
+}
+
+// Exit status of a program aborted by a runtime error
+pub const PANIC_STATUS: i32 = 101;
+
+// System V AMD64 integer argument registers
+const ARGUMENT_REGISTERS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+// Stack slots of the local variables of one function with their types, addressed
+// as -offset(%rbp), and of the structs its expressions make along the way.
+// depth counts the values pushed on top of them, to keep calls 16-byte aligned.
+#[derive(Debug, Default)]
+struct StackFrame {
+    function: String,
+    slots: Vec<(String, usize, Type)>,
+    size: usize,
+    depth: usize,
+    // the slot holding where a returned struct goes, with its type
+    out: Option<(usize, Type)>,
+    // the slot a tuple returned in registers is gathered in, with its type
+    gathered: Option<(usize, Type)>,
+}
+
+// Where break and continue jump to in a loop, and the stack depth there.
+#[derive(Debug)]
+struct Loop {
+    start: String,
+    end: String,
+    depth: usize,
+}
+
+struct Generator<'a> {
+    out: &'a mut dyn Write,
+    frame: StackFrame,
+    file: &'a str,
+    overflow_checks: bool,
+    optimization: u8,
+    // the layouts of the structs and enums and the signatures of the functions
+    structs: HashMap<String, Rc<StructType>>,
+    enums: HashMap<String, Rc<EnumType>>,
+    functions: HashMap<String, FunctionType>,
+    // numbers the local labels
+    labels: usize,
+    // label and message of every runtime error, emitted after the code
+    traps: Vec<(String, String)>,
+    // label and contents of every string literal
+    strings: Vec<(String, String)>,
+    // whether the program prints, which needs the routines of generate_runtime
+    prints: bool,
+    // the loops around the code being generated, innermost last
+    loops: Vec<Loop>,
+}
+
+impl X86_64 {
+    pub fn new() -> X86_64 {
+        X86_64 {
+            file: String::new(),
+            overflow_checks: false,
+            optimization: 0u8,
+        }
+    }
+
+    pub fn with_file(mut self, file: &str) -> X86_64 {
+        self.file = file.to_owned();
+        self
+    }
+
+    pub fn with_overflow_checks(mut self, overflow_checks: bool) -> X86_64 {
+        self.overflow_checks = overflow_checks;
+        self
+    }
+
+    pub fn with_optimization(mut self, optimization: u8) -> X86_64 {
+        self.optimization = optimization;
+        self
+    }
+}
+
+impl Backend for X86_64 {
+    fn generate(&mut self, program: &Program, out: &mut dyn Write) -> io::Result<()> {
+        let mut generator = Generator {
+            out,
+            frame: StackFrame::default(),
+            file: &self.file,
+            overflow_checks: self.overflow_checks,
+            optimization: self.optimization,
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            functions: HashMap::new(),
+            labels: 0usize,
+            traps: Vec::new(),
+            strings: Vec::new(),
+            prints: false,
+            loops: Vec::new(),
+        };
+
+        generator.generate_program(program)
+    }
+}
+
+impl StackFrame {
+    fn new(function: &str) -> StackFrame {
+        StackFrame {
+            function: function.to_owned(),
+            slots: Vec::new(),
+            size: 0usize,
+            depth: 0usize,
+            out: None,
+            gathered: None,
+        }
+    }
+
+    // The offset of size more bytes below the slots so far, 8-byte aligned.
+    fn reserve(&mut self, size: usize) -> usize {
+        self.size = align_to(self.size + size, 8);
+        self.size
+    }
+
+    // Every let gets its own slot, so shadowed variables keep their values.
+    fn allocate(&mut self, name: &str, slot_type: Type) -> usize {
+        let offset = self.reserve(slot_type.size());
+        self.slots.push((name.to_owned(), offset, slot_type));
+        offset
+    }
+
+    // The latest slot wins, which is what shadowing needs.
+    fn lookup(&self, name: &str) -> (usize, Type) {
+        self.slots.iter().rev().find(|(slot, _, _)| slot == name).map(|(_, offset, slot_type)| (*offset, slot_type.clone()))
+            .expect("the semantic pass resolves every variable")
+    }
+}
+
+// The type of a constant expression, which fold computed. Lowering gives every
+// literal a suffix, so arithmetic has the type of its left operand.
+fn folded_type(arithmetic: &Arithmetic) -> Type {
+    match arithmetic {
+        Arithmetic::MultiTerm(left, op, _) => match op {
+            Operator::Plus | Operator::Minus | Operator::Mul | Operator::Div | Operator::Rem => folded_node_type(left),
+            _ => Type::Bool,
+        },
+        Arithmetic::Term(node) => folded_node_type(node),
+    }
+}
+
+fn folded_node_type(node: &Node) -> Type {
+    match node {
+        Node::Number(number) => Type::Int(number.get_suffix().unwrap_or(IntType::I64)),
+        Node::Boolean(_) => Type::Bool,
+        Node::Char(_) => Type::Char,
+        Node::Unary(unary) => match unary.get_op() {
+            UnaryOperator::Negate => folded_node_type(unary.get_operand()),
+            UnaryOperator::Not => Type::Bool,
+        },
+        Node::Arithmetic(arithmetic) => folded_type(arithmetic),
+        _ => unreachable!("only literals and operators on them fold"),
+    }
+}
+
+// The integer type of the operands of an operator, None for bools and chars.
+fn int_type_of(operand_type: &Type) -> Option<IntType> {
+    match operand_type {
+        Type::Int(int_type) => Some(*int_type),
+        _ => None,
+    }
+}
+
+// The offset and the type of the field name of struct_type, or of a tuple.
+fn field_of(struct_type: &Type, name: &str) -> (usize, Type) {
+    match struct_type {
+        Type::Struct(struct_type) => {
+            let field = struct_type.get_field(name).expect("the semantic pass resolves every field");
+            let offset = struct_type.offset_of(name).expect("the semantic pass resolves every field");
+            (offset, field.get_type().clone())
+        }
+        Type::Tuple(elements) => {
+            let index: usize = name.parse().expect("the semantic pass resolves every field");
+            (struct_type.offset_of(index), elements[index].clone())
+        }
+        _ => unreachable!("the semantic pass only lets structs and tuples have fields"),
+    }
+}
+
+// Whether a value of return_type comes back in %rax and %rdx rather than through the hidden argument.
+fn in_registers(return_type: &Type) -> bool {
+    matches!(return_type, Type::Tuple(_)) && return_type.size() <= 16
+}
+
+// The low size bytes of one of the registers %rax, %rcx, %rdx and %rdi, named like %rax.
+fn register(name: &str, size: usize) -> String {
+    let letter = &name[2..3];
+    match (size, name) {
+        (1, "%rdi") => "%dil".to_owned(),
+        (1, _) => format!("%{}l", letter),
+        (2, "%rdi") => "%di".to_owned(),
+        (2, _) => format!("%{}x", letter),
+        (4, "%rdi") => "%edi".to_owned(),
+        (4, _) => format!("%e{}x", letter),
+        _ => name.to_owned(),
+    }
+}
+
+// text as the contents of an assembler string literal
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            b' '..=b'~' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+
+    escaped
+}
+
+impl<'a> Generator<'a> {
+    fn push(&mut self, operand: &str) -> io::Result<()> {
+        self.frame.depth += 1;
+        writeln!(self.out, "  push {}", operand)
+    }
+
+    fn pop(&mut self, operand: &str) -> io::Result<()> {
+        self.frame.depth -= 1;
+        writeln!(self.out, "  pop {}", operand)
+    }
+
+    fn generate_program(&mut self, program: &Program) -> io::Result<()> {
+        for decl in program.get_structs() {
+            self.declare(decl.get_name(), program);
+        }
+        for decl in program.get_enums() {
+            self.declare(decl.get_name(), program);
+        }
+        for function in program.get_functions() {
+            let params = function.get_params().iter().map(|param| self.resolve(param.get_type_name())).collect();
+            let return_type = function.get_return_type().map_or(Type::Unit, |type_name| self.resolve(type_name));
+            self.functions.insert(function.get_name().to_owned(), FunctionType::new(params, return_type));
+        }
+
+        writeln!(self.out, "  .text")?;
+
+        for function in program.get_functions() {
+            self.generate_function(function)?;
+        }
+
+        // the top-level statements make up main, the semantic pass rejects a user defined one alongside.
+        // main returns the value of the last statement, which becomes the exit status.
+        if program.has_statements() {
+            self.frame = StackFrame::new("main");
+            self.generate_prologue()?;
+
+            for statement in program.get_statements() {
+                self.generate_statement(statement)?;
+            }
+
+            self.generate_epilogue()?;
+        }
+
+        self.generate_traps()?;
+        self.generate_runtime()?;
+        self.generate_strings()?;
+
+        // the stack does not need to be executable
+        writeln!(self.out)?;
+        writeln!(self.out, "  .section .note.GNU-stack,\"\",@progbits")
+    }
+
+    // Each trap loads its message and jumps to .L.panic,
+    // which prints it to stderr and exits with PANIC_STATUS.
+    fn generate_traps(&mut self) -> io::Result<()> {
+        if self.traps.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(self.out)?;
+        for (label, message) in self.traps.iter() {
+            writeln!(self.out, "{}:", label)?;
+            writeln!(self.out, "  lea {}.message(%rip), %rsi", label)?;
+            writeln!(self.out, "  mov ${}, %rdx", message.len())?;
+            writeln!(self.out, "  jmp .L.panic")?;
+        }
+
+        // the trap may come from any stack depth
+        writeln!(self.out, ".L.panic:")?;
+        writeln!(self.out, "  and $-16, %rsp")?;
+        writeln!(self.out, "  mov $2, %rdi")?;
+        writeln!(self.out, "  call write")?;
+        writeln!(self.out, "  mov ${}, %rdi", PANIC_STATUS)?;
+        writeln!(self.out, "  call exit")?;
+
+        writeln!(self.out)?;
+        writeln!(self.out, "  .section .rodata")?;
+        for (label, message) in self.traps.iter() {
+            writeln!(self.out, "{}.message:", label)?;
+            writeln!(self.out, "  .ascii \"{}\"", escape(message))?;
+        }
+
+        Ok(())
+    }
+
+    // print and println call these with the value in %rdi, a str as its address.
+    // They may be called at any stack depth, so they align the stack for write themselves.
+    fn generate_runtime(&mut self) -> io::Result<()> {
+        if !self.prints {
+            return Ok(());
+        }
+
+        writeln!(self.out)?;
+        writeln!(self.out, "  .text")?;
+        writeln!(self.out, ".L.print.str:")?;
+        writeln!(self.out, "  mov 8(%rdi), %rdx")?;
+        writeln!(self.out, "  mov (%rdi), %rsi")?;
+        writeln!(self.out, "  jmp .L.print.write")?;
+
+        writeln!(self.out, ".L.print.bool:")?;
+        writeln!(self.out, "  lea .L.print.true(%rip), %rsi")?;
+        writeln!(self.out, "  mov $4, %rdx")?;
+        writeln!(self.out, "  test %rdi, %rdi")?;
+        writeln!(self.out, "  jnz .L.print.write")?;
+        writeln!(self.out, "  lea .L.print.false(%rip), %rsi")?;
+        writeln!(self.out, "  mov $5, %rdx")?;
+        writeln!(self.out, "  jmp .L.print.write")?;
+
+        writeln!(self.out, ".L.print.newline:")?;
+        writeln!(self.out, "  lea .L.print.eol(%rip), %rsi")?;
+        writeln!(self.out, "  mov $1, %rdx")?;
+        writeln!(self.out, "  jmp .L.print.write")?;
+
+        // the digits go backwards into a buffer in the frame, from the last one;
+        // the magnitude is unsigned, so it is right for MIN too
+        writeln!(self.out, ".L.print.int:")?;
+        writeln!(self.out, "  push %rbp")?;
+        writeln!(self.out, "  mov %rsp, %rbp")?;
+        writeln!(self.out, "  sub $32, %rsp")?;
+        writeln!(self.out, "  lea -1(%rbp), %rsi")?;
+        writeln!(self.out, "  mov %rdi, %rax")?;
+        writeln!(self.out, "  test %rax, %rax")?;
+        writeln!(self.out, "  jns .L.print.digits")?;
+        writeln!(self.out, "  neg %rax")?;
+        writeln!(self.out, "  jmp .L.print.digits")?;
+
+        // the same digits without a sign, which a clear %rdi leaves out
+        writeln!(self.out, ".L.print.uint:")?;
+        writeln!(self.out, "  push %rbp")?;
+        writeln!(self.out, "  mov %rsp, %rbp")?;
+        writeln!(self.out, "  sub $32, %rsp")?;
+        writeln!(self.out, "  lea -1(%rbp), %rsi")?;
+        writeln!(self.out, "  mov %rdi, %rax")?;
+        writeln!(self.out, "  xor %edi, %edi")?;
+        writeln!(self.out, ".L.print.digits:")?;
+        writeln!(self.out, "  mov $10, %rcx")?;
+        writeln!(self.out, ".L.print.digit:")?;
+        writeln!(self.out, "  xor %edx, %edx")?;
+        writeln!(self.out, "  div %rcx")?;
+        writeln!(self.out, "  add $48, %dl")?;
+        writeln!(self.out, "  mov %dl, (%rsi)")?;
+        writeln!(self.out, "  dec %rsi")?;
+        writeln!(self.out, "  test %rax, %rax")?;
+        writeln!(self.out, "  jnz .L.print.digit")?;
+        writeln!(self.out, "  test %rdi, %rdi")?;
+        writeln!(self.out, "  jns .L.print.number")?;
+        writeln!(self.out, "  movb $45, (%rsi)")?;
+        writeln!(self.out, "  dec %rsi")?;
+        writeln!(self.out, ".L.print.number:")?;
+        writeln!(self.out, "  lea -1(%rbp), %rdx")?;
+        writeln!(self.out, "  sub %rsi, %rdx")?;
+        writeln!(self.out, "  inc %rsi")?;
+        writeln!(self.out, "  call .L.print.write")?;
+        writeln!(self.out, "  mov %rbp, %rsp")?;
+        writeln!(self.out, "  pop %rbp")?;
+        writeln!(self.out, "  ret")?;
+
+        // the UTF-8 of the char goes into a buffer in the frame: a leading byte with
+        // the top bits, then 6 bits per continuation byte
+        writeln!(self.out, ".L.print.char:")?;
+        writeln!(self.out, "  push %rbp")?;
+        writeln!(self.out, "  mov %rsp, %rbp")?;
+        writeln!(self.out, "  sub $16, %rsp")?;
+        writeln!(self.out, "  lea -8(%rbp), %rsi")?;
+        writeln!(self.out, "  mov %dil, (%rsi)")?;
+        writeln!(self.out, "  mov $1, %rdx")?;
+        writeln!(self.out, "  cmp $0x80, %edi")?;
+        writeln!(self.out, "  jb .L.print.chars")?;
+        for (len, limit, lead) in [(2, 0x800, 0xc0), (3, 0x10000, 0xe0), (4, 0x110000, 0xf0)] {
+            let next = if len < 4 { format!(".L.print.char{}", len + 1) } else { ".L.print.chars".to_owned() };
+            if len > 2 {
+                writeln!(self.out, ".L.print.char{}:", len)?;
+            }
+            writeln!(self.out, "  cmp ${:#x}, %edi", limit)?;
+            if len < 4 {
+                writeln!(self.out, "  jae {}", next)?;
+            }
+            writeln!(self.out, "  mov %edi, %eax")?;
+            writeln!(self.out, "  shr ${}, %eax", 6 * (len - 1))?;
+            writeln!(self.out, "  or ${:#x}, %al", lead)?;
+            writeln!(self.out, "  mov %al, (%rsi)")?;
+            for i in 1..len {
+                writeln!(self.out, "  mov %edi, %eax")?;
+                writeln!(self.out, "  shr ${}, %eax", 6 * (len - 1 - i))?;
+                writeln!(self.out, "  and $0x3f, %al")?;
+                writeln!(self.out, "  or $0x80, %al")?;
+                writeln!(self.out, "  mov %al, {}(%rsi)", i)?;
+            }
+            writeln!(self.out, "  mov ${}, %rdx", len)?;
+            writeln!(self.out, "  jmp .L.print.chars")?;
+        }
+        writeln!(self.out, ".L.print.chars:")?;
+        writeln!(self.out, "  call .L.print.write")?;
+        writeln!(self.out, "  mov %rbp, %rsp")?;
+        writeln!(self.out, "  pop %rbp")?;
+        writeln!(self.out, "  ret")?;
+
+        // %rdx bytes at %rsi to stdout
+        writeln!(self.out, ".L.print.write:")?;
+        writeln!(self.out, "  push %rbp")?;
+        writeln!(self.out, "  mov %rsp, %rbp")?;
+        writeln!(self.out, "  and $-16, %rsp")?;
+        writeln!(self.out, "  mov $1, %rdi")?;
+        writeln!(self.out, "  call write")?;
+        writeln!(self.out, "  mov %rbp, %rsp")?;
+        writeln!(self.out, "  pop %rbp")?;
+        writeln!(self.out, "  ret")?;
+
+        writeln!(self.out)?;
+        writeln!(self.out, "  .section .rodata")?;
+        writeln!(self.out, ".L.print.true:")?;
+        writeln!(self.out, "  .ascii \"true\"")?;
+        writeln!(self.out, ".L.print.false:")?;
+        writeln!(self.out, "  .ascii \"false\"")?;
+        writeln!(self.out, ".L.print.eol:")?;
+        writeln!(self.out, "  .ascii \"\\n\"")
+    }
+
+    fn generate_strings(&mut self) -> io::Result<()> {
+        if self.strings.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(self.out)?;
+        writeln!(self.out, "  .section .rodata")?;
+        for (label, contents) in self.strings.iter() {
+            writeln!(self.out, "{}:", label)?;
+            writeln!(self.out, "  .ascii \"{}\"", escape(contents))?;
+        }
+
+        Ok(())
+    }
+
+    // Lays out the struct or enum called name after the structs and enums its fields hold.
+    fn declare(&mut self, name: &str, program: &Program) {
+        if self.structs.contains_key(name) || self.enums.contains_key(name) {
+            return;
+        }
+
+        if let Some(decl) = program.get_structs().iter().find(|decl| decl.get_name() == name) {
+            let mut fields = Vec::new();
+            for field in decl.get_fields() {
+                self.declare_held(field.get_type_name(), program);
+                fields.push(Field::new(field.get_name().to_owned(), self.resolve(field.get_type_name())));
+            }
+            self.structs.insert(name.to_owned(), Rc::new(StructType::new(name.to_owned(), fields)));
+        } else if let Some(decl) = program.get_enums().iter().find(|decl| decl.get_name() == name) {
+            let mut variants = Vec::new();
+            for variant in decl.get_variants() {
+                let mut fields = Vec::new();
+                for type_name in variant.get_fields() {
+                    self.declare_held(type_name, program);
+                    fields.push(self.resolve(type_name));
+                }
+                variants.push(Variant::new(variant.get_name().to_owned(), fields));
+            }
+            self.enums.insert(name.to_owned(), Rc::new(EnumType::new(name.to_owned(), variants)));
+        }
+    }
+
+    fn declare_held(&mut self, type_name: &TypeName, program: &Program) {
+        for name in type_name.get_names() {
+            if Type::primitive(name).is_none() {
+                self.declare(name, program);
+            }
+        }
+    }
+
+    fn resolve(&self, type_name: &TypeName) -> Type {
+        match type_name {
+            TypeName::Named(name, _) => match Type::primitive(name) {
+                Some(primitive) => primitive,
+                None => match self.enums.get(name) {
+                    Some(enum_type) => Type::Enum(enum_type.clone()),
+                    None => Type::Struct(self.structs.get(name).expect("the semantic pass resolves every type").clone()),
+                },
+            },
+            TypeName::Array(element, len, _) => Type::array(self.resolve(element), *len),
+            TypeName::Tuple(elements, _) if elements.is_empty() => Type::Unit,
+            TypeName::Tuple(elements, _) => Type::Tuple(elements.iter().map(|element| self.resolve(element)).collect()),
+        }
+    }
+
+    fn new_label(&mut self, name: &str) -> String {
+        self.labels += 1;
+        format!(".L.{}.{}", name, self.labels)
+    }
+
+    // A label to jump to when the code at span fails at runtime.
+    fn trap(&mut self, span: Span, message: &str) -> String {
+        let label = self.new_label("trap");
+        let pos = span.get_pos();
+        let message = format!("{}:{}:{}: {}\n", self.file, pos.get_line(), pos.get_x(), message);
+        self.traps.push((label.clone(), message));
+        label
+    }
+
+    // The size of the frame is only known once the body is generated,
+    // so the epilogue sets it as an assembler symbol.
+    fn generate_prologue(&mut self) -> io::Result<()> {
+        // functions are global, so C code can call them
+        let name = &self.frame.function;
+        writeln!(self.out)?;
+        writeln!(self.out, ".global {}", name)?;
+        writeln!(self.out, "{}:", name)?;
+        writeln!(self.out, "  push %rbp")?;
+        writeln!(self.out, "  mov %rsp, %rbp")?;
+        writeln!(self.out, "  sub $.L.frame.{}, %rsp", self.frame.function)
+    }
+
+    fn generate_epilogue(&mut self) -> io::Result<()> {
+        writeln!(self.out, ".L.return.{}:", self.frame.function)?;
+        writeln!(self.out, "  mov %rbp, %rsp")?;
+        writeln!(self.out, "  pop %rbp")?;
+        writeln!(self.out, "  ret")?;
+        writeln!(self.out, "  .set .L.frame.{}, {}", self.frame.function, align_to(self.frame.size, 16))
+    }
+
+    fn generate_function(&mut self, function: &FunctionDecl) -> io::Result<()> {
+        self.frame = StackFrame::new(function.get_name());
+        self.generate_prologue()?;
+
+        let signature = self.functions[function.get_name()].clone();
+        let return_type = signature.get_return_type();
+        let hidden = !return_type.is_scalar() && !in_registers(return_type);
+        if hidden {
+            let offset = self.frame.reserve(8);
+            writeln!(self.out, "  mov %rdi, -{}(%rbp)", offset)?;
+            self.frame.out = Some((offset, return_type.clone()));
+        } else if in_registers(return_type) {
+            let offset = self.frame.reserve(16);
+            self.frame.gathered = Some((offset, return_type.clone()));
+        }
+
+        // spill the arguments into stack slots like any other variable,
+        // copying the structs they point to
+        for (i, (param, param_type)) in function.get_params().iter().zip(signature.get_params()).enumerate() {
+            let i = i + hidden as usize;
+            let offset = self.frame.allocate(param.get_name(), param_type.clone());
+            let register = if i < ARGUMENT_REGISTERS.len() {
+                ARGUMENT_REGISTERS[i]
+            } else {
+                // the rest were pushed by the caller, right above the return address
+                writeln!(self.out, "  mov {}(%rbp), %rax", 16 + (i - ARGUMENT_REGISTERS.len()) * 8)?;
+                "%rax"
+            };
+            if param_type.is_scalar() {
+                writeln!(self.out, "  mov {}, -{}(%rbp)", register, offset)?;
+            } else {
+                self.copy(param_type.size(), register, -(offset as isize), "%rbp")?;
+            }
+        }
+
+        // the value of the body is returned
+        self.generate_block(function.get_body())?;
+        self.pop("%rax")?;
+        self.generate_return_value()?;
+
+        // functions without a value return 0, so `fn main()` exits successfully
+        if function.get_return_type().is_none() {
+            writeln!(self.out, "  mov $0, %rax")?;
+        }
+
+        self.generate_epilogue()
+    }
+
+    // A returned struct is copied to where the hidden argument points, which is returned instead.
+    // A small tuple is gathered into 16 bytes of its own first, so loading them reads no further.
+    fn generate_return_value(&mut self) -> io::Result<()> {
+        if let Some((offset, return_type)) = self.frame.out.clone() {
+            writeln!(self.out, "  mov -{}(%rbp), %rdi", offset)?;
+            self.copy(return_type.size(), "%rax", 0, "%rdi")?;
+            writeln!(self.out, "  mov %rdi, %rax")?;
+        } else if let Some((offset, return_type)) = self.frame.gathered.clone() {
+            self.copy(return_type.size(), "%rax", -(offset as isize), "%rbp")?;
+            writeln!(self.out, "  mov -{}(%rbp), %rax", offset)?;
+            writeln!(self.out, "  mov -{}(%rbp), %rdx", offset - 8)?;
+        }
+
+        Ok(())
+    }
+
+    // Pushes the value of block, 0 for `()`, and returns its type.
+    fn generate_block(&mut self, block: &Block) -> io::Result<Type> {
+        // the variables of the block go out of scope at its end, their slots stay taken
+        let scope = self.frame.slots.len();
+        for statement in block.get_statements() {
+            self.generate_statement(statement)?;
+        }
+        let value_type = match block.get_value() {
+            Some(value) => self.generate_arithmetic(value)?,
+            None => {
+                self.push("$0")?;
+                Type::Unit
+            }
+        };
+        self.frame.slots.truncate(scope);
+
+        Ok(value_type)
+    }
+
+    fn generate_statement(&mut self, statement: &Statement) -> io::Result<()> {
+        match statement {
+            Statement::Arithmetic(arithmetic) => {
+                let value_type = self.generate_arithmetic(arithmetic)?;
+                self.pop("%rax")?;
+                self.generate_status(&value_type)?;
+            }
+            Statement::Let(l) => {
+                let value_type = self.generate_arithmetic(l.get_value())?;
+                // allocate after the initializer so `let x = x + 1;` reads the old x
+                let offset = self.frame.allocate(l.get_name(), value_type.clone());
+                self.pop("%rax")?;
+                self.generate_store(&value_type, -(offset as isize), "%rbp")?;
+                self.generate_status(&value_type)?;
+            }
+            Statement::Assign(assign) => {
+                let value_type = self.generate_arithmetic(assign.get_value())?;
+                match assign.get_target() {
+                    Node::Variable(variable) => {
+                        let (offset, _) = self.frame.lookup(variable.get_name());
+                        self.pop("%rax")?;
+                        self.generate_store(&value_type, -(offset as isize), "%rbp")?;
+                    }
+                    Node::Field(field) => {
+                        // a struct evaluates to its address, which is where the field is stored
+                        let base_type = self.generate_node(field.get_base())?;
+                        let (offset, _) = field_of(&base_type, field.get_name());
+                        self.pop("%rdi")?;
+                        self.pop("%rax")?;
+                        self.generate_store(&value_type, offset as isize, "%rdi")?;
+                    }
+                    Node::Index(index) => {
+                        self.generate_element(index)?;
+                        self.pop("%rdi")?;
+                        self.pop("%rax")?;
+                        self.generate_store(&value_type, 0, "%rdi")?;
+                    }
+                    _ => unreachable!("the parser only assigns to variables and their fields and elements"),
+                }
+                self.generate_status(&value_type)?;
+            }
+            Statement::Return(r) => {
+                match r.get_value() {
+                    Some(value) => {
+                        self.generate_arithmetic(value)?;
+                        self.pop("%rax")?;
+                        self.generate_return_value()?;
+                    }
+                    None => {
+                        writeln!(self.out, "  mov $0, %rax")?;
+                    }
+                }
+                writeln!(self.out, "  jmp .L.return.{}", self.frame.function)?;
+            }
+            Statement::While(w) => {
+                self.generate_while(w)?;
+                // a loop has no value, so main ending with one exits with 0
+                writeln!(self.out, "  mov $0, %rax")?;
+            }
+            Statement::Destructure(_) => {
+                unreachable!("destructuring lets are lowered into lets");
+            }
+            Statement::For(_) => {
+                unreachable!("for loops are lowered into while loops");
+            }
+            Statement::Break(_) | Statement::Continue(_) => {
+                let target = self.loops.last().expect("the semantic pass keeps `break` and `continue` in loops");
+                let label = match statement {
+                    Statement::Break(_) => target.end.clone(),
+                    _ => target.start.clone(),
+                };
+                // drop what the enclosing expressions pushed since the loop began
+                let pushed = self.frame.depth - target.depth;
+                if pushed > 0 {
+                    writeln!(self.out, "  add ${}, %rsp", pushed * 8)?;
+                }
+                writeln!(self.out, "  jmp {}", label)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // main exits with 0 after a statement with a struct value, like in the interpreter,
+    // rather than with its address.
+    fn generate_status(&mut self, value_type: &Type) -> io::Result<()> {
+        if !value_type.is_scalar() {
+            writeln!(self.out, "  mov $0, %rax")?;
+        }
+
+        Ok(())
+    }
+
+    // Pushes the value of value_type stored at offset(base), or its address for a str, a struct,
+    // an array, an enum or a tuple.
+    fn generate_load(&mut self, value_type: &Type, offset: isize, base: &str) -> io::Result<()> {
+        match value_type {
+            Type::Unit => {
+                self.push("$0")
+            }
+            Type::Bool | Type::Int(IntType::U8) => {
+                writeln!(self.out, "  movzbq {}({}), %rax", offset, base)?;
+                self.push("%rax")
+            }
+            Type::Int(IntType::I8) => {
+                writeln!(self.out, "  movsbq {}({}), %rax", offset, base)?;
+                self.push("%rax")
+            }
+            Type::Int(IntType::U16) => {
+                writeln!(self.out, "  movzwq {}({}), %rax", offset, base)?;
+                self.push("%rax")
+            }
+            Type::Int(IntType::I16) => {
+                writeln!(self.out, "  movswq {}({}), %rax", offset, base)?;
+                self.push("%rax")
+            }
+            // writing %eax clears the upper half of %rax
+            Type::Int(IntType::U32) | Type::Char => {
+                writeln!(self.out, "  movl {}({}), %eax", offset, base)?;
+                self.push("%rax")
+            }
+            Type::Int(IntType::I32) => {
+                writeln!(self.out, "  movslq {}({}), %rax", offset, base)?;
+                self.push("%rax")
+            }
+            Type::Str | Type::Struct(_) | Type::Array(_, _) | Type::Enum(_) | Type::Tuple(_) => {
+                writeln!(self.out, "  lea {}({}), %rax", offset, base)?;
+                self.push("%rax")
+            }
+            _ => {
+                self.push(&format!("{}({})", offset, base))
+            }
+        }
+    }
+
+    // Stores %rax, a value of value_type, at offset(base). For a str, a struct, an
+    // array, an enum or a tuple %rax is its address, and it is copied.
+    fn generate_store(&mut self, value_type: &Type, offset: isize, base: &str) -> io::Result<()> {
+        match value_type {
+            Type::Unit => Ok(()),
+            Type::Str | Type::Struct(_) | Type::Array(_, _) | Type::Enum(_) | Type::Tuple(_) => self.copy(value_type.size(), "%rax", offset, base),
+            _ => writeln!(self.out, "  mov {}, {}({})", register("%rax", value_type.size()), offset, base),
+        }
+    }
+
+    // Copies size bytes from where the register from points to offset(base), through %r11,
+    // which no argument is passed in.
+    fn copy(&mut self, size: usize, from: &str, offset: isize, base: &str) -> io::Result<()> {
+        let mut copied = 0usize;
+        while copied < size {
+            let (chunk, scratch) = match size - copied {
+                8.. => (8, "%r11"),
+                4..=7 => (4, "%r11d"),
+                2 | 3 => (2, "%r11w"),
+                _ => (1, "%r11b"),
+            };
+            writeln!(self.out, "  mov {}({}), {}", copied, from, scratch)?;
+            writeln!(self.out, "  mov {}, {}({})", scratch, offset + copied as isize, base)?;
+            copied += chunk;
+        }
+
+        Ok(())
+    }
+
+    fn generate_while(&mut self, w: &While) -> io::Result<()> {
+        let start = self.new_label("while");
+        let end = self.new_label("while.end");
+
+        writeln!(self.out, "{}:", start)?;
+        self.generate_jump_unless(w.get_condition(), &end)?;
+
+        self.loops.push(Loop {
+            start: start.clone(),
+            end: end.clone(),
+            depth: self.frame.depth,
+        });
+        self.generate_block(w.get_body())?;
+        self.pop("%rax")?;
+        self.loops.pop();
+
+        writeln!(self.out, "  jmp {}", start)?;
+        writeln!(self.out, "{}:", end)
+    }
+
+    // Each branch pushes one value, so the stack is the same after either.
+    fn generate_if(&mut self, i: &If) -> io::Result<Type> {
+        let otherwise = self.new_label("else");
+        let end = self.new_label("if.end");
+
+        self.generate_jump_unless(i.get_condition(), &otherwise)?;
+        let depth = self.frame.depth;
+        let then_type = self.generate_block(i.get_then())?;
+        writeln!(self.out, "  jmp {}", end)?;
+
+        self.frame.depth = depth;
+        writeln!(self.out, "{}:", otherwise)?;
+        let otherwise_type = match i.get_otherwise() {
+            Some(block) => self.generate_block(block)?,
+            None => {
+                self.push("$0")?;
+                Type::Unit
+            }
+        };
+        writeln!(self.out, "{}:", end)?;
+
+        // a then block that never finishes is `()`, and the else block has the type
+        if then_type == Type::Unit {
+            Ok(otherwise_type)
+        } else {
+            Ok(then_type)
+        }
+    }
+
+    // Jumps to label when condition is 0, which is false.
+    fn generate_jump_unless(&mut self, condition: &Arithmetic, label: &str) -> io::Result<()> {
+        if self.optimization > 0 {
+            if let Some(n) = condition.fold() {
+                if n == 0 {
+                    writeln!(self.out, "  jmp {}", label)?;
+                }
+                return Ok(());
+            }
+        }
+
+        self.generate_arithmetic(condition)?;
+        self.pop("%rax")?;
+        writeln!(self.out, "  test %rax, %rax")?;
+        writeln!(self.out, "  jz {}", label)
+    }
+
+    // Pushes the value of arithmetic and returns its type.
+    fn generate_arithmetic(&mut self, arithmetic: &Arithmetic) -> io::Result<Type> {
+        if self.optimization > 0 {
+            if let Some(n) = arithmetic.fold() {
+                self.push_constant(n)?;
+                return Ok(folded_type(arithmetic));
+            }
+        }
+
+        match arithmetic {
+            Arithmetic::Term(term) => {
+                self.generate_node(term)
+            }
+            Arithmetic::MultiTerm(left, op @ (Operator::And | Operator::Or), right) => {
+                self.generate_logic(*op, left, right)?;
+                Ok(Type::Bool)
+            }
+            Arithmetic::MultiTerm(left, op, right) => {
+                let operand_type = self.generate_node(left)?;
+                self.generate_node(right)?;
+                self.generate_operator(*op, &operand_type, arithmetic.get_span())?;
+                match op {
+                    Operator::Plus | Operator::Minus | Operator::Mul | Operator::Div | Operator::Rem => Ok(operand_type),
+                    _ => Ok(Type::Bool),
+                }
+            }
+        }
+    }
+
+    fn generate_node(&mut self, node: &Node) -> io::Result<Type> {
+        match node {
+            Node::Arithmetic(arithmetic) => {
+                self.generate_arithmetic(arithmetic)
+            }
+            Node::Number(number) => {
+                let n = number.get_value().expect("the semantic pass checks the range of literals");
+                // a u64 above the maximum of i64 has the same bits as a negative one
+                self.push_constant(n as i64)?;
+                Ok(Type::Int(number.get_suffix().unwrap_or(IntType::I64)))
+            }
+            Node::Boolean(b) => {
+                self.push_constant(b.get_value() as i64)?;
+                Ok(Type::Bool)
+            }
+            Node::Char(c) => {
+                self.push_constant(c.get_value() as i64)?;
+                Ok(Type::Char)
+            }
+            Node::Str(s) => {
+                self.generate_str(s)
+            }
+            Node::Variable(variable) => {
+                let (offset, slot_type) = self.frame.lookup(variable.get_name());
+                self.generate_load(&slot_type, -(offset as isize), "%rbp")?;
+                Ok(slot_type)
+            }
+            Node::Call(call) => {
+                self.generate_call(call)
+            }
+            Node::Unary(unary) => {
+                self.generate_unary(unary)
+            }
+            Node::If(i) => {
+                self.generate_if(i)
+            }
+            Node::StructLiteral(literal) => {
+                self.generate_struct_literal(literal)
+            }
+            Node::Field(field) => {
+                self.generate_field(field)
+            }
+            Node::Array(array) => {
+                self.generate_array(array)
+            }
+            Node::Tuple(t) => {
+                self.generate_tuple(t)
+            }
+            Node::Repeat(repeat) => {
+                self.generate_repeat(repeat)
+            }
+            Node::Index(index) => {
+                let element_type = self.generate_element(index)?;
+                self.pop("%rax")?;
+                self.generate_load(&element_type, 0, "%rax")?;
+                Ok(element_type)
+            }
+            Node::Variant(variant) => {
+                self.generate_variant(variant)
+            }
+            Node::Match(m) => {
+                self.generate_match(m)
+            }
+        }
+    }
+
+    // Fills a slot of its own with the fields, in the order they are written.
+    fn generate_struct_literal(&mut self, literal: &StructLiteral) -> io::Result<Type> {
+        let struct_type = self.structs[literal.get_name()].clone();
+        let slot = self.frame.reserve(struct_type.size()) as isize;
+        for init in literal.get_fields() {
+            let value_type = self.generate_arithmetic(init.get_value())?;
+            let offset = struct_type.offset_of(init.get_name()).expect("the semantic pass resolves every field");
+            self.pop("%rax")?;
+            self.generate_store(&value_type, offset as isize - slot, "%rbp")?;
+        }
+
+        writeln!(self.out, "  lea -{}(%rbp), %rax", slot)?;
+        self.push("%rax")?;
+        Ok(Type::Struct(struct_type))
+    }
+
+    // Fills a slot of its own with the elements. Their layout is only known once all
+    // are computed, so each is kept in a slot as it is, before a later one can assign
+    // to what it reads, and moved into place at the end.
+    fn generate_tuple(&mut self, t: &TupleLiteral) -> io::Result<Type> {
+        let mut parts = Vec::new();
+        for element in t.get_elements() {
+            let element_type = self.generate_arithmetic(element)?;
+            let part = self.frame.reserve(element_type.size()) as isize;
+            self.pop("%rax")?;
+            self.generate_store(&element_type, -part, "%rbp")?;
+            parts.push((part, element_type));
+        }
+
+        let tuple_type = Type::Tuple(parts.iter().map(|(_, element_type)| element_type.clone()).collect());
+        let slot = self.frame.reserve(tuple_type.size()) as isize;
+        for (i, (part, element_type)) in parts.iter().enumerate() {
+            self.generate_load(element_type, -part, "%rbp")?;
+            self.pop("%rax")?;
+            self.generate_store(element_type, tuple_type.offset_of(i) as isize - slot, "%rbp")?;
+        }
+
+        writeln!(self.out, "  lea -{}(%rbp), %rax", slot)?;
+        self.push("%rax")?;
+        Ok(tuple_type)
+    }
+
+    // Fills a slot of its own with the tag and the fields.
+    fn generate_variant(&mut self, literal: &VariantLiteral) -> io::Result<Type> {
+        let enum_type = self.enums[literal.get_enum_name()].clone();
+        let (tag, _) = enum_type.get_variant(literal.get_name()).expect("the semantic pass resolves every variant");
+        let slot = self.frame.reserve(enum_type.size()) as isize;
+        writeln!(self.out, "  movq ${}, -{}(%rbp)", tag, slot)?;
+        for (i, arg) in literal.get_args().iter().enumerate() {
+            let value_type = self.generate_arithmetic(arg)?;
+            self.pop("%rax")?;
+            self.generate_store(&value_type, enum_type.offset_of(tag, i) as isize - slot, "%rbp")?;
+        }
+
+        writeln!(self.out, "  lea -{}(%rbp), %rax", slot)?;
+        self.push("%rax")?;
+        Ok(Type::Enum(enum_type))
+    }
+
+    // The value is copied into a slot of its own, then the arms are tried in order,
+    // each jumping to the next when its pattern or its guard fails. On an enum, a table
+    // indexed by the tag first jumps to the first arm that can match its variant.
+    // Each arm pushes one value, like the branches of an if.
+    fn generate_match(&mut self, m: &Match) -> io::Result<Type> {
+        let value_type = self.generate_arithmetic(m.get_value())?;
+        let slot = self.frame.reserve(value_type.size()) as isize;
+        self.pop("%rax")?;
+        self.generate_store(&value_type, -slot, "%rbp")?;
+
+        let labels: Vec<String> = m.get_arms().iter().map(|_| self.new_label("arm")).collect();
+        let unmatched = self.new_label("match.unmatched");
+        let end = self.new_label("match.end");
+
+        if let Type::Enum(enum_type) = &value_type {
+            let table = self.new_label("match.table");
+            writeln!(self.out, "  mov -{}(%rbp), %rax", slot)?;
+            writeln!(self.out, "  lea {}(%rip), %rdi", table)?;
+            writeln!(self.out, "  movslq (%rdi,%rax,4), %rax")?;
+            writeln!(self.out, "  add %rdi, %rax")?;
+            writeln!(self.out, "  jmp *%rax")?;
+
+            // entries are relative to the table, so the code stays position independent
+            writeln!(self.out, "  .pushsection .rodata")?;
+            writeln!(self.out, "  .p2align 2")?;
+            writeln!(self.out, "{}:", table)?;
+            for variant in enum_type.get_variants() {
+                let first = m.get_arms().iter().position(|arm| match arm.get_pattern() {
+                    Pattern::Variant(pattern) => pattern.get_name() == variant.get_name(),
+                    pattern => pattern.is_irrefutable(),
+                });
+                let target = first.map_or(&unmatched, |i| &labels[i]);
+                writeln!(self.out, "  .long {} - {}", target, table)?;
+            }
+            writeln!(self.out, "  .popsection")?;
+        }
+
+        let depth = self.frame.depth;
+        let mut match_type = Type::Unit;
+        for (i, arm) in m.get_arms().iter().enumerate() {
+            let next = labels.get(i + 1).unwrap_or(&unmatched).clone();
+            self.frame.depth = depth;
+            writeln!(self.out, "{}:", labels[i])?;
+
+            let scope = self.frame.slots.len();
+            self.generate_pattern(arm.get_pattern(), &value_type, -slot, &next)?;
+            if let Some(guard) = arm.get_guard() {
+                self.generate_jump_unless(guard, &next)?;
+            }
+            let arm_type = self.generate_block(arm.get_body())?;
+            self.frame.slots.truncate(scope);
+            writeln!(self.out, "  jmp {}", end)?;
+
+            // an arm that never finishes is `()`, and the others have the type
+            if match_type == Type::Unit {
+                match_type = arm_type;
+            }
+        }
+
+        // the semantic pass makes sure some arm always matches
+        writeln!(self.out, "{}:", unmatched)?;
+        writeln!(self.out, "  ud2")?;
+        writeln!(self.out, "{}:", end)?;
+        self.frame.depth = depth + 1;
+
+        Ok(match_type)
+    }
+
+    // Jumps to fail unless the value of value_type at offset(%rbp) matches pattern,
+    // and gives the names the pattern binds slots holding their parts of it.
+    fn generate_pattern(&mut self, pattern: &Pattern, value_type: &Type, offset: isize, fail: &str) -> io::Result<()> {
+        match pattern {
+            Pattern::Wildcard(_) => {}
+            Pattern::Binding(name, _) => {
+                let slot = self.frame.allocate(name, value_type.clone());
+                self.generate_load(value_type, offset, "%rbp")?;
+                self.pop("%rax")?;
+                self.generate_store(value_type, -(slot as isize), "%rbp")?;
+            }
+            Pattern::Number(number) => {
+                // compared in memory at the width of the type, with the bits of the number
+                let n = number.get_value().expect("the semantic pass checks the range of literals");
+                match value_type.size() {
+                    1 => writeln!(self.out, "  cmpb ${}, {}(%rbp)", n as i8, offset)?,
+                    2 => writeln!(self.out, "  cmpw ${}, {}(%rbp)", n as i16, offset)?,
+                    4 => writeln!(self.out, "  cmpl ${}, {}(%rbp)", n as i32, offset)?,
+                    // cmp only takes 32-bit immediates
+                    _ if i32::try_from(n as i64).is_ok() => writeln!(self.out, "  cmpq ${}, {}(%rbp)", n as i64, offset)?,
+                    _ => {
+                        writeln!(self.out, "  mov ${}, %rax", n as i64)?;
+                        writeln!(self.out, "  cmp %rax, {}(%rbp)", offset)?;
+                    }
+                }
+                writeln!(self.out, "  jne {}", fail)?;
+            }
+            Pattern::Char(c) => {
+                writeln!(self.out, "  cmpl ${}, {}(%rbp)", c.get_value() as u32, offset)?;
+                writeln!(self.out, "  jne {}", fail)?;
+            }
+            Pattern::Boolean(b) => {
+                writeln!(self.out, "  cmpb ${}, {}(%rbp)", b.get_value() as u8, offset)?;
+                writeln!(self.out, "  jne {}", fail)?;
+            }
+            Pattern::Variant(variant) => {
+                let enum_type = match value_type {
+                    Type::Enum(enum_type) => enum_type.clone(),
+                    _ => unreachable!("the semantic pass only lets variant patterns match enums"),
+                };
+                let (tag, declared) = enum_type.get_variant(variant.get_name()).expect("the semantic pass resolves every variant");
+                writeln!(self.out, "  cmpq ${}, {}(%rbp)", tag, offset)?;
+                writeln!(self.out, "  jne {}", fail)?;
+                for (i, (field, field_type)) in variant.get_fields().iter().zip(declared.get_fields()).enumerate() {
+                    self.generate_pattern(field, field_type, offset + enum_type.offset_of(tag, i) as isize, fail)?;
+                }
+            }
+            Pattern::Tuple(t) => {
+                let elements = match value_type {
+                    Type::Tuple(elements) => elements,
+                    _ => unreachable!("the semantic pass only lets tuple patterns match tuples"),
+                };
+                for (i, (field, element_type)) in t.get_fields().iter().zip(elements).enumerate() {
+                    self.generate_pattern(field, element_type, offset + value_type.offset_of(i) as isize, fail)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Fills a slot of its own with the address and the length of the bytes.
+    fn generate_str(&mut self, s: &Str) -> io::Result<Type> {
+        let label = self.new_label("str");
+        let slot = self.frame.reserve(Type::Str.size());
+        writeln!(self.out, "  lea {}(%rip), %rax", label)?;
+        writeln!(self.out, "  mov %rax, -{}(%rbp)", slot)?;
+        writeln!(self.out, "  movq ${}, -{}(%rbp)", s.get_value().len(), slot - 8)?;
+        self.strings.push((label, s.get_value().to_owned()));
+
+        writeln!(self.out, "  lea -{}(%rbp), %rax", slot)?;
+        self.push("%rax")?;
+        Ok(Type::Str)
+    }
+
+    fn generate_field(&mut self, field: &FieldAccess) -> io::Result<Type> {
+        let base_type = self.generate_node(field.get_base())?;
+        let (offset, field_type) = field_of(&base_type, field.get_name());
+        self.pop("%rax")?;
+        self.generate_load(&field_type, offset as isize, "%rax")?;
+        Ok(field_type)
+    }
+
+    // Fills a slot of its own with the elements, one after another.
+    fn generate_array(&mut self, array: &ArrayLiteral) -> io::Result<Type> {
+        let mut element_type = Type::Unit;
+        let mut slot = 0isize;
+        for (i, element) in array.get_elements().iter().enumerate() {
+            element_type = self.generate_arithmetic(element)?;
+            // the first element tells the size of the array
+            if i == 0 {
+                slot = self.frame.reserve(element_type.size() * array.get_elements().len()) as isize;
+            }
+            self.pop("%rax")?;
+            self.generate_store(&element_type, (i * element_type.size()) as isize - slot, "%rbp")?;
+        }
+
+        writeln!(self.out, "  lea -{}(%rbp), %rax", slot)?;
+        self.push("%rax")?;
+        Ok(Type::array(element_type, array.get_elements().len()))
+    }
+
+    // The value is computed once and stored len times by a loop.
+    fn generate_repeat(&mut self, repeat: &ArrayRepeat) -> io::Result<Type> {
+        let value_type = self.generate_arithmetic(repeat.get_value())?;
+        let array_type = Type::array(value_type.clone(), repeat.get_len());
+        let slot = self.frame.reserve(array_type.size());
+        self.pop("%rax")?;
+
+        if repeat.get_len() > 0 {
+            let fill = self.new_label("repeat");
+            writeln!(self.out, "  lea -{}(%rbp), %rdi", slot)?;
+            writeln!(self.out, "  mov ${}, %rcx", repeat.get_len())?;
+            writeln!(self.out, "{}:", fill)?;
+            self.generate_store(&value_type, 0, "%rdi")?;
+            writeln!(self.out, "  add ${}, %rdi", value_type.size())?;
+            writeln!(self.out, "  dec %rcx")?;
+            writeln!(self.out, "  jnz {}", fill)?;
+        }
+
+        writeln!(self.out, "  lea -{}(%rbp), %rax", slot)?;
+        self.push("%rax")?;
+        Ok(array_type)
+    }
+
+    // Pushes the address of the element and returns its type. An index out of
+    // bounds aborts, and a negative one is a large unsigned number.
+    fn generate_element(&mut self, index: &Index) -> io::Result<Type> {
+        let (element_type, len) = match self.generate_node(index.get_base())? {
+            Type::Array(element_type, len) => (*element_type, len),
+            _ => unreachable!("the semantic pass only lets arrays be indexed"),
+        };
+        let size = element_type.size();
+
+        if self.optimization > 0 {
+            if let Some(i) = index.get_index().fold() {
+                let offset = i as usize * size;
+                if offset > 0 {
+                    self.pop("%rax")?;
+                    writeln!(self.out, "  add ${}, %rax", offset)?;
+                    self.push("%rax")?;
+                }
+                return Ok(element_type);
+            }
+        }
+
+        self.generate_arithmetic(index.get_index())?;
+        self.pop("%rdi")?;
+        self.pop("%rax")?;
+        let trap = self.trap(index.get_span(), &format!("index out of bounds: the len is {}", len));
+        writeln!(self.out, "  mov ${}, %rsi", len)?;
+        writeln!(self.out, "  cmp %rsi, %rdi")?;
+        writeln!(self.out, "  jae {}", trap)?;
+        writeln!(self.out, "  imul ${}, %rdi, %rdi", size)?;
+        writeln!(self.out, "  add %rdi, %rax")?;
+        self.push("%rax")?;
+        Ok(element_type)
+    }
+
+    // push only takes 32-bit immediates
+    fn push_constant(&mut self, n: i64) -> io::Result<()> {
+        if i32::try_from(n).is_ok() {
+            return self.push(&format!("${}", n));
+        }
+
+        writeln!(self.out, "  mov ${}, %rax", n)?;
+        self.push("%rax")
+    }
+
+    // The semantic pass only lets signed integers be negated.
+    fn generate_unary(&mut self, unary: &Unary) -> io::Result<Type> {
+        let operand_type = self.generate_node(unary.get_operand())?;
+        self.pop("%rax")?;
+        let value_type = match unary.get_op() {
+            UnaryOperator::Negate => {
+                let message = "attempt to negate with overflow";
+                writeln!(self.out, "  neg %rax")?;
+                match int_type_of(&operand_type) {
+                    Some(IntType::I64) | None => self.check_overflow("jo", unary.get_span(), message)?,
+                    Some(int_type) => self.narrow(int_type, unary.get_span(), message)?,
+                }
+                operand_type
+            }
+            UnaryOperator::Not => {
+                writeln!(self.out, "  xor $1, %rax")?;
+                Type::Bool
+            }
+        };
+
+        self.push("%rax")?;
+        Ok(value_type)
+    }
+
+    fn generate_operator(&mut self, op: Operator, operand_type: &Type, span: Span) -> io::Result<()> {
+        // %rbx is callee-saved, so only caller-saved registers are used as scratch
+        self.pop("%rdi")?;
+        self.pop("%rax")?;
+        let int_type = int_type_of(operand_type);
+        // chars compare like unsigned integers
+        let signed = int_type.is_some_and(|int_type| int_type.is_signed());
+        match op {
+            Operator::Plus => {
+                writeln!(self.out, "  add %rdi, %rax")?;
+                self.check_result(int_type, span, "attempt to add with overflow")?;
+            }
+            Operator::Minus => {
+                writeln!(self.out, "  sub %rdi, %rax")?;
+                self.check_result(int_type, span, "attempt to subtract with overflow")?;
+            }
+            Operator::Mul => {
+                // mul sets the carry when the product does not fit into %rax
+                if int_type == Some(IntType::U64) {
+                    writeln!(self.out, "  mul %rdi")?;
+                } else {
+                    writeln!(self.out, "  imul %rdi, %rax")?;
+                }
+                self.check_result(int_type, span, "attempt to multiply with overflow")?;
+            }
+            Operator::Div | Operator::Rem if signed => {
+                self.generate_division(op, span)?;
+                if let Some(int_type) = int_type.filter(|int_type| *int_type != IntType::I64) {
+                    self.narrow(int_type, span, "attempt to divide with overflow")?;
+                }
+            }
+            Operator::Div | Operator::Rem => {
+                self.generate_unsigned_division(op, span)?;
+            }
+            Operator::Equal | Operator::NotEqual | Operator::Less | Operator::LessEqual
+                | Operator::Greater | Operator::GreaterEqual => {
+                let condition = match (op, signed) {
+                    (Operator::Equal, _) => "e",
+                    (Operator::NotEqual, _) => "ne",
+                    (Operator::Less, true) => "l",
+                    (Operator::LessEqual, true) => "le",
+                    (Operator::Greater, true) => "g",
+                    (_, true) => "ge",
+                    (Operator::Less, false) => "b",
+                    (Operator::LessEqual, false) => "be",
+                    (Operator::Greater, false) => "a",
+                    (_, false) => "ae",
+                };
+                writeln!(self.out, "  cmp %rdi, %rax")?;
+                writeln!(self.out, "  set{} %al", condition)?;
+                writeln!(self.out, "  movzb %al, %rax")?;
+            }
+            Operator::And | Operator::Or => {
+                unreachable!("generated by generate_logic");
+            }
+        }
+
+        self.push("%rax")
+    }
+
+    // 1 or 0. The right operand only runs when the left one does not decide the result,
+    // that is when it is true for && and false for ||.
+    fn generate_logic(&mut self, op: Operator, left: &Node, right: &Node) -> io::Result<()> {
+        let end = self.new_label("logic.end");
+        let decided = match op {
+            Operator::And => "jz",
+            _ => "jnz",
+        };
+
+        self.generate_node(left)?;
+        self.pop("%rax")?;
+        writeln!(self.out, "  test %rax, %rax")?;
+        writeln!(self.out, "  setne %al")?;
+        writeln!(self.out, "  movzb %al, %rax")?;
+        writeln!(self.out, "  {} {}", decided, end)?;
+
+        self.generate_node(right)?;
+        self.pop("%rax")?;
+        writeln!(self.out, "  test %rax, %rax")?;
+        writeln!(self.out, "  setne %al")?;
+        writeln!(self.out, "  movzb %al, %rax")?;
+
+        writeln!(self.out, "{}:", end)?;
+        self.push("%rax")
+    }
+
+    // %rax / %rdi or %rax % %rdi into %rax. idiv faults on a zero divisor and on
+    // the one overflowing case, MIN / -1, so both are handled before it.
+    fn generate_division(&mut self, op: Operator, span: Span) -> io::Result<()> {
+        let message = match op {
+            Operator::Rem => "attempt to calculate the remainder with a divisor of zero",
+            _ => "attempt to divide by zero",
+        };
+        let zero = self.trap(span, message);
+        writeln!(self.out, "  test %rdi, %rdi")?;
+        writeln!(self.out, "  jz {}", zero)?;
+
+        // x / -1 is -x, which wraps for MIN, and x % -1 is 0
+        let divide = self.new_label("divide");
+        let end = self.new_label("divide.end");
+        writeln!(self.out, "  cmp $-1, %rdi")?;
+        writeln!(self.out, "  jne {}", divide)?;
+        match op {
+            Operator::Rem => {
+                writeln!(self.out, "  mov $0, %rax")?;
+            }
+            _ => {
+                writeln!(self.out, "  neg %rax")?;
+                self.check_overflow("jo", span, "attempt to divide with overflow")?;
+            }
+        }
+        writeln!(self.out, "  jmp {}", end)?;
+
+        writeln!(self.out, "{}:", divide)?;
+        writeln!(self.out, "  cqo")?;
+        writeln!(self.out, "  idiv %rdi")?;
+        if let Operator::Rem = op {
+            writeln!(self.out, "  mov %rdx, %rax")?;
+        }
+        writeln!(self.out, "{}:", end)
+    }
+
+    // %rax / %rdi or %rax % %rdi into %rax for unsigned integers, which div
+    // computes exactly, so only a zero divisor is handled before it.
+    fn generate_unsigned_division(&mut self, op: Operator, span: Span) -> io::Result<()> {
+        let message = match op {
+            Operator::Rem => "attempt to calculate the remainder with a divisor of zero",
+            _ => "attempt to divide by zero",
+        };
+        let zero = self.trap(span, message);
+        writeln!(self.out, "  test %rdi, %rdi")?;
+        writeln!(self.out, "  jz {}", zero)?;
+
+        writeln!(self.out, "  xor %edx, %edx")?;
+        writeln!(self.out, "  div %rdi")?;
+        if let Operator::Rem = op {
+            writeln!(self.out, "  mov %rdx, %rax")?;
+        }
+
+        Ok(())
+    }
+
+    // Brings the result of a 64-bit operation on values of int_type back into the type.
+    // An i64 overflowed when the operation did, a u64 when it carried, and a narrower
+    // integer when its exact result in %rax differs from its low bytes extended again.
+    fn check_result(&mut self, int_type: Option<IntType>, span: Span, message: &str) -> io::Result<()> {
+        match int_type {
+            Some(IntType::I64) | None => self.check_overflow("jo", span, message),
+            Some(IntType::U64) => self.check_overflow("jc", span, message),
+            Some(int_type) => self.narrow(int_type, span, message),
+        }
+    }
+
+    // Extends the low bytes of %rax for an integer narrower than 64 bits, like a load
+    // of it would. In checked mode a result that changes by this overflowed.
+    fn narrow(&mut self, int_type: IntType, span: Span, message: &str) -> io::Result<()> {
+        let extend = match int_type {
+            IntType::I8 => "movsbq %al, %rcx",
+            IntType::U8 => "movzbq %al, %rcx",
+            IntType::I16 => "movswq %ax, %rcx",
+            IntType::U16 => "movzwq %ax, %rcx",
+            IntType::I32 => "movslq %eax, %rcx",
+            // writing %ecx clears the upper half of %rcx
+            IntType::U32 => "mov %eax, %ecx",
+            IntType::I64 | IntType::U64 => return Ok(()),
+        };
+        writeln!(self.out, "  {}", extend)?;
+        if self.overflow_checks {
+            writeln!(self.out, "  cmp %rcx, %rax")?;
+            self.check_overflow("jne", span, message)?;
+        }
+        writeln!(self.out, "  mov %rcx, %rax")
+    }
+
+    // Jumps to a trap with the conditional jump when the last instruction overflowed,
+    // in checked mode.
+    fn check_overflow(&mut self, jump: &str, span: Span, message: &str) -> io::Result<()> {
+        if !self.overflow_checks {
+            return Ok(());
+        }
+
+        let trap = self.trap(span, message);
+        writeln!(self.out, "  {} {}", jump, trap)
+    }
+
+    // System V AMD64: the first six arguments go in registers, the rest on the stack
+    // with the seventh at the lowest address, and %rsp is 16-byte aligned at the call.
+    // A struct is passed as its address, and the callee copies it.
+    fn generate_call(&mut self, call: &Call) -> io::Result<Type> {
+        match Builtin::from_name(call.get_name()) {
+            Some(Builtin::Len) => return self.generate_len(call),
+            Some(builtin) => return self.generate_print(builtin, call),
+            None => {}
+        }
+
+        let return_type = self.functions[call.get_name()].get_return_type().clone();
+        let hidden = !return_type.is_scalar() && !in_registers(&return_type);
+        let count = call.get_args().len() + hidden as usize;
+        let stack_args = count.saturating_sub(ARGUMENT_REGISTERS.len());
+
+        let padding = (self.frame.depth + count + stack_args) % 2;
+        if padding == 1 {
+            writeln!(self.out, "  sub $8, %rsp")?;
+            self.frame.depth += 1;
+        }
+
+        // a returned struct goes into a slot of this frame, passed as the first argument
+        if hidden {
+            let slot = self.frame.reserve(return_type.size());
+            writeln!(self.out, "  lea -{}(%rbp), %rax", slot)?;
+            self.push("%rax")?;
+        }
+
+        // evaluate left to right, so the last argument ends up on top
+        for arg in call.get_args() {
+            self.generate_arithmetic(arg)?;
+        }
+
+        // copy the stack arguments again in reverse order
+        for (copied, i) in (ARGUMENT_REGISTERS.len()..count).rev().enumerate() {
+            let offset = (count - 1 - i + copied) * 8;
+            self.push(&format!("{}(%rsp)", offset))?;
+        }
+
+        for (i, register) in ARGUMENT_REGISTERS.iter().enumerate().take(count) {
+            let offset = (count - 1 - i + stack_args) * 8;
+            writeln!(self.out, "  mov {}(%rsp), {}", offset, register)?;
+        }
+
+        // %al holds the number of vector registers used by variadic callees
+        writeln!(self.out, "  mov $0, %rax")?;
+        writeln!(self.out, "  call {}", call.get_name())?;
+
+        let pushed = count + stack_args + padding;
+        writeln!(self.out, "  add ${}, %rsp", pushed * 8)?;
+        self.frame.depth -= pushed;
+
+        // a small tuple comes back in %rax and %rdx, and goes into a slot of this frame
+        if in_registers(&return_type) {
+            let slot = self.frame.reserve(16);
+            writeln!(self.out, "  mov %rax, -{}(%rbp)", slot)?;
+            writeln!(self.out, "  mov %rdx, -{}(%rbp)", slot - 8)?;
+            writeln!(self.out, "  lea -{}(%rbp), %rax", slot)?;
+        }
+
+        self.push("%rax")?;
+        Ok(return_type)
+    }
+
+    // print and println, one routine of generate_runtime per argument.
+    fn generate_print(&mut self, builtin: Builtin, call: &Call) -> io::Result<Type> {
+        self.prints = true;
+        for arg in call.get_args() {
+            let arg_type = self.generate_arithmetic(arg)?;
+            self.pop("%rdi")?;
+            let routine = match arg_type {
+                Type::Str => ".L.print.str",
+                Type::Bool => ".L.print.bool",
+                Type::Char => ".L.print.char",
+                Type::Int(int_type) if !int_type.is_signed() => ".L.print.uint",
+                _ => ".L.print.int",
+            };
+            writeln!(self.out, "  call {}", routine)?;
+        }
+        if builtin == Builtin::Println {
+            writeln!(self.out, "  call .L.print.newline")?;
+        }
+
+        self.push("$0")?;
+        Ok(Type::Unit)
+    }
+
+    // The length of an array is known from its type, that of a str is stored with it.
+    fn generate_len(&mut self, call: &Call) -> io::Result<Type> {
+        match self.generate_arithmetic(&call.get_args()[0])? {
+            Type::Array(_, len) => {
+                self.pop("%rax")?;
+                self.push_constant(len as i64)?;
+            }
+            _ => {
+                self.pop("%rax")?;
+                self.push("8(%rax)")?;
+            }
+        }
+
+        Ok(Type::Int(IntType::I64))
+    }
+}
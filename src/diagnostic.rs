@@ -0,0 +1,292 @@
+use std::fmt;
+use std::io::{ self, IsTerminal };
+use super::tokenizer::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// A source range with a message printed under it.
+#[derive(Debug, Clone)]
+pub struct Label {
+    span: Span,
+    message: String,
+    primary: bool,
+}
+
+// An error or warning about the source, shared by every stage of the compiler.
+// The primary label points at the offending code, secondary labels add context.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    severity: Severity,
+    code: &'static str,
+    message: String,
+    labels: Vec<Label>,
+    notes: Vec<String>,
+    help: Option<String>,
+}
+
+// Prints diagnostics rustc-style, with the source lines they point at.
+#[derive(Debug)]
+pub struct Renderer<'a> {
+    file: &'a str,
+    source: &'a str,
+    colored: bool,
+}
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+impl Label {
+    fn new(span: Span, message: String, primary: bool) -> Label {
+        Label {
+            span,
+            message,
+            primary,
+        }
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn is_primary(&self) -> bool {
+        self.primary
+    }
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: String) -> Diagnostic {
+        Diagnostic::new(Severity::Error, code, message)
+    }
+
+    pub fn warning(code: &'static str, message: String) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, code, message)
+    }
+
+    fn new(severity: Severity, code: &'static str, message: String) -> Diagnostic {
+        Diagnostic {
+            severity,
+            code,
+            message,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    pub fn with_label(mut self, span: Span, message: String) -> Diagnostic {
+        self.labels.retain(|label| !label.primary);
+        self.labels.insert(0, Label::new(span, message, true));
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: String) -> Diagnostic {
+        self.labels.push(Label::new(span, message, false));
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Diagnostic {
+        self.notes.push(note);
+        self
+    }
+
+    pub fn with_help(mut self, help: String) -> Diagnostic {
+        self.help = Some(help);
+        self
+    }
+
+    pub fn get_severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn get_code(&self) -> &str {
+        self.code
+    }
+
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn get_primary(&self) -> Option<&Label> {
+        self.labels.iter().find(|label| label.primary)
+    }
+
+    pub fn get_labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    pub fn get_notes(&self) -> &[String] {
+        &self.notes
+    }
+
+    pub fn get_help(&self) -> Option<&str> {
+        self.help.as_deref()
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+// Without the source at hand: `3:5: error[E0101]: message`
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(primary) = self.get_primary() {
+            let pos = primary.span.get_pos();
+            write!(f, "{}:{}: ", pos.get_line(), pos.get_x())?;
+        }
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+impl<'a> Renderer<'a> {
+    // Colours are used when stderr, where diagnostics go, is a terminal.
+    pub fn new(file: &'a str, source: &'a str) -> Renderer<'a> {
+        Renderer {
+            file,
+            source,
+            colored: io::stderr().is_terminal(),
+        }
+    }
+
+    pub fn with_color(mut self, colored: bool) -> Renderer<'a> {
+        self.colored = colored;
+        self
+    }
+
+    /*
+        error[E0206]: mismatched types
+         --> file.mylang:2:18
+          |
+        1 | fn f() -> int {
+          |    - expected `int` because of the return type
+        2 |     return g();
+          |            ^^^ expected `int`, found `()`
+          |
+          = note: ...
+    */
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let mut out = String::new();
+
+        let severity_color = match diagnostic.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        };
+        out.push_str(&format!("{}{}{}{}{}\n",
+            self.paint(severity_color, &format!("{}[{}]", diagnostic.severity, diagnostic.code)),
+            self.paint(BOLD, ": "), self.start(BOLD), diagnostic.message, self.end()));
+
+        // primary first, then the others in source order
+        let mut labels: Vec<&Label> = diagnostic.labels.iter().collect();
+        labels.sort_by_key(|label| (!label.primary, label.span.get_start()));
+
+        let max_line = labels.iter().map(|label| self.line_col(label.span.get_start()).0).max().unwrap_or(0);
+        let width = max_line.to_string().len();
+        let gutter = " ".repeat(width);
+
+        if let Some(primary) = labels.first() {
+            let (line, col) = self.line_col(primary.span.get_start());
+            out.push_str(&format!("{}{} {}:{}:{}\n", gutter, self.paint(BLUE, "-->"), self.file, line, col));
+            out.push_str(&format!("{} {}\n", gutter, self.paint(BLUE, "|")));
+        }
+
+        // labels on the same line share the source line
+        let mut lines: Vec<usize> = labels.iter().map(|label| self.line_col(label.span.get_start()).0).collect();
+        lines.sort_unstable();
+        lines.dedup();
+
+        let mut previous: Option<usize> = None;
+        for line in lines {
+            if let Some(previous) = previous {
+                if line > previous + 1 {
+                    out.push_str(&format!("{}\n", self.paint(BLUE, "...")));
+                }
+            }
+            previous = Some(line);
+
+            let text = self.source.lines().nth(line - 1).unwrap_or("");
+            out.push_str(&format!("{} {}\n", self.paint(BLUE, &format!("{:>width$} |", line, width = width)), text));
+
+            for label in labels.iter().filter(|label| self.line_col(label.span.get_start()).0 == line) {
+                let (_, col) = self.line_col(label.span.get_start());
+
+                // keep tabs so the marks line up with the source
+                let indent: String = text.chars().take(col - 1).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+
+                // underline up to the end of the line, at least one char
+                let line_end = text.chars().count() + 1;
+                let (start, end) = (self.boundary(label.span.get_start()), self.boundary(label.span.get_end()));
+                let len = self.source[start..end.max(start)].chars().count();
+                let len = len.min(line_end.saturating_sub(col)).max(1);
+
+                let (mark, color) = if label.primary { ("^", severity_color) } else { ("-", BLUE) };
+                let marks = format!("{} {}", mark.repeat(len), label.message);
+                out.push_str(&format!("{} {}{}\n", self.paint(BLUE, &format!("{} |", gutter)), indent, self.paint(color, marks.trim_end())));
+            }
+        }
+
+        if !diagnostic.notes.is_empty() || diagnostic.help.is_some() {
+            out.push_str(&format!("{} {}\n", gutter, self.paint(BLUE, "|")));
+        }
+        for note in diagnostic.notes.iter() {
+            out.push_str(&format!("{} {} {} {}\n", gutter, self.paint(BLUE, "="), self.paint(BOLD, "note:"), note));
+        }
+        if let Some(help) = &diagnostic.help {
+            out.push_str(&format!("{} {} {} {}\n", gutter, self.paint(BLUE, "="), self.paint(BOLD, "help:"), help));
+        }
+
+        out
+    }
+
+    // 1-based line and column of a byte offset
+    fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = self.boundary(offset);
+        let before = &self.source[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let col = before[line_start..].chars().count() + 1;
+
+        (line, col)
+    }
+
+    // offset within the source, moved back to the start of the char it is in
+    fn boundary(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+
+        offset
+    }
+
+    fn paint(&self, color: &'static str, text: &str) -> String {
+        format!("{}{}{}", self.start(color), text, self.end())
+    }
+
+    fn start(&self, color: &'static str) -> &'static str {
+        if self.colored { color } else { "" }
+    }
+
+    fn end(&self) -> &'static str {
+        if self.colored { RESET } else { "" }
+    }
+}
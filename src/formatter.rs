@@ -0,0 +1,551 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use super::ast::{
+    Program, StructDecl, EnumDecl, FunctionDecl, Block, Statement, Iterable,
+    Arithmetic, Node, MatchArm, Operator, UnaryOperator,
+};
+use super::parser::precedence;
+use super::tokenizer::{ Token, TokenType, Span };
+use super::types::tuple;
+
+const INDENT: &str = "    ";
+
+// Prints a program back as source in the canonical layout: four space indents,
+// one statement per line, a blank line between structs, enums and functions, and
+// only the parentheses the precedence of the operators needs. Comments go with the
+// line of code they are on, or else the one after them.
+#[derive(Debug, Default)]
+pub struct Formatter {
+    out: String,
+    depth: usize,
+    // shared with the formatters of nested blocks
+    comments: Rc<RefCell<Comments>>,
+}
+
+// The comments of the source and the lines of code they go with.
+#[derive(Debug, Default)]
+struct Comments {
+    comments: Vec<Comment>,
+    // the depth of braces after each `{` and `}`, by offset
+    braces: Vec<(usize, usize)>,
+    // where the lines of code start, found by a first pass
+    anchors: Vec<Span>,
+    placed: bool,
+    // by the start of the line of code
+    before: HashMap<usize, Vec<String>>,
+    on: HashMap<usize, Vec<String>>,
+    after: HashMap<usize, Vec<String>>,
+    // after all the code
+    end: Vec<String>,
+}
+
+#[derive(Debug)]
+struct Comment {
+    text: String,
+    span: Span,
+    depth: usize,
+    // code comes before it on its line
+    trailing: bool,
+    // only a `}` comes after it
+    closing: bool,
+    // nothing but comments comes after it
+    last: bool,
+}
+
+impl Formatter {
+    pub fn new() -> Formatter {
+        Formatter {
+            out: String::new(),
+            depth: 0usize,
+            comments: Rc::new(RefCell::new(Comments::default())),
+        }
+    }
+
+    // Keeps the comments among tokens, those of the source.
+    pub fn with_comments(mut self, tokens: &[Token]) -> Formatter {
+        self.comments = Rc::new(RefCell::new(Comments::new(tokens)));
+        self
+    }
+
+    // Structs come first, then enums, then functions, then the top-level statements.
+    pub fn format(mut self, program: &Program) -> String {
+        // a first pass finds the lines of code the comments can go with
+        if !self.comments.borrow().comments.is_empty() {
+            self.format_program(program);
+            self.out.clear();
+            self.comments.borrow_mut().place();
+        }
+        self.format_program(program);
+
+        let end = std::mem::take(&mut self.comments.borrow_mut().end);
+        for comment in end {
+            self.line(&comment);
+        }
+
+        self.out
+    }
+
+    fn format_program(&mut self, program: &Program) {
+        for (i, decl) in program.get_structs().iter().enumerate() {
+            if i > 0 {
+                self.out.push('\n');
+            }
+            self.format_struct(decl);
+        }
+
+        for (i, decl) in program.get_enums().iter().enumerate() {
+            if i > 0 || !program.get_structs().is_empty() {
+                self.out.push('\n');
+            }
+            self.format_enum(decl);
+        }
+
+        let types = program.get_structs().len() + program.get_enums().len();
+        for (i, function) in program.get_functions().iter().enumerate() {
+            if i > 0 || types > 0 {
+                self.out.push('\n');
+            }
+            self.format_function(function);
+        }
+
+        if program.has_statements() && !(program.get_functions().is_empty() && types == 0) {
+            self.out.push('\n');
+        }
+        for statement in program.get_statements() {
+            self.format_statement(statement, false);
+        }
+    }
+
+    // one field per line, each with a `,`
+    fn format_struct(&mut self, decl: &StructDecl) {
+        if decl.get_fields().is_empty() {
+            self.anchored(decl.get_span(), &format!("struct {} {{}}", decl.get_name()));
+            return;
+        }
+
+        self.anchored(decl.get_span(), &format!("struct {} {{", decl.get_name()));
+        self.depth += 1;
+        for field in decl.get_fields() {
+            self.anchored(field.get_span(), &format!("{}: {},", field.get_name(), field.get_type_name()));
+        }
+        self.depth -= 1;
+        self.line("}");
+    }
+
+    // one variant per line, each with a `,`
+    fn format_enum(&mut self, decl: &EnumDecl) {
+        if decl.get_variants().is_empty() {
+            self.anchored(decl.get_span(), &format!("enum {} {{}}", decl.get_name()));
+            return;
+        }
+
+        self.anchored(decl.get_span(), &format!("enum {} {{", decl.get_name()));
+        self.depth += 1;
+        for variant in decl.get_variants() {
+            if variant.get_fields().is_empty() {
+                self.anchored(variant.get_span(), &format!("{},", variant.get_name()));
+            } else {
+                let fields: Vec<String> = variant.get_fields().iter().map(|field| field.to_string()).collect();
+                self.anchored(variant.get_span(), &format!("{}({}),", variant.get_name(), fields.join(", ")));
+            }
+        }
+        self.depth -= 1;
+        self.line("}");
+    }
+
+    fn format_function(&mut self, function: &FunctionDecl) {
+        let params: Vec<String> = function.get_params().iter().map(|param| {
+            format!("{}: {}", param.get_name(), param.get_type_name())
+        }).collect();
+
+        let mut head = format!("fn {}({})", function.get_name(), params.join(", "));
+        if let Some(return_type) = function.get_return_type() {
+            head.push_str(&format!(" -> {}", return_type));
+        }
+        let body = self.format_braced(function.get_body());
+        self.anchored(function.get_span(), &format!("{} {}", head, body));
+    }
+
+    // A block as it goes after the code on the current line, ending at the current indent.
+    // A block of only a value stays on one line, as in `if c { 1 } else { 2 }`, unless
+    // comments go with the value.
+    fn format_block(&self, block: &Block) -> String {
+        match (block.get_statements(), block.get_value()) {
+            ([], Some(value)) if !self.comments.borrow_mut().go_with(value.get_span()) => {
+                return format!("{{ {} }}", self.format_arithmetic(value));
+            }
+            ([], None) => return "{}".to_owned(),
+            _ => {}
+        }
+
+        self.format_braced(block)
+    }
+
+    // block with one line per statement, like function bodies always are
+    fn format_braced(&self, block: &Block) -> String {
+        let statements = block.get_statements();
+        let mut inner = self.nested();
+        for (i, statement) in statements.iter().enumerate() {
+            // a final if without `;` would become the value
+            let last = i + 1 == statements.len() && block.get_value().is_none();
+            inner.format_statement(statement, last);
+        }
+        if let Some(value) = block.get_value() {
+            let text = inner.format_arithmetic(value);
+            inner.anchored(value.get_span(), &text);
+        }
+
+        format!("{{\n{}{}}}", inner.out, INDENT.repeat(self.depth))
+    }
+
+    // Statements ending with a block go without `;`, unless they are the last one of a block.
+    fn format_statement(&mut self, statement: &Statement, last: bool) {
+        let line = match statement {
+            Statement::Arithmetic(arithmetic @ Arithmetic::Term(Node::If(_) | Node::Match(_))) => {
+                let terminator = if last { ";" } else { "" };
+                format!("{}{}", self.format_arithmetic(arithmetic), terminator)
+            }
+            Statement::Arithmetic(arithmetic) => {
+                format!("{};", self.format_arithmetic(arithmetic))
+            }
+            Statement::Let(l) => {
+                let mut line = String::from("let ");
+                if l.is_mutable() {
+                    line.push_str("mut ");
+                }
+                line.push_str(l.get_name());
+                if let Some(type_name) = l.get_type_name() {
+                    line.push_str(&format!(": {}", type_name));
+                }
+                format!("{} = {};", line, self.format_arithmetic(l.get_value()))
+            }
+            Statement::Destructure(d) => {
+                let mut line = format!("let {}", d.get_pattern());
+                if let Some(type_name) = d.get_type_name() {
+                    line.push_str(&format!(": {}", type_name));
+                }
+                format!("{} = {};", line, self.format_arithmetic(d.get_value()))
+            }
+            Statement::Assign(assign) => {
+                format!("{} = {};", self.format_node(assign.get_target()), self.format_arithmetic(assign.get_value()))
+            }
+            Statement::Return(r) => {
+                match r.get_value() {
+                    Some(value) => format!("return {};", self.format_arithmetic(value)),
+                    None => "return;".to_owned(),
+                }
+            }
+            Statement::While(w) => {
+                format!("while {} {}", self.format_arithmetic(w.get_condition()), self.format_block(w.get_body()))
+            }
+            Statement::For(f) => {
+                let iterable = match f.get_iterable() {
+                    Iterable::Range(range) => {
+                        let dots = if range.is_inclusive() { "..=" } else { ".." };
+                        format!("{}{}{}", self.format_arithmetic(range.get_start()), dots, self.format_arithmetic(range.get_end()))
+                    }
+                    Iterable::Expression(expression) => self.format_arithmetic(expression),
+                };
+                format!("for {} in {} {}", f.get_variable(), iterable, self.format_block(f.get_body()))
+            }
+            Statement::Break(_) => "break;".to_owned(),
+            Statement::Continue(_) => "continue;".to_owned(),
+        };
+
+        self.anchored(statement.get_span(), &line);
+    }
+
+    fn line(&mut self, line: &str) {
+        self.out.push_str(&INDENT.repeat(self.depth));
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    // The line of code starting at span, with the comments before, on and after it.
+    fn anchored(&mut self, span: Span, code: &str) {
+        let comments = Rc::clone(&self.comments);
+        let mut comments = comments.borrow_mut();
+        if !comments.placed {
+            comments.anchors.push(span);
+            self.line(code);
+            return;
+        }
+
+        let start = span.get_start();
+        for comment in comments.before.remove(&start).unwrap_or_default() {
+            self.line(&comment);
+        }
+        match comments.on.remove(&start) {
+            // at the end of the first line of the code
+            Some(on) => {
+                let (first, rest) = code.split_once('\n').map_or((code, ""), |(first, rest)| (first, rest));
+                let separator = if rest.is_empty() { "" } else { "\n" };
+                self.line(&format!("{} {}{}{}", first, on.join(" "), separator, rest));
+            }
+            None => self.line(code),
+        }
+        for comment in comments.after.remove(&start).unwrap_or_default() {
+            self.line(&comment);
+        }
+    }
+
+    // a formatter for the lines of a block inside the current one
+    fn nested(&self) -> Formatter {
+        Formatter {
+            out: String::new(),
+            depth: self.depth + 1,
+            comments: Rc::clone(&self.comments),
+        }
+    }
+
+    fn format_arithmetic(&self, arithmetic: &Arithmetic) -> String {
+        match arithmetic {
+            Arithmetic::Term(node) => self.format_node(node),
+            Arithmetic::MultiTerm(left, op, right) => {
+                let op_precedence = precedence(*op);
+                // operators are left-associative, so an equal one on the right needs parentheses
+                let left = self.format_operand(left, |inner| inner < op_precedence);
+                let right = self.format_operand(right, |inner| inner <= op_precedence);
+                let op = match op {
+                    Operator::Plus => "+",
+                    Operator::Minus => "-",
+                    Operator::Mul => "*",
+                    Operator::Div => "/",
+                    Operator::Rem => "%",
+                    Operator::Equal => "==",
+                    Operator::NotEqual => "!=",
+                    Operator::Less => "<",
+                    Operator::LessEqual => "<=",
+                    Operator::Greater => ">",
+                    Operator::GreaterEqual => ">=",
+                    Operator::And => "&&",
+                    Operator::Or => "||",
+                };
+                format!("{} {} {}", left, op, right)
+            }
+        }
+    }
+
+    // An arm whose body is only a value is written without braces, and ends with a `,`.
+    fn format_arm(&self, arm: &MatchArm) -> String {
+        let mut head = arm.get_pattern().to_string();
+        if let Some(guard) = arm.get_guard() {
+            head.push_str(&format!(" if {}", self.format_arithmetic(guard)));
+        }
+
+        let body = arm.get_body();
+        match (body.get_statements(), body.get_value()) {
+            ([], Some(value)) => format!("{} => {},", head, self.format_arithmetic(value)),
+            _ => format!("{} => {}", head, self.format_block(body)),
+        }
+    }
+
+    // node as an operand, in parentheses if it is an operation that needs_parentheses by its precedence
+    fn format_operand(&self, node: &Node, needs_parentheses: impl Fn(u8) -> bool) -> String {
+        match node {
+            Node::Arithmetic(arithmetic) => match arithmetic.as_ref() {
+                Arithmetic::MultiTerm(_, op, _) if needs_parentheses(precedence(*op)) => {
+                    format!("({})", self.format_arithmetic(arithmetic))
+                }
+                Arithmetic::Term(inner) => self.format_operand(inner, needs_parentheses),
+                _ => self.format_arithmetic(arithmetic),
+            },
+            _ => self.format_node(node),
+        }
+    }
+
+    fn format_node(&self, node: &Node) -> String {
+        match node {
+            Node::Number(number) => number.get_inner().to_owned(),
+            Node::Boolean(b) => b.get_value().to_string(),
+            // the escapes of Debug are the ones the tokenizer reads
+            Node::Str(s) => format!("{:?}", s.get_value()),
+            Node::Char(c) => format!("{:?}", c.get_value()),
+            Node::Variable(variable) => variable.get_name().to_owned(),
+            Node::Call(call) => {
+                let args: Vec<String> = call.get_args().iter().map(|arg| self.format_arithmetic(arg)).collect();
+                format!("{}({})", call.get_name(), args.join(", "))
+            }
+            Node::Unary(unary) => {
+                let op = match unary.get_op() {
+                    UnaryOperator::Negate => "-",
+                    UnaryOperator::Not => "!",
+                };
+                // a unary operator binds tighter than any binary one
+                format!("{}{}", op, self.format_operand(unary.get_operand(), |_| true))
+            }
+            Node::If(i) => {
+                let mut text = format!("if {} {}", self.format_arithmetic(i.get_condition()), self.format_block(i.get_then()));
+                if let Some(otherwise) = i.get_otherwise() {
+                    // an else block holding just an if is `else if`
+                    match (otherwise.get_statements(), otherwise.get_value()) {
+                        ([], Some(Arithmetic::Term(node @ Node::If(_)))) => {
+                            text.push_str(&format!(" else {}", self.format_node(node)));
+                        }
+                        _ => {
+                            text.push_str(&format!(" else {}", self.format_block(otherwise)));
+                        }
+                    }
+                }
+                text
+            }
+            Node::StructLiteral(literal) => {
+                let fields: Vec<String> = literal.get_fields().iter().map(|init| {
+                    format!("{}: {}", init.get_name(), self.format_arithmetic(init.get_value()))
+                }).collect();
+                if fields.is_empty() {
+                    format!("{} {{}}", literal.get_name())
+                } else {
+                    format!("{} {{ {} }}", literal.get_name(), fields.join(", "))
+                }
+            }
+            Node::Field(field) => {
+                // only a name, a call, a place or a tuple needs no parentheses before the `.`
+                let base = match field.get_base() {
+                    base @ (Node::Variable(_) | Node::Call(_) | Node::Field(_) | Node::Index(_) | Node::Tuple(_)) => self.format_node(base),
+                    Node::Arithmetic(arithmetic) => format!("({})", self.format_arithmetic(arithmetic)),
+                    base => format!("({})", self.format_node(base)),
+                };
+                format!("{}.{}", base, field.get_name())
+            }
+            Node::Array(array) => {
+                let elements: Vec<String> = array.get_elements().iter().map(|element| self.format_arithmetic(element)).collect();
+                format!("[{}]", elements.join(", "))
+            }
+            Node::Repeat(repeat) => {
+                format!("[{}; {}]", self.format_arithmetic(repeat.get_value()), repeat.get_len())
+            }
+            Node::Tuple(t) => {
+                let elements: Vec<String> = t.get_elements().iter().map(|element| self.format_arithmetic(element)).collect();
+                tuple(&elements)
+            }
+            Node::Index(index) => {
+                // the same bases as before a `.`
+                let base = match index.get_base() {
+                    base @ (Node::Variable(_) | Node::Call(_) | Node::Field(_) | Node::Index(_)) => self.format_node(base),
+                    Node::Arithmetic(arithmetic) => format!("({})", self.format_arithmetic(arithmetic)),
+                    base => format!("({})", self.format_node(base)),
+                };
+                format!("{}[{}]", base, self.format_arithmetic(index.get_index()))
+            }
+            Node::Variant(variant) if variant.get_args().is_empty() => {
+                format!("{}::{}", variant.get_enum_name(), variant.get_name())
+            }
+            Node::Variant(variant) => {
+                let args: Vec<String> = variant.get_args().iter().map(|arg| self.format_arithmetic(arg)).collect();
+                format!("{}::{}({})", variant.get_enum_name(), variant.get_name(), args.join(", "))
+            }
+            Node::Match(m) => {
+                let value = self.format_arithmetic(m.get_value());
+                if m.get_arms().is_empty() {
+                    return format!("match {} {{}}", value);
+                }
+
+                let mut inner = self.nested();
+                for arm in m.get_arms() {
+                    let text = inner.format_arm(arm);
+                    inner.anchored(arm.get_span(), &text);
+                }
+                format!("match {} {{\n{}{}}}", value, inner.out, INDENT.repeat(self.depth))
+            }
+            // kept, since a condition needs them around a struct literal
+            Node::Arithmetic(arithmetic) if matches!(arithmetic.as_ref(), Arithmetic::Term(Node::StructLiteral(_))) => {
+                format!("({})", self.format_arithmetic(arithmetic))
+            }
+            Node::Arithmetic(arithmetic) => self.format_arithmetic(arithmetic),
+        }
+    }
+}
+
+impl Comments {
+    fn new(tokens: &[Token]) -> Comments {
+        let mut comments = Comments::default();
+        let mut depth = 0usize;
+        for (i, token) in tokens.iter().enumerate() {
+            match token.get_t_type() {
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace => depth = depth.saturating_sub(1),
+                TokenType::Comment => {
+                    let next = tokens[i + 1..].iter()
+                        .find(|next| !matches!(next.get_t_type(), TokenType::NewLine | TokenType::Comment));
+                    comments.comments.push(Comment {
+                        text: token.get_inner().trim_end().to_owned(),
+                        span: token.get_span(),
+                        depth,
+                        trailing: i > 0 && tokens[i - 1].get_t_type() != TokenType::NewLine,
+                        closing: next.is_some_and(|next| next.get_t_type() == TokenType::RightBrace),
+                        last: next.is_none_or(|next| next.get_t_type() == TokenType::EOF),
+                    });
+                    continue;
+                }
+                _ => continue,
+            }
+            comments.braces.push((token.get_span().get_start(), depth));
+        }
+
+        comments
+    }
+
+    // Gives each comment a line of code: the one it ends, at its depth of braces or else the
+    // outermost, or the last one of its block when it closes the block, or else the one after it.
+    fn place(&mut self) {
+        let mut anchors = std::mem::take(&mut self.anchors);
+        anchors.sort_by_key(|anchor| anchor.get_start());
+        anchors.dedup_by_key(|anchor| anchor.get_start());
+
+        for comment in std::mem::take(&mut self.comments) {
+            let offset = comment.span.get_start();
+            let before = &anchors[..anchors.partition_point(|anchor| anchor.get_start() < offset)];
+            if comment.trailing {
+                let line = comment.span.get_pos().get_line();
+                let on_line = || before.iter().rev().filter(|anchor| anchor.get_pos().get_line() == line);
+                let ended = on_line().find(|anchor| self.depth_at(anchor.get_start()) == comment.depth)
+                    .or_else(|| on_line().min_by_key(|anchor| self.depth_at(anchor.get_start())));
+                if let Some(anchor) = ended {
+                    self.on.entry(anchor.get_start()).or_default().push(comment.text);
+                    continue;
+                }
+            }
+            if comment.last {
+                self.end.push(comment.text);
+                continue;
+            }
+            if comment.closing {
+                // the last line of the block, or the line that opens it when it is empty
+                let enclosing = before.iter().rev().find(|anchor| self.depth_at(anchor.get_start()) <= comment.depth);
+                if let Some(anchor) = enclosing {
+                    let comments = if self.depth_at(anchor.get_start()) == comment.depth { &mut self.after } else { &mut self.on };
+                    comments.entry(anchor.get_start()).or_default().push(comment.text);
+                    continue;
+                }
+            }
+            match anchors.get(before.len()) {
+                Some(anchor) => self.before.entry(anchor.get_start()).or_default().push(comment.text),
+                None => self.end.push(comment.text),
+            }
+        }
+
+        self.placed = true;
+    }
+
+    // Whether comments go with the line of code starting at span. Before they are placed,
+    // the line is only recorded.
+    fn go_with(&mut self, span: Span) -> bool {
+        if !self.placed {
+            self.anchors.push(span);
+            return false;
+        }
+
+        let start = span.get_start();
+        self.before.contains_key(&start) || self.on.contains_key(&start) || self.after.contains_key(&start)
+    }
+
+    // the depth of braces at offset
+    fn depth_at(&self, offset: usize) -> usize {
+        match self.braces.partition_point(|(brace, _)| *brace < offset) {
+            0 => 0,
+            i => self.braces[i - 1].1,
+        }
+    }
+}
@@ -0,0 +1,729 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{ self, Write };
+use std::panic;
+use std::sync::Arc;
+use std::thread;
+use super::ast::{
+    Program, StructDecl, FunctionDecl, Block, Statement, While, Arithmetic,
+    Node, Operator, UnaryOperator, Unary, If, StructLiteral, Index, Match,
+    MatchArm, Pattern, Call,
+};
+use super::builtins::Builtin;
+use super::diagnostic::Diagnostic;
+use super::tokenizer::Span;
+use super::types::{ IntType, tuple };
+
+// Deeper recursion is reported as a stack overflow instead of crashing the interpreter,
+// which runs on a thread with STACK_SIZE bytes of stack to get this deep.
+const MAX_CALL_DEPTH: usize = 10000;
+const STACK_SIZE: usize = 256 << 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    // an integer and its type, which it fits
+    Int(i128, IntType),
+    Bool(bool),
+    Char(char),
+    Str(String),
+    Unit,
+    // the name and the fields in declaration order
+    Struct(String, Vec<(String, Value)>),
+    Array(Vec<Value>),
+    // the enum, the variant and its fields
+    Enum(String, String, Vec<Value>),
+    Tuple(Vec<Value>),
+}
+
+// Why evaluation stopped before the end: a runtime error, or control leaving
+// the expressions and statements around it up to the function or loop.
+#[derive(Debug)]
+enum Unwind {
+    Error(Diagnostic),
+    Return(Value),
+    Break,
+    Continue,
+}
+
+// Runs a checked and lowered program directly, with the same semantics as the native backend:
+// integers wrap around their type, unless overflow_checks is on, and division by zero is an error.
+// It keeps its own copy of the functions, so it can run one piece of a program
+// after another, like the REPL does.
+#[derive(Debug, Default, Clone)]
+pub struct Interpreter {
+    structs: HashMap<String, Arc<StructDecl>>,
+    functions: HashMap<String, Arc<FunctionDecl>>,
+    // the variables of the running function, latest last
+    variables: Vec<(String, Value)>,
+    depth: usize,
+    overflow_checks: bool,
+    // what print wrote, when it is kept instead of going to stdout
+    output: Option<String>,
+}
+
+impl Value {
+    // the value as an exit status, like main returning it
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Value::Int(n, _) => (*n & 0xff) as i32,
+            Value::Bool(b) => *b as i32,
+            Value::Char(c) => (*c as i32) & 0xff,
+            Value::Str(_) | Value::Unit | Value::Struct(_, _) | Value::Array(_) | Value::Enum(_, _, _) | Value::Tuple(_) => 0,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(n, _) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", b),
+            // quoted like the literal
+            Value::Char(c) => write!(f, "{:?}", c),
+            // quoted like the literal
+            Value::Str(s) => write!(f, "{:?}", s),
+            Value::Unit => write!(f, "()"),
+            Value::Struct(name, fields) if fields.is_empty() => write!(f, "{} {{}}", name),
+            Value::Struct(name, fields) => {
+                let fields: Vec<String> = fields.iter().map(|(field, value)| format!("{}: {}", field, value)).collect();
+                write!(f, "{} {{ {} }}", name, fields.join(", "))
+            }
+            Value::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|element| element.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Value::Enum(name, variant, fields) if fields.is_empty() => write!(f, "{}::{}", name, variant),
+            Value::Enum(name, variant, fields) => {
+                let fields: Vec<String> = fields.iter().map(|field| field.to_string()).collect();
+                write!(f, "{}::{}({})", name, variant, fields.join(", "))
+            }
+            Value::Tuple(elements) => {
+                let elements: Vec<String> = elements.iter().map(|element| element.to_string()).collect();
+                write!(f, "{}", tuple(&elements))
+            }
+        }
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        Interpreter {
+            structs: HashMap::new(),
+            functions: HashMap::new(),
+            variables: Vec::new(),
+            depth: 0usize,
+            overflow_checks: false,
+            output: None,
+        }
+    }
+
+    pub fn with_overflow_checks(mut self, overflow_checks: bool) -> Interpreter {
+        self.overflow_checks = overflow_checks;
+        self
+    }
+
+    // Keeps what the program prints, to be taken with take_output, instead of writing it to stdout.
+    pub fn with_captured_output(mut self, captured: bool) -> Interpreter {
+        self.output = if captured { Some(String::new()) } else { None };
+        self
+    }
+
+    // What the program printed since the last call, when it is captured.
+    pub fn take_output(&mut self) -> String {
+        self.output.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    // The value of main: the last top-level statement, or what the main function returns.
+    pub fn run(&mut self, program: &Program) -> Result<Value, Diagnostic> {
+        for decl in program.get_structs() {
+            self.define_struct(decl);
+        }
+        for function in program.get_functions() {
+            self.define(function);
+        }
+
+        if program.has_statements() {
+            return on_big_stack(|| finish(self.execute_statements(program.get_statements())));
+        }
+
+        match self.functions.get("main").cloned() {
+            Some(main) => on_big_stack(|| self.call_function(&main, Vec::new(), main.get_span())),
+            None => Err(Diagnostic::error("E0303", "`main` function not found".to_owned())
+                .with_note("a program needs top-level statements or a `fn main()`".to_owned())),
+        }
+    }
+
+    // Makes function callable by the statements executed after.
+    pub fn define(&mut self, function: &FunctionDecl) {
+        self.functions.insert(function.get_name().to_owned(), Arc::new(function.clone()));
+    }
+
+    // Makes the struct known to the statements executed after, which need its field order.
+    pub fn define_struct(&mut self, decl: &StructDecl) {
+        self.structs.insert(decl.get_name().to_owned(), Arc::new(decl.clone()));
+    }
+
+    // Executes one top-level statement, whose variables stay in scope.
+    pub fn execute(&mut self, statement: &Statement) -> Result<Value, Diagnostic> {
+        on_big_stack(|| finish(self.execute_statement(statement)))
+    }
+
+    // The top-level variables in scope with their values, latest last.
+    pub fn get_variables(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.variables.iter().map(|(name, value)| (name.as_str(), value))
+    }
+
+    fn call_function(&mut self, function: &FunctionDecl, args: Vec<Value>, span: Span) -> Result<Value, Diagnostic> {
+        if self.depth == MAX_CALL_DEPTH {
+            let error = Diagnostic::error("E0302", format!("stack overflow calling `{}`", function.get_name()))
+                .with_label(span, format!("more than {} nested calls", MAX_CALL_DEPTH));
+            return Err(error);
+        }
+
+        // a function only sees its own parameters and locals
+        let params = function.get_params().iter().map(|param| param.get_name().to_owned()).zip(args).collect();
+        let caller = std::mem::replace(&mut self.variables, params);
+        self.depth += 1;
+
+        let value = self.execute_block(function.get_body());
+
+        self.depth -= 1;
+        self.variables = caller;
+
+        match value {
+            Ok(value) | Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(err)) => Err(err),
+            Err(Unwind::Break) | Err(Unwind::Continue) => unreachable!("the semantic pass keeps `break` and `continue` in loops"),
+        }
+    }
+
+    // The value of block, `()` unless it ends with an expression.
+    fn execute_block(&mut self, block: &Block) -> Result<Value, Unwind> {
+        // variables declared in the block go out of scope at its end
+        let scope = self.variables.len();
+        let value = self.execute_statements(block.get_statements()).and_then(|_| {
+            match block.get_value() {
+                Some(value) => self.evaluate_arithmetic(value),
+                None => Ok(Value::Unit),
+            }
+        });
+        self.variables.truncate(scope);
+
+        value
+    }
+
+    // The value of the last statement.
+    fn execute_statements(&mut self, statements: &[Statement]) -> Result<Value, Unwind> {
+        let mut last = Value::Unit;
+        for statement in statements {
+            last = self.execute_statement(statement)?;
+        }
+
+        Ok(last)
+    }
+
+    // Let and Assign have the assigned value, like in the native main.
+    fn execute_statement(&mut self, statement: &Statement) -> Result<Value, Unwind> {
+        let value = match statement {
+            Statement::Arithmetic(arithmetic) => {
+                self.evaluate_arithmetic(arithmetic)?
+            }
+            Statement::Let(l) => {
+                // declared after the initializer, so `let x = x + 1;` reads the old x
+                let value = self.evaluate_arithmetic(l.get_value())?;
+                self.variables.push((l.get_name().to_owned(), value.clone()));
+                value
+            }
+            Statement::Assign(assign) => {
+                let value = self.evaluate_arithmetic(assign.get_value())?;
+                *self.place(assign.get_target())? = value.clone();
+                value
+            }
+            Statement::Return(r) => {
+                let value = match r.get_value() {
+                    Some(value) => self.evaluate_arithmetic(value)?,
+                    None => Value::Unit,
+                };
+                return Err(Unwind::Return(value));
+            }
+            Statement::While(w) => {
+                self.execute_while(w)?
+            }
+            Statement::Destructure(_) => {
+                unreachable!("destructuring lets are lowered into lets");
+            }
+            Statement::For(_) => {
+                unreachable!("for loops are lowered into while loops");
+            }
+            Statement::Break(_) => {
+                return Err(Unwind::Break);
+            }
+            Statement::Continue(_) => {
+                return Err(Unwind::Continue);
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn execute_while(&mut self, w: &While) -> Result<Value, Unwind> {
+        while boolean(self.evaluate_arithmetic(w.get_condition())?) {
+            match self.execute_block(w.get_body()) {
+                Ok(_) | Err(Unwind::Continue) => {}
+                Err(Unwind::Break) => break,
+                Err(unwind) => return Err(unwind),
+            }
+        }
+
+        Ok(Value::Unit)
+    }
+
+    fn evaluate_arithmetic(&mut self, arithmetic: &Arithmetic) -> Result<Value, Unwind> {
+        match arithmetic {
+            Arithmetic::Term(node) => self.evaluate_node(node),
+            // the right operand only runs when it decides the result
+            Arithmetic::MultiTerm(left, op @ (Operator::And | Operator::Or), right) => {
+                let left = boolean(self.evaluate_node(left)?);
+                let value = match op {
+                    Operator::And => left && boolean(self.evaluate_node(right)?),
+                    _ => left || boolean(self.evaluate_node(right)?),
+                };
+                Ok(Value::Bool(value))
+            }
+            Arithmetic::MultiTerm(left, op, right) => {
+                let left = self.evaluate_node(left)?;
+                let right = self.evaluate_node(right)?;
+                Ok(self.evaluate_operator(*op, left, right, arithmetic.get_span())?)
+            }
+        }
+    }
+
+    fn evaluate_node(&mut self, node: &Node) -> Result<Value, Unwind> {
+        match node {
+            Node::Number(number) => {
+                let n = number.get_value().expect("the semantic pass checks the range of literals");
+                Ok(Value::Int(n, number.get_suffix().unwrap_or(IntType::I64)))
+            }
+            Node::Boolean(b) => {
+                Ok(Value::Bool(b.get_value()))
+            }
+            Node::Char(c) => {
+                Ok(Value::Char(c.get_value()))
+            }
+            Node::Str(s) => {
+                Ok(Value::Str(s.get_value().to_owned()))
+            }
+            Node::Variable(variable) => {
+                Ok(self.lookup(variable.get_name()).clone())
+            }
+            Node::Call(call) => {
+                self.evaluate_call(call)
+            }
+            Node::Unary(unary) => {
+                self.evaluate_unary(unary)
+            }
+            Node::If(i) => {
+                self.evaluate_if(i)
+            }
+            Node::StructLiteral(literal) => {
+                self.evaluate_struct_literal(literal)
+            }
+            Node::Field(field) => {
+                match self.evaluate_node(field.get_base())? {
+                    Value::Struct(_, mut fields) => {
+                        let index = fields.iter().position(|(name, _)| name == field.get_name())
+                            .expect("the semantic pass resolves every field");
+                        Ok(fields.swap_remove(index).1)
+                    }
+                    Value::Tuple(mut elements) => {
+                        Ok(elements.swap_remove(tuple_index(field.get_name())))
+                    }
+                    _ => unreachable!("the semantic pass only lets structs and tuples have fields"),
+                }
+            }
+            Node::Array(array) => {
+                let mut elements = Vec::new();
+                for element in array.get_elements() {
+                    elements.push(self.evaluate_arithmetic(element)?);
+                }
+                Ok(Value::Array(elements))
+            }
+            Node::Tuple(t) => {
+                let mut elements = Vec::new();
+                for element in t.get_elements() {
+                    elements.push(self.evaluate_arithmetic(element)?);
+                }
+                Ok(Value::Tuple(elements))
+            }
+            Node::Repeat(repeat) => {
+                let value = self.evaluate_arithmetic(repeat.get_value())?;
+                Ok(Value::Array(vec![value; repeat.get_len()]))
+            }
+            Node::Index(index) => {
+                self.evaluate_index(node, index)
+            }
+            Node::Variant(variant) => {
+                let mut fields = Vec::new();
+                for arg in variant.get_args() {
+                    fields.push(self.evaluate_arithmetic(arg)?);
+                }
+                Ok(Value::Enum(variant.get_enum_name().to_owned(), variant.get_name().to_owned(), fields))
+            }
+            Node::Match(m) => {
+                self.evaluate_match(m)
+            }
+            Node::Arithmetic(arithmetic) => {
+                self.evaluate_arithmetic(arithmetic)
+            }
+        }
+    }
+
+    // Only the element is copied out of an array in a variable.
+    fn evaluate_index(&mut self, node: &Node, index: &Index) -> Result<Value, Unwind> {
+        if node.is_place() {
+            return Ok(self.place(node)?.clone());
+        }
+
+        let mut elements = match self.evaluate_node(index.get_base())? {
+            Value::Array(elements) => elements,
+            _ => unreachable!("the semantic pass only lets arrays be indexed"),
+        };
+        let i = int(self.evaluate_arithmetic(index.get_index())?);
+        let len = elements.len();
+        match in_bounds(i, len) {
+            Some(i) => Ok(elements.swap_remove(i)),
+            None => Err(out_of_bounds(index.get_span(), len).into()),
+        }
+    }
+
+    // The first arm whose pattern matches and whose guard holds gives the value.
+    fn evaluate_match(&mut self, m: &Match) -> Result<Value, Unwind> {
+        let value = self.evaluate_arithmetic(m.get_value())?;
+        for arm in m.get_arms() {
+            // the bindings of the arm go out of scope at its end
+            let scope = self.variables.len();
+            let result = self.execute_arm(arm, &value);
+            self.variables.truncate(scope);
+
+            if let Some(value) = result? {
+                return Ok(value);
+            }
+        }
+
+        unreachable!("the semantic pass checks that every value is matched")
+    }
+
+    // The value of the arm, or None when it does not match value.
+    fn execute_arm(&mut self, arm: &MatchArm, value: &Value) -> Result<Option<Value>, Unwind> {
+        if !self.bind(arm.get_pattern(), value) {
+            return Ok(None);
+        }
+        if let Some(guard) = arm.get_guard() {
+            if !boolean(self.evaluate_arithmetic(guard)?) {
+                return Ok(None);
+            }
+        }
+
+        self.execute_block(arm.get_body()).map(Some)
+    }
+
+    // Whether value matches pattern, declaring the bindings of the pattern when it does.
+    fn bind(&mut self, pattern: &Pattern, value: &Value) -> bool {
+        match (pattern, value) {
+            (Pattern::Wildcard(_), _) => true,
+            (Pattern::Binding(name, _), _) => {
+                self.variables.push((name.clone(), value.clone()));
+                true
+            }
+            (Pattern::Number(number), Value::Int(n, _)) => {
+                number.get_value() == Some(*n)
+            }
+            (Pattern::Boolean(b), Value::Bool(value)) => {
+                b.get_value() == *value
+            }
+            (Pattern::Char(c), Value::Char(value)) => {
+                c.get_value() == *value
+            }
+            (Pattern::Variant(variant), Value::Enum(_, name, fields)) => {
+                variant.get_name() == name && variant.get_fields().iter().zip(fields).all(|(pattern, field)| self.bind(pattern, field))
+            }
+            (Pattern::Tuple(t), Value::Tuple(elements)) => {
+                t.get_fields().iter().zip(elements).all(|(pattern, element)| self.bind(pattern, element))
+            }
+            _ => unreachable!("the semantic pass checks the types of patterns"),
+        }
+    }
+
+    fn evaluate_if(&mut self, i: &If) -> Result<Value, Unwind> {
+        if boolean(self.evaluate_arithmetic(i.get_condition())?) {
+            self.execute_block(i.get_then())
+        } else if let Some(otherwise) = i.get_otherwise() {
+            self.execute_block(otherwise)
+        } else {
+            Ok(Value::Unit)
+        }
+    }
+
+    // The fields are evaluated in the order they are written and stored in declaration order.
+    fn evaluate_struct_literal(&mut self, literal: &StructLiteral) -> Result<Value, Unwind> {
+        let mut values = Vec::new();
+        for init in literal.get_fields() {
+            values.push((init.get_name(), self.evaluate_arithmetic(init.get_value())?));
+        }
+
+        let decl = self.structs[literal.get_name()].clone();
+        let fields = decl.get_fields().iter().map(|field| {
+            let index = values.iter().position(|(name, _)| *name == field.get_name())
+                .expect("the semantic pass checks that every field is initialized");
+            (field.get_name().to_owned(), values.swap_remove(index).1)
+        }).collect();
+
+        Ok(Value::Struct(literal.get_name().to_owned(), fields))
+    }
+
+    fn evaluate_unary(&mut self, unary: &Unary) -> Result<Value, Unwind> {
+        let operand = self.evaluate_node(unary.get_operand())?;
+        match unary.get_op() {
+            UnaryOperator::Negate => {
+                let (n, int_type) = integer_parts(operand);
+                let value = self.wrap(n.checked_neg(), n.wrapping_neg(), int_type, unary.get_span(), "attempt to negate with overflow")?;
+                Ok(Value::Int(value, int_type))
+            }
+            UnaryOperator::Not => {
+                Ok(Value::Bool(!boolean(operand)))
+            }
+        }
+    }
+
+    fn evaluate_operator(&mut self, op: Operator, left: Value, right: Value, span: Span) -> Result<Value, Diagnostic> {
+        // both operands have the same type, and chars are compared by their code
+        let ((left, int_type), (right, _)) = match op {
+            Operator::Equal => return Ok(Value::Bool(left == right)),
+            Operator::NotEqual => return Ok(Value::Bool(left != right)),
+            _ => (integer_parts(left), integer_parts(right)),
+        };
+        let value = match op {
+            Operator::Plus => {
+                self.wrap(left.checked_add(right), left.wrapping_add(right), int_type, span, "attempt to add with overflow")?
+            }
+            Operator::Minus => {
+                self.wrap(left.checked_sub(right), left.wrapping_sub(right), int_type, span, "attempt to subtract with overflow")?
+            }
+            Operator::Mul => {
+                self.wrap(left.checked_mul(right), left.wrapping_mul(right), int_type, span, "attempt to multiply with overflow")?
+            }
+            Operator::Div => {
+                if right == 0 {
+                    return Err(runtime_error(span, "attempt to divide by zero"));
+                }
+                self.wrap(left.checked_div(right), left.wrapping_div(right), int_type, span, "attempt to divide with overflow")?
+            }
+            Operator::Rem => {
+                if right == 0 {
+                    return Err(runtime_error(span, "attempt to calculate the remainder with a divisor of zero"));
+                }
+                // MIN % -1 is 0 as in the native backend, not an overflow
+                left % right
+            }
+            Operator::Less => return Ok(Value::Bool(left < right)),
+            Operator::LessEqual => return Ok(Value::Bool(left <= right)),
+            Operator::Greater => return Ok(Value::Bool(left > right)),
+            Operator::GreaterEqual => return Ok(Value::Bool(left >= right)),
+            Operator::Equal | Operator::NotEqual => unreachable!("compared above"),
+            Operator::And | Operator::Or => unreachable!("evaluated with short-circuiting"),
+        };
+
+        Ok(Value::Int(value, int_type))
+    }
+
+    fn evaluate_call(&mut self, call: &Call) -> Result<Value, Unwind> {
+        let mut args = Vec::new();
+        for arg in call.get_args() {
+            args.push(self.evaluate_arithmetic(arg)?);
+        }
+
+        match Builtin::from_name(call.get_name()) {
+            Some(Builtin::Len) => {
+                let len = match &args[0] {
+                    Value::Array(elements) => elements.len(),
+                    Value::Str(s) => s.len(),
+                    _ => unreachable!("the semantic pass only takes the length of arrays and strs"),
+                };
+                return Ok(Value::Int(len as i128, IntType::I64));
+            }
+            Some(builtin) => {
+                self.print(builtin, &args);
+                return Ok(Value::Unit);
+            }
+            None => {}
+        }
+
+        let function = self.functions[call.get_name()].clone();
+        Ok(self.call_function(&function, args, call.get_span())?)
+    }
+
+    // print and println. A str or a char is written as it is, without the quotes of its Display.
+    fn print(&mut self, builtin: Builtin, args: &[Value]) {
+        let mut text = String::new();
+        for arg in args {
+            match arg {
+                Value::Str(s) => text.push_str(s),
+                Value::Char(c) => text.push(*c),
+                value => text.push_str(&value.to_string()),
+            }
+        }
+        if builtin == Builtin::Println {
+            text.push('\n');
+        }
+
+        match &mut self.output {
+            Some(output) => output.push_str(&text),
+            None => {
+                // like in the native backend, a failed write goes unnoticed
+                let mut stdout = io::stdout().lock();
+                let _ = stdout.write_all(text.as_bytes()).and_then(|_| stdout.flush());
+            }
+        }
+    }
+
+    // The result of an operation on values of int_type: the exact one when the type holds it,
+    // or else the wrapped one wrapped around into the type, which is an error in checked mode.
+    fn wrap(&self, exact: Option<i128>, wrapped: i128, int_type: IntType, span: Span, message: &str) -> Result<i128, Diagnostic> {
+        match exact.filter(|n| int_type.contains(*n)) {
+            Some(n) => Ok(n),
+            None if self.overflow_checks => Err(runtime_error(span, message)),
+            None => Ok(int_type.wrap(wrapped)),
+        }
+    }
+
+    // The value a variable or a field or an element of one is stored in. The indexes
+    // are evaluated first, from the variable outwards like in the native backend.
+    fn place(&mut self, place: &Node) -> Result<&mut Value, Unwind> {
+        let mut path = Vec::new();
+        let mut root = place;
+        let name = loop {
+            match root {
+                Node::Variable(variable) => break variable.get_name(),
+                Node::Field(field) => {
+                    path.push(root);
+                    root = field.get_base();
+                }
+                Node::Index(index) => {
+                    path.push(root);
+                    root = index.get_base();
+                }
+                _ => unreachable!("the parser only assigns to variables and their fields and elements"),
+            }
+        };
+        path.reverse();
+
+        let mut indexes = Vec::new();
+        for node in path.iter() {
+            if let Node::Index(index) = node {
+                indexes.push(int(self.evaluate_arithmetic(index.get_index())?));
+            }
+        }
+
+        let mut indexes = indexes.into_iter();
+        let mut value = self.lookup(name);
+        for node in path {
+            value = match (node, value) {
+                (Node::Field(field), Value::Struct(_, fields)) => {
+                    fields.iter_mut().find(|(name, _)| name == field.get_name()).map(|(_, value)| value)
+                        .expect("the semantic pass resolves every field")
+                }
+                (Node::Field(field), Value::Tuple(elements)) => {
+                    &mut elements[tuple_index(field.get_name())]
+                }
+                (Node::Index(index), Value::Array(elements)) => {
+                    let i = indexes.next().expect("every index is evaluated");
+                    let len = elements.len();
+                    match in_bounds(i, len) {
+                        Some(i) => &mut elements[i],
+                        None => return Err(out_of_bounds(index.get_span(), len).into()),
+                    }
+                }
+                _ => unreachable!("the semantic pass only lets structs and tuples have fields and arrays be indexed"),
+            };
+        }
+
+        Ok(value)
+    }
+
+    // The latest declaration wins, which is what shadowing needs.
+    fn lookup(&mut self, name: &str) -> &mut Value {
+        self.variables.iter_mut().rev().find(|(variable, _)| *variable == name).map(|(_, value)| value)
+            .expect("the semantic pass resolves every variable")
+    }
+}
+
+// Runs f on a thread with STACK_SIZE bytes of stack, for deeply recursive programs.
+fn on_big_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, f)
+            .expect("the interpreter thread starts")
+            .join()
+            .unwrap_or_else(|payload| panic::resume_unwind(payload))
+    })
+}
+
+impl From<Diagnostic> for Unwind {
+    fn from(err: Diagnostic) -> Unwind {
+        Unwind::Error(err)
+    }
+}
+
+// The result of top-level code, where `return` ends main.
+fn finish(value: Result<Value, Unwind>) -> Result<Value, Diagnostic> {
+    match value {
+        Ok(value) | Err(Unwind::Return(value)) => Ok(value),
+        Err(Unwind::Error(err)) => Err(err),
+        Err(Unwind::Break) | Err(Unwind::Continue) => unreachable!("the semantic pass keeps `break` and `continue` in loops"),
+    }
+}
+
+// The semantic pass only lets integers into arithmetic, and chars into comparisons,
+// which are made by their code.
+fn integer_parts(value: Value) -> (i128, IntType) {
+    match value {
+        Value::Int(n, int_type) => (n, int_type),
+        Value::Char(c) => (c as i128, IntType::U32),
+        Value::Bool(_) | Value::Str(_) | Value::Unit | Value::Struct(_, _) | Value::Array(_) | Value::Enum(_, _, _) | Value::Tuple(_) => unreachable!("the semantic pass only lets integers into arithmetic"),
+    }
+}
+
+// An index, which may be of any integer type. One too large for an i64 is out of bounds anyway.
+fn int(value: Value) -> i64 {
+    let (n, _) = integer_parts(value);
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
+
+// and only bools into conditions and logic
+fn boolean(value: Value) -> bool {
+    match value {
+        Value::Bool(b) => b,
+        Value::Int(_, _) | Value::Char(_) | Value::Str(_) | Value::Unit | Value::Struct(_, _) | Value::Array(_) | Value::Enum(_, _, _) | Value::Tuple(_) => unreachable!("the semantic pass only lets `bool` into conditions"),
+    }
+}
+
+// the element a tuple field like `t.0` names
+fn tuple_index(name: &str) -> usize {
+    name.parse().expect("the semantic pass resolves every field")
+}
+
+// i as an index into len elements, unless it is out of bounds
+fn in_bounds(i: i64, len: usize) -> Option<usize> {
+    usize::try_from(i).ok().filter(|i| *i < len)
+}
+
+fn out_of_bounds(span: Span, len: usize) -> Diagnostic {
+    runtime_error(span, &format!("index out of bounds: the len is {}", len))
+}
+
+fn runtime_error(span: Span, message: &str) -> Diagnostic {
+    Diagnostic::error("E0301", message.to_owned())
+        .with_label(span, "evaluated here".to_owned())
+}
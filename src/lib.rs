@@ -1,5 +1,12 @@
 pub mod tokenizer;
 pub mod parser;
 pub mod semantic;
+pub mod lower;
+pub mod types;
+pub mod builtins;
 pub mod codegen;
 pub mod ast;
+pub mod diagnostic;
+pub mod interp;
+pub mod repl;
+pub mod formatter;
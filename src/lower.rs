@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use super::ast::{
+    Program, FunctionDecl, Block, Statement, Let, Destructure, Assign, Return,
+    While, For, Iterable, Range, Arithmetic, Node, Number, Variable, Unary, If,
+    StructLiteral, FieldInit, FieldAccess, ArrayLiteral, ArrayRepeat, TupleLiteral,
+    Index, VariantLiteral, Match, MatchArm, Pattern, Call, Operator, UnaryOperator,
+};
+use super::tokenizer::Span;
+use super::types::IntType;
+
+// Variables of the lowered for loops. Source code cannot spell these names,
+// so they never clash with its own; nested loops shadow the outer ones.
+const NEXT: &str = "for.next";
+const END: &str = "for.end";
+const MORE: &str = "for.more";
+const ARRAY: &str = "for.array";
+// the value of a destructuring let
+const TUPLE: &str = "let.tuple";
+
+/*
+    Rewrites a checked program into the smaller language the interpreter and the
+    backends run, which has no for loops and no destructuring lets:
+
+        for i in start..end { body }        for i in start..=end { body }
+
+        let mut for.next = start;           let mut for.next = start;
+        let for.end = end;                  let for.end = end;
+                                            let mut for.more = for.next <= for.end;
+        while for.next < for.end {          while for.more {
+            let i = for.next;                   let i = for.next;
+            for.next = for.next + 1;            for.more = for.next < for.end;
+                                                if for.more { for.next = for.next + 1; }
+            body                                body
+        }                                   }
+
+    The step comes before the body so `continue` does not skip it, and an inclusive
+    range stops before stepping past its end, which could overflow.
+
+    An array is iterated over by index, on a copy so the body may change the original:
+
+        for x in array { body }
+
+        let for.array = array;
+        let mut for.next = 0;
+        while for.next < len(for.array) {
+            let x = for.array[for.next];
+            for.next = for.next + 1;
+            body
+        }
+
+    A destructuring let reads the fields of its value one binding at a time:
+
+        let (a, (_, b)) = value;
+
+        let let.tuple = value;
+        let a = let.tuple.0;
+        let b = let.tuple.1.1;
+
+    Every integer literal is given the suffix of its type, which types holds for the ones
+    the semantic pass found without one, so `let x: u8 = 1;` becomes `let x: u8 = 1u8;`.
+    The 1 a range steps by gets the type of the range.
+    A negated literal becomes one negative literal, like `-128i8`, so no backend
+    ever negates the minimum of a type at runtime.
+*/
+pub fn lower(program: &Program, types: &HashMap<Span, IntType>) -> Program {
+    let mut lowered = Program::new();
+    for decl in program.get_structs() {
+        lowered.push_struct(decl.clone());
+    }
+    for decl in program.get_enums() {
+        lowered.push_enum(decl.clone());
+    }
+    for function in program.get_functions() {
+        let body = lower_block(function.get_body(), types);
+        let return_type = function.get_return_type().cloned();
+        lowered.push_function(FunctionDecl::new(function.get_name().to_owned(), function.get_params().to_vec(),
+                                                return_type, body, function.get_span()));
+    }
+    for statement in program.get_statements() {
+        for statement in lower_statement(statement, types) {
+            lowered.push(statement);
+        }
+    }
+
+    lowered
+}
+
+fn lower_block(block: &Block, types: &HashMap<Span, IntType>) -> Block {
+    let mut lowered = Block::new();
+    for statement in block.get_statements() {
+        for statement in lower_statement(statement, types) {
+            lowered.push(statement);
+        }
+    }
+    if let Some(value) = block.get_value() {
+        lowered.set_value(lower_arithmetic(value, types));
+    }
+
+    lowered
+}
+
+fn lower_statement(statement: &Statement, types: &HashMap<Span, IntType>) -> Vec<Statement> {
+    let lowered = match statement {
+        Statement::Arithmetic(arithmetic) => {
+            Statement::Arithmetic(lower_arithmetic(arithmetic, types))
+        }
+        Statement::Let(l) => {
+            let type_name = l.get_type_name().cloned();
+            Statement::Let(Let::new(l.get_name().to_owned(), l.is_mutable(), type_name, lower_arithmetic(l.get_value(), types), l.get_span()))
+        }
+        Statement::Destructure(destructure) => {
+            return lower_destructure(destructure, types);
+        }
+        Statement::Assign(assign) => {
+            Statement::Assign(Assign::new(lower_node(assign.get_target(), types), lower_arithmetic(assign.get_value(), types), assign.get_span()))
+        }
+        Statement::Return(r) => {
+            Statement::Return(Return::new(r.get_value().map(|arithmetic| lower_arithmetic(arithmetic, types)), r.get_span()))
+        }
+        Statement::While(w) => {
+            Statement::While(While::new(lower_arithmetic(w.get_condition(), types), lower_block(w.get_body(), types), w.get_span()))
+        }
+        Statement::For(f) => {
+            return lower_for(f, types);
+        }
+        Statement::Break(_) | Statement::Continue(_) => {
+            statement.clone()
+        }
+    };
+
+    vec![lowered]
+}
+
+fn lower_for(f: &For, types: &HashMap<Span, IntType>) -> Vec<Statement> {
+    match f.get_iterable() {
+        Iterable::Range(range) => lower_for_range(f, range, types),
+        // the semantic pass only lets arrays be iterated besides ranges
+        Iterable::Expression(array) => lower_for_array(f, array, types),
+    }
+}
+
+fn lower_for_range(f: &For, range: &Range, types: &HashMap<Span, IntType>) -> Vec<Statement> {
+    let span = f.get_span();
+    let mut statements = vec![
+        let_statement(NEXT, true, lower_arithmetic(range.get_start(), types), span),
+        let_statement(END, false, lower_arithmetic(range.get_end(), types), span),
+    ];
+
+    let mut body = Block::new();
+    body.push(let_statement(f.get_variable(), false, Arithmetic::Term(variable(NEXT, span)), span));
+    let step = Statement::Assign(Assign::new(variable(NEXT, span), binary(NEXT, Operator::Plus, step(span, types), span), span));
+    let condition = if range.is_inclusive() {
+        statements.push(let_statement(MORE, true, binary(NEXT, Operator::LessEqual, variable(END, span), span), span));
+        body.push(Statement::Assign(Assign::new(variable(MORE, span), binary(NEXT, Operator::Less, variable(END, span), span), span)));
+
+        let mut then = Block::new();
+        then.push(step);
+        let step_if_more = If::new(Arithmetic::Term(variable(MORE, span)), then, None, span);
+        body.push(Statement::Arithmetic(Arithmetic::Term(Node::If(Box::new(step_if_more)))));
+
+        Arithmetic::Term(variable(MORE, span))
+    } else {
+        body.push(step);
+
+        binary(NEXT, Operator::Less, variable(END, span), span)
+    };
+
+    push_body(&mut body, f.get_body(), types);
+
+    statements.push(Statement::While(While::new(condition, body, span)));
+    statements
+}
+
+fn lower_for_array(f: &For, array: &Arithmetic, types: &HashMap<Span, IntType>) -> Vec<Statement> {
+    let span = f.get_span();
+    let mut statements = vec![
+        let_statement(ARRAY, false, lower_arithmetic(array, types), span),
+        let_statement(NEXT, true, Arithmetic::Term(number(0, span)), span),
+    ];
+
+    let mut body = Block::new();
+    let element = Index::new(variable(ARRAY, span), Arithmetic::Term(variable(NEXT, span)), span);
+    body.push(let_statement(f.get_variable(), false, Arithmetic::Term(Node::Index(element)), span));
+    body.push(Statement::Assign(Assign::new(variable(NEXT, span), binary(NEXT, Operator::Plus, number(1, span), span), span)));
+    push_body(&mut body, f.get_body(), types);
+
+    let len = Call::new("len".to_owned(), vec![Arithmetic::Term(variable(ARRAY, span))], span);
+    let condition = binary(NEXT, Operator::Less, Node::Call(len), span);
+    statements.push(Statement::While(While::new(condition, body, span)));
+    statements
+}
+
+fn lower_destructure(destructure: &Destructure, types: &HashMap<Span, IntType>) -> Vec<Statement> {
+    let span = destructure.get_span();
+    let value = lower_arithmetic(destructure.get_value(), types);
+    let mut statements = vec![Statement::Let(Let::new(TUPLE.to_owned(), false, destructure.get_type_name().cloned(), value, span))];
+    bind_fields(destructure.get_pattern(), variable(TUPLE, span), &mut statements);
+    statements
+}
+
+// A let for each binding in pattern, of its part of the value at place.
+fn bind_fields(pattern: &Pattern, place: Node, statements: &mut Vec<Statement>) {
+    match pattern {
+        Pattern::Wildcard(_) => {}
+        Pattern::Binding(name, span) => {
+            statements.push(let_statement(name, false, Arithmetic::Term(place), *span));
+        }
+        Pattern::Tuple(tuple) => {
+            for (i, field) in tuple.get_fields().iter().enumerate() {
+                let element = FieldAccess::new(place.clone(), i.to_string(), field.get_span());
+                bind_fields(field, Node::Field(element), statements);
+            }
+        }
+        Pattern::Number(_) | Pattern::Boolean(_) | Pattern::Char(_) | Pattern::Variant(_) => {
+            unreachable!("the parser only destructures tuples in a let");
+        }
+    }
+}
+
+// Appends the lowered body of a for loop to the body of its while loop.
+fn push_body(body: &mut Block, for_body: &Block, types: &HashMap<Span, IntType>) {
+    let lowered = lower_block(for_body, types);
+    for statement in lowered.get_statements() {
+        body.push(statement.clone());
+    }
+    if let Some(value) = lowered.get_value() {
+        body.set_value(value.clone());
+    }
+}
+
+fn lower_arithmetic(arithmetic: &Arithmetic, types: &HashMap<Span, IntType>) -> Arithmetic {
+    match arithmetic {
+        Arithmetic::Term(node) => Arithmetic::Term(lower_node(node, types)),
+        Arithmetic::MultiTerm(left, op, right) => Arithmetic::MultiTerm(lower_node(left, types), *op, lower_node(right, types)),
+    }
+}
+
+fn lower_node(node: &Node, types: &HashMap<Span, IntType>) -> Node {
+    match node {
+        Node::Call(call) => {
+            let args = call.get_args().iter().map(|arithmetic| lower_arithmetic(arithmetic, types)).collect();
+            Node::Call(Call::new(call.get_name().to_owned(), args, call.get_span()))
+        }
+        Node::Unary(unary) => {
+            let operand = lower_node(unary.get_operand(), types);
+            match (unary.get_op(), operand) {
+                (UnaryOperator::Negate, Node::Number(number)) if !number.get_inner().starts_with('-') => {
+                    Node::Number(Number::new(format!("-{}", number.get_inner()), unary.get_span()))
+                }
+                (op, operand) => Node::Unary(Unary::new(op, operand, unary.get_span())),
+            }
+        }
+        Node::If(i) => {
+            let otherwise = i.get_otherwise().map(|block| lower_block(block, types));
+            Node::If(Box::new(If::new(lower_arithmetic(i.get_condition(), types), lower_block(i.get_then(), types), otherwise, i.get_span())))
+        }
+        Node::StructLiteral(literal) => {
+            let fields = literal.get_fields().iter().map(|init| {
+                FieldInit::new(init.get_name().to_owned(), lower_arithmetic(init.get_value(), types), init.get_span())
+            }).collect();
+            Node::StructLiteral(StructLiteral::new(literal.get_name().to_owned(), fields, literal.get_span()))
+        }
+        Node::Field(field) => {
+            Node::Field(FieldAccess::new(lower_node(field.get_base(), types), field.get_name().to_owned(), field.get_span()))
+        }
+        Node::Array(array) => {
+            let elements = array.get_elements().iter().map(|arithmetic| lower_arithmetic(arithmetic, types)).collect();
+            Node::Array(ArrayLiteral::new(elements, array.get_span()))
+        }
+        Node::Repeat(repeat) => {
+            Node::Repeat(ArrayRepeat::new(lower_arithmetic(repeat.get_value(), types), repeat.get_len(), repeat.get_span()))
+        }
+        Node::Tuple(tuple) => {
+            let elements = tuple.get_elements().iter().map(|arithmetic| lower_arithmetic(arithmetic, types)).collect();
+            Node::Tuple(TupleLiteral::new(elements, tuple.get_span()))
+        }
+        Node::Index(index) => {
+            Node::Index(Index::new(lower_node(index.get_base(), types), lower_arithmetic(index.get_index(), types), index.get_span()))
+        }
+        Node::Variant(variant) => {
+            let args = variant.get_args().iter().map(|arithmetic| lower_arithmetic(arithmetic, types)).collect();
+            Node::Variant(Box::new(VariantLiteral::new(variant.get_enum_name().to_owned(), variant.get_name().to_owned(), args, variant.get_span())))
+        }
+        Node::Match(m) => {
+            let arms = m.get_arms().iter().map(|arm| {
+                let guard = arm.get_guard().map(|arithmetic| lower_arithmetic(arithmetic, types));
+                MatchArm::new(arm.get_pattern().clone(), guard, lower_block(arm.get_body(), types), arm.get_span())
+            }).collect();
+            Node::Match(Box::new(Match::new(lower_arithmetic(m.get_value(), types), arms, m.get_span())))
+        }
+        Node::Arithmetic(arithmetic) => {
+            Node::Arithmetic(Box::new(lower_arithmetic(arithmetic, types)))
+        }
+        Node::Number(number) if number.get_suffix().is_none() => {
+            let int_type = types.get(&number.get_span()).copied().unwrap_or(IntType::I64);
+            Node::Number(Number::new(format!("{}{}", number.get_inner(), int_type.get_suffix()), number.get_span()))
+        }
+        Node::Number(_) | Node::Boolean(_) | Node::Str(_) | Node::Char(_) | Node::Variable(_) => {
+            node.clone()
+        }
+    }
+}
+
+fn let_statement(name: &str, mutable: bool, value: Arithmetic, span: Span) -> Statement {
+    Statement::Let(Let::new(name.to_owned(), mutable, None, value, span))
+}
+
+// the variable left op right
+fn binary(left: &str, op: Operator, right: Node, span: Span) -> Arithmetic {
+    Arithmetic::MultiTerm(variable(left, span), op, right)
+}
+
+fn variable(name: &str, span: Span) -> Node {
+    Node::Variable(Variable::new(name.to_owned(), span))
+}
+
+// The 1 a range steps by, of the type of the range, which the semantic pass
+// gives at the span of its loop.
+fn step(span: Span, types: &HashMap<Span, IntType>) -> Node {
+    let int_type = types.get(&span).copied().unwrap_or(IntType::I64);
+    Node::Number(Number::new(format!("1{}", int_type.get_suffix()), span))
+}
+
+fn number(n: i64, span: Span) -> Node {
+    Node::Number(Number::new(n.to_string(), span))
+}
@@ -1,61 +1,407 @@
 extern crate my_lang;
 
-use my_lang::tokenizer::Tokenizer;
+use my_lang::tokenizer::{ Tokenizer, Token, TokenType };
 use my_lang::parser::Parser;
-use my_lang::ast::Ast;
+use my_lang::semantic::TypeChecker;
+use my_lang::lower::lower;
+use my_lang::codegen::{ Backend, X86_64, PANIC_STATUS };
+use my_lang::ast::Program;
+use my_lang::interp::Interpreter;
+use my_lang::repl::Repl;
+use my_lang::formatter::Formatter;
+use my_lang::diagnostic::{ Diagnostic, Renderer };
 use std::env;
-use std::fs::File;
+use std::fs::{ self, File };
+use std::io;
 use std::io::prelude::*;
+use std::path::{ Path, PathBuf };
+use std::process::{ self, Command };
+
+const USAGE: &str = "\
+usage: my_lang <command> [options] <file>
+
+commands:
+    build     compile the file, into an executable unless --emit says otherwise
+    run       interpret the file, exiting with the status the executable would
+    check     report the errors in the file without compiling it
+    fmt       rewrite the file in the canonical layout
+    repl      read and run code interactively
+
+options:
+    --emit=<kind>        tokens, ast, ir, asm, obj or exe (build only)
+    -o <file>            write the output to file, `-` for stdout (text only)
+    -O<level>            optimization level 0 or 1, -O is -O1
+    --overflow-checks    abort on integer overflow instead of wrapping
+
+<file> is `-` to read stdin.";
+
+// exit statuses besides 0 and those of the programs run
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Subcommand {
+    Build,
+    Run,
+    Check,
+    Fmt,
+    Repl,
+}
+
+// What build produces
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Emit {
+    Tokens,
+    Ast,
+    // the checked and lowered program the backends translate
+    Ir,
+    Asm,
+    Obj,
+    Exe,
+}
+
+#[derive(Debug)]
+struct Options {
+    subcommand: Subcommand,
+    // `-` is stdin
+    input: Option<String>,
+    emit: Emit,
+    output: Option<PathBuf>,
+    optimization: u8,
+    overflow_checks: bool,
+}
+
+// A file being compiled and the name diagnostics give it.
+#[derive(Debug)]
+struct Source {
+    name: String,
+    contents: String,
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() == 2 {
-        let mut file = match File::open(&args[1]) {
-            Ok(file) => file,
-            Err(err) => {
-                println!("err: {:?}", err);
-                return;
-            }
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match Options::parse(&args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+
+    if options.subcommand == Subcommand::Repl {
+        process::exit(repl(&options));
+    }
+
+    let input = options.input.as_deref().expect("the options have an input");
+    let source = match Source::read(input) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("error: cannot read `{}`: {}", input, err);
+            process::exit(EXIT_ERROR);
+        }
+    };
+
+    let status = match options.subcommand {
+        Subcommand::Build => build(&options, &source),
+        Subcommand::Run => run(&options, &source),
+        Subcommand::Check => check(&source),
+        Subcommand::Fmt => fmt(&options, &source),
+        Subcommand::Repl => unreachable!("handled above"),
+    };
+    process::exit(status);
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let subcommand = match args.first().map(String::as_str) {
+            Some("build") => Subcommand::Build,
+            Some("run") => Subcommand::Run,
+            Some("check") => Subcommand::Check,
+            Some("fmt") => Subcommand::Fmt,
+            Some("repl") => Subcommand::Repl,
+            Some(other) => return Err(format!("unknown command `{}`", other)),
+            None => return Err("no command given".to_owned()),
         };
-        let mut contents = String::new();
-        match file.read_to_string(&mut contents) {
-            Ok(_) => {}
-            Err(err) => {
-                println!("err: {:?}", err);
-                return;
-            }
+
+        let mut options = Options {
+            subcommand,
+            input: None,
+            emit: Emit::Exe,
+            output: None,
+            optimization: 0u8,
+            overflow_checks: false,
         };
 
-        let mut tokenizer = Tokenizer::new(contents.chars().collect());
-        let mut tokens = match tokenizer.tokenize() {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("{}", err);
-                return;
+        let mut args = args[1..].iter();
+        while let Some(arg) = args.next() {
+            if let Some(emit) = arg.strip_prefix("--emit=") {
+                if subcommand != Subcommand::Build {
+                    return Err("only build takes --emit".to_owned());
+                }
+                options.emit = match emit {
+                    "tokens" => Emit::Tokens,
+                    "ast" => Emit::Ast,
+                    "ir" => Emit::Ir,
+                    "asm" => Emit::Asm,
+                    "obj" => Emit::Obj,
+                    "exe" => Emit::Exe,
+                    _ => return Err(format!("unknown --emit kind `{}`", emit)),
+                };
+            } else if let Some(level) = arg.strip_prefix("-O") {
+                options.optimization = match level {
+                    "" => 1,
+                    "0" | "1" => level.parse().expect("a digit"),
+                    _ => return Err(format!("unknown optimization level `{}`", level)),
+                };
+            } else if arg == "-o" {
+                match args.next() {
+                    Some(output) => options.output = Some(PathBuf::from(output)),
+                    None => return Err("-o needs a file name".to_owned()),
+                }
+            } else if arg == "--overflow-checks" {
+                options.overflow_checks = true;
+            } else if arg.starts_with('-') && arg != "-" {
+                return Err(format!("unknown option `{}`", arg));
+            } else if options.input.is_some() {
+                return Err(format!("more than one input file, `{}`", arg));
+            } else {
+                options.input = Some(arg.to_owned());
             }
+        }
+
+        if options.input.is_none() && subcommand != Subcommand::Repl {
+            return Err("no input file given".to_owned());
+        }
+        // objects and executables are binary files, never written to stdout
+        if options.output.as_ref().is_some_and(|output| output.as_os_str() == "-")
+            && matches!(options.emit, Emit::Obj | Emit::Exe) && subcommand == Subcommand::Build {
+            return Err("cannot write an object or an executable to stdout, give -o a file".to_owned());
+        }
+
+        Ok(options)
+    }
+
+    // Where the output goes, None for stdout. Text goes to stdout
+    // by default, files are named after the input.
+    fn output_path(&self) -> Option<PathBuf> {
+        if let Some(output) = &self.output {
+            return if output.as_os_str() == "-" { None } else { Some(output.clone()) };
+        }
+
+        let input = self.input.as_deref().unwrap_or("-");
+        let stem = match input {
+            "-" => PathBuf::from("a"),
+            _ => PathBuf::from(Path::new(input).file_stem().unwrap_or_default()),
+        };
+        match self.emit {
+            Emit::Obj => Some(stem.with_extension("o")),
+            Emit::Exe if input == "-" => Some(PathBuf::from("a.out")),
+            Emit::Exe => Some(stem),
+            _ => None,
+        }
+    }
+}
+
+impl Source {
+    fn read(input: &str) -> io::Result<Source> {
+        let mut contents = String::new();
+        let name = if input == "-" {
+            io::stdin().read_to_string(&mut contents)?;
+            "<stdin>".to_owned()
+        } else {
+            File::open(input)?.read_to_string(&mut contents)?;
+            input.to_owned()
         };
 
-        dbg!(&tokens);
+        Ok(Source {
+            name,
+            contents,
+        })
+    }
+
+    fn report(&self, errs: &[Diagnostic]) {
+        let renderer = Renderer::new(&self.name, &self.contents);
+        for err in errs {
+            eprintln!("{}", renderer.render(err));
+        }
+    }
 
-        let mut parser = Parser::new(tokens);
-        let mut asts = match parser.parse() {
-            Ok(asts) => asts,
+    fn tokenize(&self) -> Option<Vec<Token>> {
+        match Tokenizer::new(self.contents.chars().collect()).tokenize() {
+            Ok(tokens) => Some(tokens),
             Err(errs) => {
-                for err in errs {
-                    println!("{}", err);
+                self.report(&errs);
+                None
+            }
+        }
+    }
+
+    fn parse(&self) -> Option<Program> {
+        match Parser::new(self.tokenize()?).parse() {
+            Ok(program) => Some(program),
+            Err(errs) => {
+                self.report(&errs);
+                None
+            }
+        }
+    }
+
+    // The checked and lowered program, or None after reporting the errors. Warnings are
+    // reported either way.
+    fn analyze(&self) -> Option<Program> {
+        let program = self.parse()?;
+        let mut checker = TypeChecker::new();
+        let checked = checker.check(&program);
+        self.report(&checker.take_warnings());
+        if let Err(errs) = checked {
+            self.report(&errs);
+            return None;
+        }
+
+        Some(lower(&program, &checker.take_literal_types()))
+    }
+}
+
+fn build(options: &Options, source: &Source) -> i32 {
+    let output = options.output_path();
+
+    let text = match options.emit {
+        Emit::Tokens => source.tokenize().map(|tokens| {
+            tokens.iter().filter(|token| token.get_t_type() != TokenType::EOF)
+                .map(|token| format!("{}\n", token)).collect::<String>().into_bytes()
+        }),
+        Emit::Ast => source.parse().map(|program| format!("{:#?}\n", program).into_bytes()),
+        Emit::Ir => source.analyze().map(|program| format!("{:#?}\n", program).into_bytes()),
+        Emit::Asm | Emit::Obj | Emit::Exe => source.analyze().and_then(|program| {
+            let mut assembly = Vec::new();
+            let mut backend = X86_64::new()
+                .with_file(&source.name)
+                .with_overflow_checks(options.overflow_checks)
+                .with_optimization(options.optimization);
+            match backend.generate(&program, &mut assembly) {
+                Ok(()) => Some(assembly),
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    None
                 }
-                return;
             }
-        };
+        }),
+    };
+    let text = match text {
+        Some(text) => text,
+        None => return EXIT_ERROR,
+    };
+
+    let written = match (options.emit, &output) {
+        (Emit::Obj, Some(output)) => assemble(&text, output),
+        (Emit::Exe, Some(output)) => link(&text, output),
+        (_, Some(output)) => fs::write(output, &text),
+        (_, None) => io::stdout().write_all(&text),
+    };
+    if let Err(err) = written {
+        eprintln!("error: {}", err);
+        return EXIT_ERROR;
+    }
+
+    0
+}
 
-        dbg!(&asts);
+fn run(options: &Options, source: &Source) -> i32 {
+    let program = match source.analyze() {
+        Some(program) => program,
+        None => return EXIT_ERROR,
+    };
 
-        asts.check_semantic();
+    match Interpreter::new().with_overflow_checks(options.overflow_checks).run(&program) {
+        Ok(value) => value.exit_code(),
+        Err(err) => {
+            source.report(&[err]);
+            PANIC_STATUS
+        }
+    }
+}
+
+fn check(source: &Source) -> i32 {
+    match source.analyze() {
+        Some(_) => 0,
+        None => EXIT_ERROR,
+    }
+}
 
-        asts.generate_code();
+// Formats in place, or to -o, or to stdout when reading stdin.
+fn fmt(options: &Options, source: &Source) -> i32 {
+    let tokens = match source.tokenize() {
+        Some(tokens) => tokens,
+        None => return EXIT_ERROR,
+    };
+    let program = match source.parse() {
+        Some(program) => program,
+        None => return EXIT_ERROR,
+    };
+    let formatted = Formatter::new().with_comments(&tokens).format(&program);
 
-    } else {
-        println!("usage: {} <filepath>", args[0]);
-        return;
+    let output = match &options.output {
+        Some(output) if output.as_os_str() != "-" => Some(output.clone()),
+        Some(_) => None,
+        None if source.name == "<stdin>" => None,
+        None => Some(PathBuf::from(&source.name)),
+    };
+    let written = match output {
+        Some(output) => fs::write(output, formatted),
+        None => io::stdout().write_all(formatted.as_bytes()),
+    };
+    if let Err(err) = written {
+        eprintln!("error: {}", err);
+        return EXIT_ERROR;
     }
+
+    0
+}
+
+fn repl(options: &Options) -> i32 {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut repl = Repl::new().with_overflow_checks(options.overflow_checks);
+    if let Err(err) = repl.run(&mut stdin.lock(), &mut stdout.lock()) {
+        eprintln!("error: {}", err);
+        return EXIT_ERROR;
+    }
+
+    0
+}
+
+// Writes the assembly to a temporary file and assembles it with the system `as`.
+fn assemble(assembly: &[u8], output: &Path) -> io::Result<()> {
+    let source = temporary("s");
+    fs::write(&source, assembly)?;
+
+    let assembled = execute(Command::new("as").arg("-o").arg(output).arg(&source));
+    fs::remove_file(&source)?;
+
+    assembled
+}
+
+// Assembles and links with `cc`, so the program gets the C runtime.
+fn link(assembly: &[u8], output: &Path) -> io::Result<()> {
+    let object = temporary("o");
+    assemble(assembly, &object)?;
+
+    let linked = execute(Command::new("cc").arg("-o").arg(output).arg(&object));
+    fs::remove_file(&object)?;
+
+    linked
+}
+
+// An intermediate file of this process, which is never one the user named.
+fn temporary(extension: &str) -> PathBuf {
+    env::temp_dir().join(format!("my_lang-{}.{}", process::id(), extension))
+}
+
+fn execute(command: &mut Command) -> io::Result<()> {
+    let status = command.status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("{:?} failed with {}", command, status)));
+    }
+
+    Ok(())
 }
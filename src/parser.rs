@@ -1,49 +1,113 @@
+use std::convert::TryFrom;
 use super::ast::{
-    Program, Statement, Arithmetic, Node, Number,
-    Operator,
+    Program, StructDecl, FieldDecl, EnumDecl, VariantDecl, FunctionDecl, Parameter,
+    Block, Statement, Let, Destructure, Assign, Return, While, For, Iterable, Range,
+    Arithmetic, Node, Number, Boolean, Str, Char, Variable, Unary, If, StructLiteral,
+    FieldInit, FieldAccess, ArrayLiteral, ArrayRepeat, TupleLiteral, Index,
+    VariantLiteral, Match, MatchArm, Pattern, VariantPattern, TuplePattern, Call,
+    Operator, UnaryOperator, TypeName,
 };
-use super::tokenizer::{ Token, TokenType };
+use super::tokenizer::{ Token, TokenType, Span };
+use super::diagnostic::Diagnostic;
 
 #[derive(Debug)]
 pub struct Parser {
     index: usize,
     tokens: Vec<Token>,
     len: usize,
-    state: ParseState,
-    err_handler: Vec<String>,
+    err_handler: Vec<Diagnostic>,
     open_paren_count: u32,
+    // false in conditions, where the `{` after a name starts the block
+    struct_literals: bool,
 }
 
 #[derive(Debug)]
-pub struct ParseErrorHandler {
-    err: Vec<String>
-}
+pub struct ParseErrorHandler;
 
 #[derive(Debug, Clone, Copy)]
-pub enum ParseState {
-    Normal,
-    Arithmetic,
+struct BinaryOperator {
+    token: TokenType,
+    op: Operator,
+    // higher binds tighter
+    precedence: u8,
 }
 
+// Every binary operator the parser knows, all left-associative.
+// A new operator is one more entry here.
+const BINARY_OPERATORS: [BinaryOperator; 13] = [
+    BinaryOperator { token: TokenType::OrOr, op: Operator::Or, precedence: 4 },
+    BinaryOperator { token: TokenType::AndAnd, op: Operator::And, precedence: 6 },
+    BinaryOperator { token: TokenType::EqualEqual, op: Operator::Equal, precedence: 8 },
+    BinaryOperator { token: TokenType::BangEqual, op: Operator::NotEqual, precedence: 8 },
+    BinaryOperator { token: TokenType::Less, op: Operator::Less, precedence: 8 },
+    BinaryOperator { token: TokenType::LessEqual, op: Operator::LessEqual, precedence: 8 },
+    BinaryOperator { token: TokenType::Greater, op: Operator::Greater, precedence: 8 },
+    BinaryOperator { token: TokenType::GreaterEqual, op: Operator::GreaterEqual, precedence: 8 },
+    BinaryOperator { token: TokenType::Plus, op: Operator::Plus, precedence: 10 },
+    BinaryOperator { token: TokenType::Minus, op: Operator::Minus, precedence: 10 },
+    BinaryOperator { token: TokenType::Asterisk, op: Operator::Mul, precedence: 20 },
+    BinaryOperator { token: TokenType::Slash, op: Operator::Div, precedence: 20 },
+    BinaryOperator { token: TokenType::Percent, op: Operator::Rem, precedence: 20 },
+];
+
 /*
-    Statement   := Arithmetic ;
-    Arithmetic  := Node Op Node | Node
-    Node        := (Arithmetic) | Arithmetic | Number | -Number
+    Program     := (Struct | Enum | Function | Statement)*
+    Struct      := struct Identifier { [Field {, Field} [,]] }
+    Field       := Identifier : Type
+    Enum        := enum Identifier { [Variant {, Variant} [,]] }
+    Variant     := Identifier [( [Type {, Type}] )]
+    Function    := fn Identifier ( [Parameter {, Parameter}] ) [-> Type] Block
+    Parameter   := Identifier : Type
+    Type        := Identifier | [ Type ; Number ] | ( ) | ( Type , [Type {, Type} [,]] )
+    Block       := { Statement* [Arithmetic] }
+    Statement   := (Let | Assign | Return | break | continue | Arithmetic) ; | If [;] | Match [;] | While [;] | For [;]
+    Return      := return [Arithmetic]
+    Let         := let [mut] Identifier [: Type] = Arithmetic | let TuplePattern [: Type] = Arithmetic
+    TuplePattern := ( LetPattern {, LetPattern} [,] )
+    LetPattern  := _ | Identifier | TuplePattern
+    Assign      := Place = Arithmetic
+    Place       := Identifier {. (Identifier | Number) | [ Arithmetic ]}
+    While       := while Arithmetic Block
+    For         := for Identifier in Arithmetic [(.. | ..=) Arithmetic] Block
+    Arithmetic  := Node {Op Node}
+    Op          := see BINARY_OPERATORS
+    Node        := Primary {. (Identifier | Number) | [ Arithmetic ]}
+    Primary     := (Arithmetic) | Number | true | false | String | Char | Identifier | Call | Unary | If | Match | StructExpr | ArrayExpr | TupleExpr | VariantExpr
+    Unary       := (- | !) Node
+    If          := if Arithmetic Block [else (Block | If)]
+    StructExpr  := Identifier { [Identifier : Arithmetic {, Identifier : Arithmetic} [,]] }
+    ArrayExpr   := [ [Arithmetic {, Arithmetic} [,]] ] | [ Arithmetic ; Number ]
+    TupleExpr   := ( Arithmetic , [Arithmetic {, Arithmetic} [,]] )
+    VariantExpr := Identifier :: Identifier [( [Arithmetic {, Arithmetic}] )]
+    Match       := match Arithmetic { [Arm {, Arm} [,]] }
+    Arm         := Pattern [if Arithmetic] => (Block | Arithmetic)
+    Pattern     := _ | Identifier | [-] Number | true | false | Char | Identifier :: Identifier [( [Pattern {, Pattern}] )] | ( Pattern {, Pattern} [,] )
+    Call        := Identifier ( [Arithmetic {, Arithmetic}] )
+    Number      := [0x | 0o | 0b] Digit {Digit | _} [i8 | i16 | i32 | i64 | u8 | u16 | u32 | u64]
+
+    The conditions of If and While, the value of Match and the range of For have no
+    StructExpr outside of parentheses, as in `if x == y { ... }` the `{` starts the block.
+    The `,` after an Arm whose body is a Block may be left out.
+    A Number without a suffix takes the integer type it is used as, or else `int`.
+    One thing in parentheses is only parenthesized, a tuple of one is written with a `,`
+    after it, like `(1,)`. Fields of tuples are numbered, like `t.0`.
 */
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Parser {
+    pub fn new(mut tokens: Vec<Token>) -> Parser {
+        // comments only matter to the formatter, which takes them from the tokens
+        tokens.retain(|token| token.get_t_type() != TokenType::Comment);
         let len = tokens.len();
         Parser {
             index: 0usize,
-            tokens: tokens,
-            len: len,
-            state: ParseState::Normal,
+            tokens,
+            len,
             err_handler: Vec::new(),
             open_paren_count: 0u32,
+            struct_literals: true,
         }
     }
 
-    pub fn parse(&mut self) -> Result<Program, Vec<String>> {
+    pub fn parse(&mut self) -> Result<Program, Vec<Diagnostic>> {
         let mut program = Program::new();
     
         loop {
@@ -56,167 +120,1167 @@ impl Parser {
                 continue;
             }
 
+            let start = self.index;
+            if token.get_t_type() == TokenType::Struct {
+                match self.get_struct() {
+                    Ok(decl) => program.push_struct(decl),
+                    Err(err) => {
+                        self.err_handler.push(err);
+                        self.skip_to_next_statement(start);
+                    }
+                };
+                continue;
+            }
+            if token.get_t_type() == TokenType::Enum {
+                match self.get_enum() {
+                    Ok(decl) => program.push_enum(decl),
+                    Err(err) => {
+                        self.err_handler.push(err);
+                        self.skip_to_next_statement(start);
+                    }
+                };
+                continue;
+            }
+            if token.get_t_type() == TokenType::Fn {
+                match self.get_function() {
+                    Ok(function) => program.push_function(function),
+                    Err(err) => {
+                        self.err_handler.push(err);
+                        self.skip_to_next_statement(start);
+                    }
+                };
+                continue;
+            }
+
             match self.get_statement() {
                 Ok(statement) => program.push(statement),
                 Err(err) => {
                     self.err_handler.push(err);
-                    self.skip_to_next_statement();
+                    self.skip_to_next_statement(start);
                 },
             };
         }
 
-        if self.err_handler.len() == 0 {
+        if self.err_handler.is_empty() {
             Ok(program)
         } else {
             Err(self.err_handler.clone())
         }
     }
 
-    // Statement   := Arithmetic ;
-    fn get_statement(&mut self) -> Result<Statement, String> {
-        let statement: Statement;
+    // Struct      := struct Identifier { [Field {, Field} [,]] }
+    fn get_struct(&mut self) -> Result<StructDecl, Diagnostic> {
+        // skip struct
+        let token = self.next();
+        if token.get_t_type() != TokenType::Identifier {
+            return Err(ParseErrorHandler::expected(token, "struct name"));
+        }
+        let name = token.get_inner();
+        let span = token.get_span();
+
+        let open = self.next();
+        if open.get_t_type() != TokenType::LeftBrace {
+            return Err(ParseErrorHandler::expected(open, "`{`"));
+        }
+        let mut token = self.next();
+
+        let mut fields = Vec::new();
+        while token.get_t_type() != TokenType::RightBrace {
+            if token.get_t_type() == TokenType::EOF {
+                return Err(ParseErrorHandler::unclosed(token, open, "`}`"));
+            }
+            fields.push(self.get_field()?);
+
+            token = self.now();
+            match token.get_t_type() {
+                TokenType::Comma => {
+                    token = self.next();
+                }
+                TokenType::RightBrace => {}
+                _ => {
+                    return Err(ParseErrorHandler::expected(token, "`,` or `}`"));
+                }
+            }
+        }
+        self.next();
+
+        Ok(StructDecl::new(name, fields, span))
+    }
+
+    // Field       := Identifier : Type
+    fn get_field(&mut self) -> Result<FieldDecl, Diagnostic> {
+        let token = self.now();
+        if token.get_t_type() != TokenType::Identifier {
+            return Err(ParseErrorHandler::expected(token, "field name"));
+        }
+        let name = token.get_inner();
+        let span = token.get_span();
+
+        let token = self.next();
+        if token.get_t_type() != TokenType::Colon {
+            return Err(ParseErrorHandler::expected(token, "`:`")
+                .with_help("fields are written as `name: type`".to_owned()));
+        }
+        self.next();
+
+        let type_name = self.get_type()?;
+        let span = span.to(type_name.get_span());
+
+        Ok(FieldDecl::new(name, type_name, span))
+    }
+
+    // Enum        := enum Identifier { [Variant {, Variant} [,]] }
+    fn get_enum(&mut self) -> Result<EnumDecl, Diagnostic> {
+        // skip enum
+        let token = self.next();
+        if token.get_t_type() != TokenType::Identifier {
+            return Err(ParseErrorHandler::expected(token, "enum name"));
+        }
+        let name = token.get_inner();
+        let span = token.get_span();
+
+        let open = self.next();
+        if open.get_t_type() != TokenType::LeftBrace {
+            return Err(ParseErrorHandler::expected(open, "`{`"));
+        }
+        let mut token = self.next();
+
+        let mut variants = Vec::new();
+        while token.get_t_type() != TokenType::RightBrace {
+            if token.get_t_type() == TokenType::EOF {
+                return Err(ParseErrorHandler::unclosed(token, open, "`}`"));
+            }
+            variants.push(self.get_variant()?);
+
+            token = self.now();
+            match token.get_t_type() {
+                TokenType::Comma => {
+                    token = self.next();
+                }
+                TokenType::RightBrace => {}
+                _ => {
+                    return Err(ParseErrorHandler::expected(token, "`,` or `}`"));
+                }
+            }
+        }
+        self.next();
+
+        Ok(EnumDecl::new(name, variants, span))
+    }
+
+    // Variant     := Identifier [( [Type {, Type}] )]
+    fn get_variant(&mut self) -> Result<VariantDecl, Diagnostic> {
+        let token = self.now();
+        if token.get_t_type() != TokenType::Identifier {
+            return Err(ParseErrorHandler::expected(token, "variant name"));
+        }
+        let name = token.get_inner();
+        let mut span = token.get_span();
 
-        let arithmetic = match self.get_arithmetic() {
-            Ok(arithmetic) => arithmetic,
-            Err(err) => return Err(err),
+        let open = self.next();
+        let mut fields = Vec::new();
+        if open.get_t_type() == TokenType::LeftParenthesis {
+            let mut token = self.next();
+            while token.get_t_type() != TokenType::RightParenthesis {
+                fields.push(self.get_type()?);
+
+                token = self.now();
+                match token.get_t_type() {
+                    TokenType::Comma => {
+                        token = self.next();
+                    }
+                    TokenType::RightParenthesis => {}
+                    _ => {
+                        return Err(ParseErrorHandler::expected(token, "`,` or `)`"));
+                    }
+                }
+            }
+            span = span.to(token.get_span());
+            self.next();
+        }
+
+        Ok(VariantDecl::new(name, fields, span))
+    }
+
+    // Function    := fn Identifier ( [Parameter {, Parameter}] ) [-> Type] Block
+    fn get_function(&mut self) -> Result<FunctionDecl, Diagnostic> {
+        // skip fn
+        let token = self.next();
+        if token.get_t_type() != TokenType::Identifier {
+            return Err(ParseErrorHandler::expected(token, "function name"));
+        }
+        let name = token.get_inner();
+        let span = token.get_span();
+
+        let mut token = self.next();
+        if token.get_t_type() != TokenType::LeftParenthesis {
+            return Err(ParseErrorHandler::expected(token, "`(`"));
+        }
+        token = self.next();
+
+        let mut params = Vec::new();
+        while token.get_t_type() != TokenType::RightParenthesis {
+            params.push(self.get_parameter()?);
+
+            token = self.now();
+            match token.get_t_type() {
+                TokenType::Comma => {
+                    token = self.next();
+                }
+                TokenType::RightParenthesis => {}
+                _ => {
+                    return Err(ParseErrorHandler::expected(token, "`,` or `)`"));
+                }
+            }
+        }
+        token = self.next();
+
+        let mut return_type = None;
+        if token.get_t_type() == TokenType::Arrow {
+            self.next();
+            return_type = Some(self.get_type()?);
+        }
+
+        let body = self.get_block()?;
+
+        Ok(FunctionDecl::new(name, params, return_type, body, span))
+    }
+
+    // Parameter   := Identifier : Type
+    fn get_parameter(&mut self) -> Result<Parameter, Diagnostic> {
+        let token = self.now();
+        if token.get_t_type() != TokenType::Identifier {
+            return Err(ParseErrorHandler::expected(token, "parameter name"));
+        }
+        let name = token.get_inner();
+        let span = token.get_span();
+
+        let token = self.next();
+        if token.get_t_type() != TokenType::Colon {
+            return Err(ParseErrorHandler::expected(token, "`:`")
+                .with_help("parameters are written as `name: type`".to_owned()));
+        }
+        self.next();
+
+        let type_name = self.get_type()?;
+        let span = span.to(type_name.get_span());
+
+        Ok(Parameter::new(name, type_name, span))
+    }
+
+    // Type        := Identifier | [ Type ; Number ]
+    fn get_type(&mut self) -> Result<TypeName, Diagnostic> {
+        let token = self.now();
+        match token.get_t_type() {
+            TokenType::Identifier => {
+                self.next();
+                Ok(TypeName::Named(token.get_inner(), token.get_span()))
+            }
+            TokenType::LeftBracket => {
+                self.next();
+                let element = self.get_type()?;
+
+                let semicolon = self.now();
+                if semicolon.get_t_type() != TokenType::Semicolon {
+                    return Err(ParseErrorHandler::expected(semicolon, "`;`")
+                        .with_help("array types are written as `[type; length]`".to_owned()));
+                }
+                let len = array_len(&self.next())?;
+
+                let close = self.next();
+                if close.get_t_type() != TokenType::RightBracket {
+                    return Err(ParseErrorHandler::unclosed(close, token, "`]`"));
+                }
+                self.next();
+
+                Ok(TypeName::Array(Box::new(element), len, token.get_span().to(close.get_span())))
+            }
+            TokenType::LeftParenthesis => {
+                // `()` is the unit type, the tuple of no elements
+                let close = self.next();
+                if close.get_t_type() == TokenType::RightParenthesis {
+                    self.next();
+                    return Ok(TypeName::Tuple(Vec::new(), token.get_span().to(close.get_span())));
+                }
+                let mut elements = vec![self.get_type()?];
+                let mut tuple = false;
+                while self.now().get_t_type() == TokenType::Comma {
+                    tuple = true;
+                    if self.next().get_t_type() == TokenType::RightParenthesis {
+                        break;
+                    }
+                    elements.push(self.get_type()?);
+                }
+
+                let close = self.now();
+                if close.get_t_type() != TokenType::RightParenthesis {
+                    return Err(ParseErrorHandler::unclosed(close, token, "`)`"));
+                }
+                self.next();
+
+                if tuple {
+                    Ok(TypeName::Tuple(elements, token.get_span().to(close.get_span())))
+                } else {
+                    // only parenthesized
+                    Ok(elements.remove(0))
+                }
+            }
+            _ => Err(ParseErrorHandler::expected(token, "type")),
+        }
+    }
+
+    // Block       := { Statement* [Arithmetic] }
+    fn get_block(&mut self) -> Result<Block, Diagnostic> {
+        let open = self.now();
+        if open.get_t_type() != TokenType::LeftBrace {
+            return Err(ParseErrorHandler::expected(open, "`{`"));
+        }
+        self.next();
+
+        let mut block = Block::new();
+        loop {
+            let token = self.now();
+            match token.get_t_type() {
+                TokenType::RightBrace => {
+                    self.next();
+                    break;
+                }
+                TokenType::EOF => {
+                    return Err(ParseErrorHandler::unclosed(token, open, "`}`"));
+                }
+                _ => {}
+            }
+
+            let start = self.index;
+            match self.get_statement() {
+                // an expression without `;` right before the `}`
+                Ok(Statement::Arithmetic(value)) if self.now().get_t_type() == TokenType::RightBrace
+                    && self.previous().get_t_type() != TokenType::Semicolon => {
+                    block.set_value(value);
+                }
+                Ok(statement) => block.push(statement),
+                Err(err) => {
+                    self.err_handler.push(err);
+                    self.skip_to_next_statement(start);
+                }
+            }
+        }
+
+        Ok(block)
+    }
+
+    // Statement   := (Let | Assign | Return | break | continue | Arithmetic) ; | If [;] | While [;] | For [;]
+    // An expression may go without `;` at the end of a block, where it is the value.
+    fn get_statement(&mut self) -> Result<Statement, Diagnostic> {
+        let token = self.now();
+        let statement = match token.get_t_type() {
+            TokenType::Let if self.peek().get_t_type() == TokenType::LeftParenthesis => Statement::Destructure(self.get_destructure()?),
+            TokenType::Let => Statement::Let(self.get_let()?),
+            TokenType::Return => Statement::Return(self.get_return()?),
+            TokenType::Break => {
+                self.next();
+                Statement::Break(token.get_span())
+            }
+            TokenType::Continue => {
+                self.next();
+                Statement::Continue(token.get_span())
+            }
+            // statements ending with a block need no `;`
+            TokenType::While | TokenType::For | TokenType::If | TokenType::Match => {
+                let statement = match token.get_t_type() {
+                    TokenType::While => Statement::While(self.get_while()?),
+                    TokenType::For => Statement::For(self.get_for()?),
+                    TokenType::Match => Statement::Arithmetic(Arithmetic::Term(Node::Match(Box::new(self.get_match()?)))),
+                    _ => Statement::Arithmetic(Arithmetic::Term(Node::If(Box::new(self.get_if()?)))),
+                };
+                if self.now().get_t_type() == TokenType::Semicolon {
+                    self.next();
+                }
+                return Ok(statement);
+            }
+            _ => {
+                let arithmetic = self.get_arithmetic()?;
+                if self.now().get_t_type() == TokenType::Equal {
+                    Statement::Assign(self.get_assign(arithmetic)?)
+                } else {
+                    Statement::Arithmetic(arithmetic)
+                }
+            }
         };
 
         let token = self.now();
+        if token.get_t_type() == TokenType::RightBrace && matches!(statement, Statement::Arithmetic(_)) {
+            return Ok(statement);
+        }
         if token.get_t_type() != TokenType::Semicolon {
-            return Err(ParseErrorHandler::create_error(token, "Statement should have Semicolon."));
+            // point right after the statement, where the semicolon belongs
+            let error = Diagnostic::error("E0101", format!("expected `;`, found {}", describe(&token)))
+                .with_label(self.previous().get_span().end_point(), "expected `;`".to_owned())
+                .with_secondary(token.get_span(), "unexpected token".to_owned());
+            return Err(error);
         }
         self.next();
 
-        statement = Statement::Arithmetic(arithmetic);
-        
         Ok(statement)
     }
 
-    // Arithmetic  := Node Op Node | Node
-    fn get_arithmetic(&mut self) -> Result<Arithmetic, String> {
-        let mut arithmetic: Arithmetic;
+    // Let         := let [mut] Identifier [: Type] = Arithmetic
+    fn get_let(&mut self) -> Result<Let, Diagnostic> {
+        let span = self.now().get_span();
+
+        // skip let
+        let mut token = self.next();
+
+        let mutable = token.get_t_type() == TokenType::Mut;
+        if mutable {
+            token = self.next();
+        }
+
+        if mutable && token.get_t_type() == TokenType::LeftParenthesis {
+            return Err(ParseErrorHandler::expected(token, "variable name")
+                .with_help("the variables of a tuple pattern cannot be `mut`, declare them again with `let mut`".to_owned()));
+        }
+        if token.get_t_type() != TokenType::Identifier {
+            return Err(ParseErrorHandler::expected(token, "variable name"));
+        }
+        let name = token.get_inner();
+        token = self.next();
+
+        let mut type_name = None;
+        if token.get_t_type() == TokenType::Colon {
+            self.next();
+            type_name = Some(self.get_type()?);
+            token = self.now();
+        }
+
+        if token.get_t_type() != TokenType::Equal {
+            return Err(ParseErrorHandler::expected(token, "`=`")
+                .with_note("variables are initialized where they are declared".to_owned()));
+        }
+        self.next();
+
+        let value = self.get_arithmetic()?;
+
+        let span = span.to(value.get_span());
+
+        Ok(Let::new(name, mutable, type_name, value, span))
+    }
+
+    // Let         := let TuplePattern [: Type] = Arithmetic
+    fn get_destructure(&mut self) -> Result<Destructure, Diagnostic> {
+        let span = self.now().get_span();
+
+        // skip let
+        self.next();
+        let pattern = self.get_let_pattern()?;
+
+        let mut type_name = None;
+        let mut token = self.now();
+        if token.get_t_type() == TokenType::Colon {
+            self.next();
+            type_name = Some(self.get_type()?);
+            token = self.now();
+        }
+
+        if token.get_t_type() != TokenType::Equal {
+            return Err(ParseErrorHandler::expected(token, "`=`")
+                .with_note("variables are initialized where they are declared".to_owned()));
+        }
+        self.next();
 
-        let l_node = match self.get_node() {
-            Ok(node) => node,
-            Err(err) => return Err(err),
+        let value = self.get_arithmetic()?;
+
+        let span = span.to(value.get_span());
+
+        Ok(Destructure::new(pattern, type_name, value, span))
+    }
+
+    // LetPattern  := _ | Identifier | TuplePattern
+    // Only patterns that match any value, which a `let` needs.
+    fn get_let_pattern(&mut self) -> Result<Pattern, Diagnostic> {
+        let token = self.now();
+        let pattern = match token.get_t_type() {
+            TokenType::LeftParenthesis => {
+                return self.get_tuple_pattern(Parser::get_let_pattern);
+            }
+            TokenType::Identifier if token.get_inner() == "_" => Pattern::Wildcard(token.get_span()),
+            TokenType::Identifier => Pattern::Binding(token.get_inner(), token.get_span()),
+            _ => {
+                return Err(ParseErrorHandler::expected(token, "variable name"));
+            }
         };
+        self.next();
 
-        arithmetic = Arithmetic::Term(l_node);
-
-        let t_type = self.now().get_t_type();
-        if  t_type == TokenType::Plus || t_type == TokenType::Minus ||
-            t_type == TokenType::Asterisk || t_type == TokenType::Slash
-        {
-            loop {
-                let token = self.now();
-                let op = match token.get_t_type() {
-                    TokenType::Plus => Operator::Plus,
-                    TokenType::Minus => Operator::Minus,
-                    TokenType::Asterisk => Operator::Mul,
-                    TokenType::Slash => Operator::Div,
-                    _ => break,
-                };
+        Ok(pattern)
+    }
+
+    // Return      := return [Arithmetic]
+    fn get_return(&mut self) -> Result<Return, Diagnostic> {
+        let span = self.now().get_span();
+
+        // skip return
+        let token = self.next();
+        if token.get_t_type() == TokenType::Semicolon {
+            return Ok(Return::new(None, span));
+        }
+
+        let value = self.get_arithmetic()?;
+        let span = span.to(value.get_span());
+
+        Ok(Return::new(Some(value), span))
+    }
+
+    // While       := while Arithmetic Block
+    fn get_while(&mut self) -> Result<While, Diagnostic> {
+        let span = self.now().get_span();
+
+        // skip while
+        self.next();
 
-                // skip Operator
+        let condition = self.with_struct_literals(false, Parser::get_arithmetic)?;
+        let span = span.to(condition.get_span());
+        let body = self.get_block()?;
+
+        Ok(While::new(condition, body, span))
+    }
+
+    // For         := for Identifier in Arithmetic [(.. | ..=) Arithmetic] Block
+    fn get_for(&mut self) -> Result<For, Diagnostic> {
+        let span = self.now().get_span();
+
+        // skip for
+        let token = self.next();
+        if token.get_t_type() != TokenType::Identifier {
+            return Err(ParseErrorHandler::expected(token, "loop variable"));
+        }
+        let variable = token.get_inner();
+
+        let token = self.next();
+        if token.get_t_type() != TokenType::In {
+            return Err(ParseErrorHandler::expected(token, "`in`"));
+        }
+        self.next();
+
+        let start = self.with_struct_literals(false, Parser::get_arithmetic)?;
+        let iterable = match self.now().get_t_type() {
+            TokenType::DotDot | TokenType::DotDotEqual => {
+                let inclusive = self.now().get_t_type() == TokenType::DotDotEqual;
                 self.next();
+                let end = self.with_struct_literals(false, Parser::get_arithmetic)?;
+                Iterable::Range(Range::new(start, end, inclusive))
+            }
+            _ => Iterable::Expression(start),
+        };
+        let span = span.to(iterable.get_span());
+        let body = self.get_block()?;
 
-                let r_node = match self.get_node() {
-                    Ok(node) => node,
-                    Err(err) => return Err(err),
-                };
+        Ok(For::new(variable, iterable, body, span))
+    }
+
+    // Assign      := Place = Arithmetic
+    // target is the expression already parsed before the `=`.
+    fn get_assign(&mut self, target: Arithmetic) -> Result<Assign, Diagnostic> {
+        let target = target.into_node();
+        if !target.is_place() {
+            let error = Diagnostic::error("E0104", "invalid left-hand side of assignment".to_owned())
+                .with_label(target.get_span(), "cannot assign to this expression".to_owned())
+                .with_note("only variables and their fields and elements can be assigned to".to_owned());
+            return Err(error);
+        }
+
+        // skip Equal
+        self.next();
+
+        let value = self.get_arithmetic()?;
+
+        let span = target.get_span().to(value.get_span());
+
+        Ok(Assign::new(target, value, span))
+    }
+
+    // Arithmetic  := Node {Op Node}
+    fn get_arithmetic(&mut self) -> Result<Arithmetic, Diagnostic> {
+        self.get_binary(0)
+    }
+
+    // Precedence climbing: parses operators binding at least as tight as min_precedence.
+    fn get_binary(&mut self, min_precedence: u8) -> Result<Arithmetic, Diagnostic> {
+        let mut arithmetic = Arithmetic::Term(self.get_node()?);
+
+        loop {
+            let binary = match binary_operator(self.now().get_t_type()) {
+                Some(binary) if binary.precedence >= min_precedence => binary,
+                _ => break,
+            };
+
+            // skip Operator
+            self.next();
 
-                arithmetic = match op {
-                    Operator::Plus | Operator::Minus => {
-                        arithmetic.insert_top(op, r_node)
+            // only tighter operators go right, so equal ones group to the left
+            let right = self.get_binary(binary.precedence + 1)?;
+
+            arithmetic = Arithmetic::MultiTerm(arithmetic.into_node(), binary.op, right.into_node());
+        }
+
+        Ok(arithmetic)
+    }
+
+    // Node        := Primary {. Identifier | [ Arithmetic ]}
+    fn get_node(&mut self) -> Result<Node, Diagnostic> {
+        let mut node = self.get_primary()?;
+
+        loop {
+            match self.now().get_t_type() {
+                TokenType::Dot => {
+                    // tuples have numbered fields
+                    let token = self.next();
+                    if token.get_t_type() != TokenType::Identifier && token.get_t_type() != TokenType::Number {
+                        return Err(ParseErrorHandler::expected(token, "field name"));
                     }
-                    Operator::Mul | Operator::Div => {
-                        arithmetic.insert_right(op, r_node)
+                    self.next();
+
+                    let span = node.get_span().to(token.get_span());
+                    node = Node::Field(FieldAccess::new(node, token.get_inner(), span));
+                }
+                TokenType::LeftBracket => {
+                    let open = self.now();
+                    self.next();
+                    let index = self.with_struct_literals(true, Parser::get_arithmetic)?;
+
+                    let close = self.now();
+                    if close.get_t_type() != TokenType::RightBracket {
+                        return Err(ParseErrorHandler::unclosed(close, open, "`]`"));
                     }
-                };
+                    self.next();
 
+                    let span = node.get_span().to(close.get_span());
+                    node = Node::Index(Index::new(node, index, span));
+                }
+                _ => break,
             }
-        };
-        
-        Ok(arithmetic)
+        }
+
+        Ok(node)
     }
 
-    fn get_node(&mut self) -> Result<Node, String> {
+    fn get_primary(&mut self) -> Result<Node, Diagnostic> {
         let node: Node;
         let token = self.now();
 
         match token.get_t_type() {
             TokenType::Number  => {
-                let number = Number::new(token.get_inner());
+                let number = Number::new(token.get_inner(), token.get_span());
                 node = Node::Number(number);
 
                 self.next();
             }
+            TokenType::True | TokenType::False => {
+                node = Node::Boolean(Boolean::new(token.get_t_type() == TokenType::True, token.get_span()));
+
+                self.next();
+            }
+            TokenType::Str => {
+                node = Node::Str(Str::new(token.get_inner(), token.get_span()));
+
+                self.next();
+            }
+            TokenType::Char => {
+                node = Node::Char(Char::new(char_value(&token), token.get_span()));
+
+                self.next();
+            }
             TokenType::LeftParenthesis => {
                 self.next();
                 self.inc_open_paren_count();
-                let arithmetic = match self.get_arithmetic() {
-                    Ok(arithmetic) => arithmetic,
-                    Err(err) => return Err(err),
-                };
-                node = Node::Arithmetic(Box::new(arithmetic));
+                let mut elements = vec![self.with_struct_literals(true, Parser::get_arithmetic)?];
+                let mut tuple = false;
+                while self.now().get_t_type() == TokenType::Comma {
+                    tuple = true;
+                    if self.next().get_t_type() == TokenType::RightParenthesis {
+                        break;
+                    }
+                    elements.push(self.with_struct_literals(true, Parser::get_arithmetic)?);
+                }
 
-                let token = self.now();
+                let close = self.now();
                 
-                if token.get_t_type() == TokenType::RightParenthesis {
-                    match self.dec_open_paren_count() {
-                        Ok(_) => {}
-                        Err(err) => {
-                            return Err(err);
-                        }
-                    };
+                if close.get_t_type() == TokenType::RightParenthesis {
+                    self.dec_open_paren_count()?;
                     self.next();
                 } else {
-                    return Err(ParseErrorHandler::create_error(token, "There isn't Close Parenthesis."));
+                    return Err(ParseErrorHandler::unclosed(close, token, "`)`"));
                 }
+
+                node = if tuple {
+                    Node::Tuple(TupleLiteral::new(elements, token.get_span().to(close.get_span())))
+                } else {
+                    Node::Arithmetic(Box::new(elements.remove(0)))
+                };
             }
-            // On the way
-            TokenType::Minus => {
-                // Skip Minus
-                self.next();
-                let token = self.next();
+            TokenType::Identifier if self.peek().get_t_type() == TokenType::LeftParenthesis => {
+                node = Node::Call(self.get_call()?);
+            }
+            TokenType::Identifier if self.peek().get_t_type() == TokenType::ColonColon => {
+                node = Node::Variant(Box::new(self.get_variant_expr()?));
+            }
+            TokenType::Identifier if self.struct_literals && self.peek().get_t_type() == TokenType::LeftBrace => {
+                node = Node::StructLiteral(self.get_struct_expr()?);
+            }
+            TokenType::Identifier => {
+                node = Node::Variable(Variable::new(token.get_inner(), token.get_span()));
 
-                return Err(format!(""));
+                self.next();
+            }
+            TokenType::Minus => {
+                node = Node::Unary(self.get_unary(UnaryOperator::Negate)?);
+            }
+            TokenType::Bang => {
+                node = Node::Unary(self.get_unary(UnaryOperator::Not)?);
+            }
+            TokenType::If => {
+                node = Node::If(Box::new(self.get_if()?));
+            }
+            TokenType::Match => {
+                node = Node::Match(Box::new(self.get_match()?));
+            }
+            TokenType::LeftBracket => {
+                node = self.get_array_expr()?;
             }
             _ => {
-                return Err(ParseErrorHandler::create_error(token, "is not first token of Node."));
+                return Err(ParseErrorHandler::expected(token, "expression"));
             }
         }
 
         Ok(node)
     }
 
-    fn skip_to_next_statement(&mut self) {
-        let mut token = self.now();
-        let mut t_type = token.get_t_type();
+    // Unary       := (- | !) Node
+    // The operand is a single node, so `-a * b` is `(-a) * b`.
+    fn get_unary(&mut self, op: UnaryOperator) -> Result<Unary, Diagnostic> {
+        let token = self.now();
+
+        // skip Operator
+        self.next();
+
+        let operand = self.get_node()?;
+        let span = token.get_span().to(operand.get_span());
+
+        Ok(Unary::new(op, operand, span))
+    }
+
+    // If          := if Arithmetic Block [else (Block | If)]
+    fn get_if(&mut self) -> Result<If, Diagnostic> {
+        let span = self.now().get_span();
+
+        // skip if
+        self.next();
+
+        let condition = self.with_struct_literals(false, Parser::get_arithmetic)?;
+        let span = span.to(condition.get_span());
+        let then = self.get_block()?;
+
+        let mut otherwise = None;
+        if self.now().get_t_type() == TokenType::Else {
+            let token = self.next();
+            if token.get_t_type() == TokenType::If {
+                let mut block = Block::new();
+                block.set_value(Arithmetic::Term(Node::If(Box::new(self.get_if()?))));
+                otherwise = Some(block);
+            } else {
+                otherwise = Some(self.get_block()?);
+            }
+        }
+
+        Ok(If::new(condition, then, otherwise, span))
+    }
+
+    // Match       := match Arithmetic { [Arm {, Arm} [,]] }
+    fn get_match(&mut self) -> Result<Match, Diagnostic> {
+        let span = self.now().get_span();
+
+        // skip match
+        self.next();
+
+        let value = self.with_struct_literals(false, Parser::get_arithmetic)?;
+        let span = span.to(value.get_span());
+
+        let open = self.now();
+        if open.get_t_type() != TokenType::LeftBrace {
+            return Err(ParseErrorHandler::expected(open, "`{`"));
+        }
+        let mut token = self.next();
+
+        let mut arms = Vec::new();
+        while token.get_t_type() != TokenType::RightBrace {
+            if token.get_t_type() == TokenType::EOF {
+                return Err(ParseErrorHandler::unclosed(token, open, "`}`"));
+            }
+            let (arm, braced) = self.with_struct_literals(true, Parser::get_arm)?;
+            arms.push(arm);
+
+            token = self.now();
+            match token.get_t_type() {
+                TokenType::Comma => {
+                    token = self.next();
+                }
+                TokenType::RightBrace => {}
+                _ if braced => {}
+                _ => {
+                    return Err(ParseErrorHandler::expected(token, "`,` or `}`"));
+                }
+            }
+        }
+        self.next();
+
+        Ok(Match::new(value, arms, span))
+    }
+
+    // Arm         := Pattern [if Arithmetic] => (Block | Arithmetic)
+    // Also tells whether the body is a block, which needs no `,` after it.
+    fn get_arm(&mut self) -> Result<(MatchArm, bool), Diagnostic> {
+        let pattern = self.get_pattern()?;
+
+        let mut guard = None;
+        if self.now().get_t_type() == TokenType::If {
+            self.next();
+            guard = Some(self.get_arithmetic()?);
+        }
+
+        let arrow = self.now();
+        if arrow.get_t_type() != TokenType::FatArrow {
+            return Err(ParseErrorHandler::expected(arrow, "`=>`"));
+        }
+        self.next();
+
+        let braced = self.now().get_t_type() == TokenType::LeftBrace;
+        let body = if braced {
+            self.get_block()?
+        } else {
+            let mut body = Block::new();
+            body.set_value(self.get_arithmetic()?);
+            body
+        };
+        let span = pattern.get_span().to(self.previous().get_span());
+
+        Ok((MatchArm::new(pattern, guard, body, span), braced))
+    }
+
+    // Pattern     := _ | Identifier | [-] Number | true | false | Char | Identifier :: Identifier [( [Pattern {, Pattern}] )]
+    fn get_pattern(&mut self) -> Result<Pattern, Diagnostic> {
+        let token = self.now();
+        let pattern = match token.get_t_type() {
+            TokenType::Identifier if self.peek().get_t_type() == TokenType::ColonColon => {
+                return Ok(Pattern::Variant(self.get_variant_pattern()?));
+            }
+            TokenType::Identifier if token.get_inner() == "_" => Pattern::Wildcard(token.get_span()),
+            TokenType::Identifier => Pattern::Binding(token.get_inner(), token.get_span()),
+            TokenType::Number => Pattern::Number(Number::new(token.get_inner(), token.get_span())),
+            TokenType::Minus => {
+                let number = self.next();
+                if number.get_t_type() != TokenType::Number {
+                    return Err(ParseErrorHandler::expected(number, "number"));
+                }
+                let inner = format!("-{}", number.get_inner());
+                Pattern::Number(Number::new(inner, token.get_span().to(number.get_span())))
+            }
+            TokenType::True | TokenType::False => {
+                Pattern::Boolean(Boolean::new(token.get_t_type() == TokenType::True, token.get_span()))
+            }
+            TokenType::Char => Pattern::Char(Char::new(char_value(&token), token.get_span())),
+            TokenType::LeftParenthesis => {
+                return self.get_tuple_pattern(Parser::get_pattern);
+            }
+            _ => {
+                return Err(ParseErrorHandler::expected(token, "pattern"));
+            }
+        };
+        self.next();
+
+        Ok(pattern)
+    }
+
+    // ( Pattern {, Pattern} [,] ), with each element parsed by element.
+    // One pattern without a `,` is only parenthesized.
+    fn get_tuple_pattern(&mut self, element: fn(&mut Parser) -> Result<Pattern, Diagnostic>) -> Result<Pattern, Diagnostic> {
+        let open = self.now();
+        let mut fields = Vec::new();
+        let mut tuple = false;
+        self.next();
+        let close = loop {
+            fields.push(element(self)?);
+
+            let token = self.now();
+            match token.get_t_type() {
+                TokenType::Comma => {
+                    tuple = true;
+                    let token = self.next();
+                    if token.get_t_type() == TokenType::RightParenthesis {
+                        break token;
+                    }
+                }
+                TokenType::RightParenthesis => break token,
+                _ => {
+                    return Err(ParseErrorHandler::expected(token, "`,` or `)`"));
+                }
+            }
+        };
+        self.next();
+
+        if tuple {
+            Ok(Pattern::Tuple(TuplePattern::new(fields, open.get_span().to(close.get_span()))))
+        } else {
+            Ok(fields.remove(0))
+        }
+    }
+
+    // Identifier :: Identifier [( [Pattern {, Pattern}] )]
+    fn get_variant_pattern(&mut self) -> Result<VariantPattern, Diagnostic> {
+        let (enum_name, name, mut span) = self.get_path()?;
+
+        let mut fields = Vec::new();
+        if self.now().get_t_type() == TokenType::LeftParenthesis {
+            let mut token = self.next();
+            while token.get_t_type() != TokenType::RightParenthesis {
+                fields.push(self.get_pattern()?);
+
+                token = self.now();
+                match token.get_t_type() {
+                    TokenType::Comma => {
+                        token = self.next();
+                    }
+                    TokenType::RightParenthesis => {}
+                    _ => {
+                        return Err(ParseErrorHandler::expected(token, "`,` or `)`"));
+                    }
+                }
+            }
+            span = span.to(token.get_span());
+            self.next();
+        }
+
+        Ok(VariantPattern::new(enum_name, name, fields, span))
+    }
+
+    // VariantExpr := Identifier :: Identifier [( [Arithmetic {, Arithmetic}] )]
+    fn get_variant_expr(&mut self) -> Result<VariantLiteral, Diagnostic> {
+        let (enum_name, name, mut span) = self.get_path()?;
+
+        let mut args = Vec::new();
+        if self.now().get_t_type() == TokenType::LeftParenthesis {
+            let mut token = self.next();
+            while token.get_t_type() != TokenType::RightParenthesis {
+                args.push(self.with_struct_literals(true, Parser::get_arithmetic)?);
+
+                token = self.now();
+                match token.get_t_type() {
+                    TokenType::Comma => {
+                        token = self.next();
+                    }
+                    TokenType::RightParenthesis => {}
+                    _ => {
+                        return Err(ParseErrorHandler::expected(token, "`,` or `)`"));
+                    }
+                }
+            }
+            span = span.to(token.get_span());
+            self.next();
+        }
+
+        Ok(VariantLiteral::new(enum_name, name, args, span))
+    }
+
+    // Enum :: Variant, the names and their span
+    fn get_path(&mut self) -> Result<(String, String, Span), Diagnostic> {
+        let enum_token = self.now();
+
+        // skip Identifier and Colon Colon
+        self.next();
+        let token = self.next();
+        if token.get_t_type() != TokenType::Identifier {
+            return Err(ParseErrorHandler::expected(token, "variant name"));
+        }
+        self.next();
+
+        Ok((enum_token.get_inner(), token.get_inner(), enum_token.get_span().to(token.get_span())))
+    }
+
+    // StructExpr  := Identifier { [Identifier : Arithmetic {, Identifier : Arithmetic} [,]] }
+    fn get_struct_expr(&mut self) -> Result<StructLiteral, Diagnostic> {
+        let name_token = self.now();
+
+        // skip Identifier and Left Brace
+        self.next();
+        let mut token = self.next();
+
+        let mut fields = Vec::new();
+        while token.get_t_type() != TokenType::RightBrace {
+            if token.get_t_type() != TokenType::Identifier {
+                return Err(ParseErrorHandler::expected(token, "field name"));
+            }
+
+            let colon = self.next();
+            if colon.get_t_type() != TokenType::Colon {
+                return Err(ParseErrorHandler::expected(colon, "`:`")
+                    .with_help("fields are initialized as `name: value`".to_owned()));
+            }
+            self.next();
+
+            let value = self.get_arithmetic()?;
+            let span = token.get_span().to(value.get_span());
+            fields.push(FieldInit::new(token.get_inner(), value, span));
+
+            token = self.now();
+            match token.get_t_type() {
+                TokenType::Comma => {
+                    token = self.next();
+                }
+                TokenType::RightBrace => {}
+                _ => {
+                    return Err(ParseErrorHandler::expected(token, "`,` or `}`"));
+                }
+            }
+        }
+        self.next();
+
+        let span = name_token.get_span().to(token.get_span());
+
+        Ok(StructLiteral::new(name_token.get_inner(), fields, span))
+    }
+
+    // ArrayExpr   := [ [Arithmetic {, Arithmetic} [,]] ] | [ Arithmetic ; Number ]
+    fn get_array_expr(&mut self) -> Result<Node, Diagnostic> {
+        let open = self.now();
+
+        // skip Left Bracket
+        let mut token = self.next();
+
+        let mut elements = Vec::new();
+        while token.get_t_type() != TokenType::RightBracket {
+            elements.push(self.with_struct_literals(true, Parser::get_arithmetic)?);
+
+            token = self.now();
+            match token.get_t_type() {
+                TokenType::Semicolon if elements.len() == 1 => {
+                    let len = array_len(&self.next())?;
+                    let close = self.next();
+                    if close.get_t_type() != TokenType::RightBracket {
+                        return Err(ParseErrorHandler::unclosed(close, open, "`]`"));
+                    }
+                    self.next();
+
+                    let value = elements.pop().expect("the value was just parsed");
+                    let span = open.get_span().to(close.get_span());
+                    return Ok(Node::Repeat(ArrayRepeat::new(value, len, span)));
+                }
+                TokenType::Comma => {
+                    token = self.next();
+                }
+                TokenType::RightBracket => {}
+                _ => {
+                    return Err(ParseErrorHandler::expected(token, "`,` or `]`"));
+                }
+            }
+        }
+        self.next();
+
+        let span = open.get_span().to(token.get_span());
+
+        Ok(Node::Array(ArrayLiteral::new(elements, span)))
+    }
+
+    // Call        := Identifier ( [Arithmetic {, Arithmetic}] )
+    fn get_call(&mut self) -> Result<Call, Diagnostic> {
+        let name_token = self.now();
+
+        // skip Identifier and Left Parenthesis
+        self.next();
+        let mut token = self.next();
+
+        let mut args = Vec::new();
+        while token.get_t_type() != TokenType::RightParenthesis {
+            args.push(self.with_struct_literals(true, Parser::get_arithmetic)?);
+
+            token = self.now();
+            match token.get_t_type() {
+                TokenType::Comma => {
+                    token = self.next();
+                }
+                TokenType::RightParenthesis => {}
+                _ => {
+                    return Err(ParseErrorHandler::expected(token, "`,` or `)`"));
+                }
+            }
+        }
+        self.next();
+
+        let span = name_token.get_span().to(token.get_span());
+
+        Ok(Call::new(name_token.get_inner(), args, span))
+    }
+
+    // Error recovery resumes at the next line,
+    // unless the failed statement (started at start) already reached it.
+    fn skip_to_next_statement(&mut self, start: usize) {
+        self.open_paren_count = 0;
+        if self.index > start && self.tokens[self.index - 1].get_t_type() == TokenType::NewLine {
+            return;
+        }
+
+        let mut t_type = self.tokens[self.index].get_t_type();
         while t_type != TokenType::NewLine && t_type != TokenType::EOF {
-            token = self.next();
-            t_type = token.get_t_type();
+            self.index += 1;
+            t_type = self.tokens[self.index].get_t_type();
         }
         self.next();
     }
 
+    // Runs parse with struct literals allowed or not, as they were after.
+    fn with_struct_literals<T>(&mut self, allowed: bool, parse: impl FnOnce(&mut Parser) -> Result<T, Diagnostic>) -> Result<T, Diagnostic> {
+        let outer = std::mem::replace(&mut self.struct_literals, allowed);
+        let result = parse(self);
+        self.struct_literals = outer;
+
+        result
+    }
+
     fn now(&self) -> Token {
         self.tokens[self.index].clone()
     }
 
+    // NewLine only matters for error recovery, so moving forward skips it.
     fn next(&mut self) -> Token {
         if self.index + 1 < self.len {
             self.index += 1;
         }
+        while self.tokens[self.index].get_t_type() == TokenType::NewLine && self.index + 1 < self.len {
+            self.index += 1;
+        }
         self.tokens[self.index].clone()
     }
 
-    fn before(&mut self) -> Option<Token> {
-        if self.index > 0 {
-            self.index -= 1;
-            Some(self.tokens[self.index].clone())
+    // the last token before the current one
+    fn previous(&self) -> Token {
+        let mut index = self.index;
+        while index > 0 {
+            index -= 1;
+            if self.tokens[index].get_t_type() != TokenType::NewLine {
+                return self.tokens[index].clone();
+            }
+        }
+
+        self.now()
+    }
+
+    fn peek(&self) -> Token {
+        let mut index = self.index + 1;
+        while index < self.len && self.tokens[index].get_t_type() == TokenType::NewLine {
+            index += 1;
+        }
+
+        if index < self.len {
+            self.tokens[index].clone()
         } else {
-            None
+            self.now()
         }
     }
 
@@ -226,10 +1290,12 @@ impl Parser {
     }
 
     // Decrement Open parenthesis count
-    fn dec_open_paren_count(&mut self) -> Result<(), String> {
+    fn dec_open_paren_count(&mut self) -> Result<(), Diagnostic> {
         if self.open_paren_count == 0 {
             let token = self.now();
-            return Err(ParseErrorHandler::create_error(token, "Parenthethis does not match."));
+            let error = Diagnostic::error("E0103", "unmatched `)`".to_owned())
+                .with_label(token.get_span(), "no `(` to close".to_owned());
+            return Err(error);
         }
         self.open_paren_count -= 1;
 
@@ -238,13 +1304,53 @@ impl Parser {
 }
 
 impl ParseErrorHandler {
-    pub fn new() -> ParseErrorHandler {
-        ParseErrorHandler {
-            err: Vec::new(),
-        }
+    // expected {expected}, found {token}
+    pub fn expected(token: Token, expected: &str) -> Diagnostic {
+        Diagnostic::error("E0101", format!("expected {}, found {}", expected, describe(&token)))
+            .with_label(token.get_span(), format!("expected {}", expected))
+    }
+
+    // reached token before the delimiter opened by open was closed
+    pub fn unclosed(token: Token, open: Token, close: &str) -> Diagnostic {
+        Diagnostic::error("E0102", format!("unclosed delimiter, expected {}", close))
+            .with_label(token.get_span(), format!("expected {}, found {}", close, describe(&token)))
+            .with_secondary(open.get_span(), "unclosed delimiter".to_owned())
+    }
+}
+
+fn binary_operator(t_type: TokenType) -> Option<BinaryOperator> {
+    BINARY_OPERATORS.iter().find(|binary| binary.token == t_type).copied()
+}
+
+// How tightly op binds, higher is tighter.
+pub fn precedence(op: Operator) -> u8 {
+    BINARY_OPERATORS.iter().find(|binary| binary.op == op).map(|binary| binary.precedence)
+        .expect("every operator is in BINARY_OPERATORS")
+}
+
+// The length of an array type or a repeat expression, which is a number.
+fn array_len(token: &Token) -> Result<usize, Diagnostic> {
+    if token.get_t_type() != TokenType::Number {
+        return Err(ParseErrorHandler::expected(token.clone(), "array length"));
     }
 
-    pub fn create_error(token: Token, sentence: &str) -> String {
-        format!("({}, {}) \"{:?} {}\" {}", token.get_line(), token.get_x(), token.get_t_type(), token.get_inner(), sentence)
+    let len = Number::new(token.get_inner(), token.get_span()).get_value();
+    len.and_then(|len| usize::try_from(len).ok()).ok_or_else(|| {
+        Diagnostic::error("E0105", "array length is too large".to_owned())
+            .with_label(token.get_span(), "too large for an array".to_owned())
+    })
+}
+
+// The tokenizer makes sure a char literal has exactly one char.
+fn char_value(token: &Token) -> char {
+    token.get_inner().chars().next().expect("char literals are one char")
+}
+
+fn describe(token: &Token) -> String {
+    match token.get_t_type() {
+        TokenType::EOF => "end of file".to_owned(),
+        TokenType::Str => "string literal".to_owned(),
+        TokenType::Char => "char literal".to_owned(),
+        _ => format!("`{}`", token.get_inner()),
     }
 }
\ No newline at end of file
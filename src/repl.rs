@@ -0,0 +1,336 @@
+use std::io::{ self, BufRead, IsTerminal, Write };
+use super::ast::{
+    Program, StructDecl, EnumDecl, FunctionDecl, Statement, Let, Arithmetic, Node,
+    Number, Boolean, Str, Char, Unary, UnaryOperator, StructLiteral, FieldInit, ArrayLiteral,
+    VariantLiteral, TupleLiteral,
+};
+use super::codegen::{ Backend, X86_64 };
+use super::diagnostic::{ Diagnostic, Renderer };
+use super::interp::{ Interpreter, Value };
+use super::lower::lower;
+use super::parser::Parser;
+use super::semantic::TypeChecker;
+use super::tokenizer::{ Tokenizer, Token, TokenType, Span };
+
+const PROMPT: &str = ">> ";
+// shown while the brackets of an input are still open
+const CONTINUE_PROMPT: &str = ".. ";
+// what diagnostics call the input
+const FILE: &str = "<repl>";
+
+const HELP: &str = "\
+:type <expr>     show the type of an expression
+:ast <code>      show the syntax tree
+:asm <code>      show the x86-64 assembly, with the variables as constants
+:tokens <code>   show the tokens
+:help            show this message";
+
+// An interactive session: each input is checked and run on top of the earlier ones,
+// keeping their variables and functions. An input that fails leaves nothing behind.
+#[derive(Debug, Default)]
+pub struct Repl {
+    checker: TypeChecker,
+    interpreter: Interpreter,
+    // every struct, enum and function defined so far, for :asm
+    structs: Vec<StructDecl>,
+    enums: Vec<EnumDecl>,
+    functions: Vec<FunctionDecl>,
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl {
+            checker: TypeChecker::new(),
+            // what the inputs print goes to out, before their values
+            interpreter: Interpreter::new().with_captured_output(true),
+            structs: Vec::new(),
+            enums: Vec::new(),
+            functions: Vec::new(),
+        }
+    }
+
+    pub fn with_overflow_checks(mut self, overflow_checks: bool) -> Repl {
+        self.interpreter = self.interpreter.with_overflow_checks(overflow_checks);
+        self
+    }
+
+    // Reads inputs until the end of input. An input goes on over the next lines
+    // while it has unclosed braces, brackets or parentheses.
+    pub fn run(&mut self, input: &mut dyn BufRead, out: &mut dyn Write) -> io::Result<()> {
+        let mut source = String::new();
+        loop {
+            let prompt = if source.is_empty() { PROMPT } else { CONTINUE_PROMPT };
+            write!(out, "{}", prompt)?;
+            out.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(out)?;
+                return Ok(());
+            }
+            source.push_str(&line);
+
+            if is_incomplete(&source) {
+                continue;
+            }
+            let input = std::mem::take(&mut source);
+            self.eval(input.trim(), out)?;
+        }
+    }
+
+    // One input: a meta-command, or functions and statements whose expression values are printed.
+    pub fn eval(&mut self, input: &str, out: &mut dyn Write) -> io::Result<()> {
+        if !input.starts_with(':') {
+            return self.eval_code(input, out);
+        }
+
+        let (command, argument) = match input.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (input, ""),
+        };
+        match command {
+            ":type" => self.show_type(argument, out),
+            ":ast" => self.show_ast(argument, out),
+            ":asm" => self.show_asm(argument, out),
+            ":tokens" => self.show_tokens(argument, out),
+            ":help" => writeln!(out, "{}", HELP),
+            _ => writeln!(out, "unknown command `{}`, see :help", command),
+        }
+    }
+
+    fn eval_code(&mut self, input: &str, out: &mut dyn Write) -> io::Result<()> {
+        if input.is_empty() {
+            return Ok(());
+        }
+
+        let source = terminate(input);
+        let renderer = renderer(&source);
+        let program = match parse(&source) {
+            Ok(program) => program,
+            Err(errs) => return report(&renderer, &errs, out),
+        };
+
+        // run on copies, so a failing input can be thrown away
+        let mut checker = self.checker.clone();
+        let checked = checker.check_more(&program);
+        report(&renderer, &checker.take_warnings(), out)?;
+        if let Err(errs) = checked {
+            return report(&renderer, &errs, out);
+        }
+        let program = lower(&program, &checker.take_literal_types());
+
+        let mut interpreter = self.interpreter.clone();
+        for decl in program.get_structs() {
+            interpreter.define_struct(decl);
+        }
+        for function in program.get_functions() {
+            interpreter.define(function);
+        }
+        for statement in program.get_statements() {
+            let result = interpreter.execute(statement);
+            write!(out, "{}", interpreter.take_output())?;
+            match result {
+                Ok(value) => {
+                    if matches!(statement, Statement::Arithmetic(_)) && value != Value::Unit {
+                        writeln!(out, "{}", value)?;
+                    }
+                }
+                Err(err) => return report(&renderer, &[err], out),
+            }
+        }
+
+        self.checker = checker;
+        self.interpreter = interpreter;
+        self.structs.extend(program.get_structs().iter().cloned());
+        self.enums.extend(program.get_enums().iter().cloned());
+        self.functions.extend(program.get_functions().iter().cloned());
+
+        Ok(())
+    }
+
+    // :type <expr>
+    fn show_type(&mut self, input: &str, out: &mut dyn Write) -> io::Result<()> {
+        let source = terminate(input);
+        let renderer = renderer(&source);
+        let program = match parse(&source) {
+            Ok(program) => program,
+            Err(errs) => return report(&renderer, &errs, out),
+        };
+
+        let arithmetic = match (program.get_functions(), program.get_statements()) {
+            ([], [Statement::Arithmetic(arithmetic)]) => arithmetic,
+            _ => return writeln!(out, "`:type` takes one expression"),
+        };
+
+        match self.checker.clone().type_of(arithmetic) {
+            Ok(arithmetic_type) => writeln!(out, "{}", arithmetic_type),
+            Err(errs) => report(&renderer, &errs, out),
+        }
+    }
+
+    // :ast <code>
+    fn show_ast(&mut self, input: &str, out: &mut dyn Write) -> io::Result<()> {
+        let source = terminate(input);
+        match parse(&source) {
+            Ok(program) => writeln!(out, "{:#?}", program),
+            Err(errs) => report(&renderer(&source), &errs, out),
+        }
+    }
+
+    // :asm <code>
+    // The assembly of a program made of the structs, enums and functions so far, the variables as
+    // `let`s of their current values, and the code.
+    fn show_asm(&mut self, input: &str, out: &mut dyn Write) -> io::Result<()> {
+        let source = terminate(input);
+        let renderer = renderer(&source);
+        let code = match parse(&source) {
+            Ok(program) => program,
+            Err(errs) => return report(&renderer, &errs, out),
+        };
+        // checked like an input first, so its errors only point into it
+        if let Err(errs) = self.checker.clone().check_more(&code) {
+            return report(&renderer, &errs, out);
+        }
+
+        let mut program = Program::new();
+        for decl in self.structs.iter().chain(code.get_structs()) {
+            program.push_struct(decl.clone());
+        }
+        for decl in self.enums.iter().chain(code.get_enums()) {
+            program.push_enum(decl.clone());
+        }
+        for function in self.functions.iter().chain(code.get_functions()) {
+            program.push_function(function.clone());
+        }
+        for (name, value) in self.interpreter.get_variables() {
+            if let Some(value) = constant(value) {
+                program.push(Statement::Let(Let::new(name.to_owned(), false, None, value, Span::default())));
+            }
+        }
+        for statement in code.get_statements() {
+            program.push(statement.clone());
+        }
+
+        // declarations alone have assembly too, without a main
+        let mut checker = TypeChecker::new();
+        if let Err(errs) = checker.check(&program) {
+            let errs: Vec<Diagnostic> = errs.into_iter().filter(|err| err.get_code() != "E0303").collect();
+            if !errs.is_empty() {
+                return report(&renderer, &errs, out);
+            }
+        }
+
+        let mut assembly = Vec::new();
+        X86_64::new().with_file(FILE).generate(&lower(&program, &checker.take_literal_types()), &mut assembly)?;
+        out.write_all(&assembly)
+    }
+
+    // :tokens <code>
+    fn show_tokens(&mut self, input: &str, out: &mut dyn Write) -> io::Result<()> {
+        let tokens = match Tokenizer::new(input.chars().collect()).tokenize() {
+            Ok(tokens) => tokens,
+            Err(errs) => return report(&renderer(input), &errs, out),
+        };
+
+        for token in tokens.iter().filter(|token| token.get_t_type() != TokenType::EOF) {
+            writeln!(out, "{}", token)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse(source: &str) -> Result<Program, Vec<Diagnostic>> {
+    let tokens = Tokenizer::new(source.chars().collect()).tokenize()?;
+    Parser::new(tokens).parse()
+}
+
+// The trailing `;` of a statement is optional in the REPL.
+fn terminate(input: &str) -> String {
+    let input = input.trim_end();
+    if input.is_empty() || input.ends_with(';') {
+        return input.to_owned();
+    }
+
+    // a declaration or a block takes no `;`, but a struct literal at the end does
+    let terminated = format!("{};", input);
+    if input.ends_with('}') && (parse(input).is_ok() || parse(&terminated).is_err()) {
+        return input.to_owned();
+    }
+
+    terminated
+}
+
+// More braces, brackets or parentheses are opened than closed. Code that does not even
+// tokenize is complete, so its errors show up right away.
+fn is_incomplete(source: &str) -> bool {
+    let tokens: Vec<Token> = match Tokenizer::new(source.chars().collect()).tokenize() {
+        Ok(tokens) => tokens,
+        Err(_) => return false,
+    };
+
+    let mut open = 0i32;
+    for token in tokens.iter() {
+        match token.get_t_type() {
+            TokenType::LeftBrace | TokenType::LeftBracket | TokenType::LeftParenthesis => open += 1,
+            TokenType::RightBrace | TokenType::RightBracket | TokenType::RightParenthesis => open -= 1,
+            _ => {}
+        }
+    }
+
+    open > 0
+}
+
+// value as an expression, None for `()` and for an empty array, which has no type by itself
+fn constant(value: &Value) -> Option<Arithmetic> {
+    let span = Span::default();
+    let node = match value {
+        Value::Int(n, int_type) => {
+            let number = Node::Number(Number::new(format!("{}{}", n.unsigned_abs(), int_type.get_suffix()), span));
+            if *n < 0 {
+                Node::Unary(Unary::new(UnaryOperator::Negate, number, span))
+            } else {
+                number
+            }
+        }
+        Value::Bool(b) => Node::Boolean(Boolean::new(*b, span)),
+        Value::Char(c) => Node::Char(Char::new(*c, span)),
+        Value::Str(s) => Node::Str(Str::new(s.clone(), span)),
+        Value::Unit => return None,
+        Value::Struct(name, fields) => {
+            let mut inits = Vec::new();
+            for (field, value) in fields {
+                inits.push(FieldInit::new(field.clone(), constant(value)?, span));
+            }
+            Node::StructLiteral(StructLiteral::new(name.clone(), inits, span))
+        }
+        Value::Array(elements) if elements.is_empty() => return None,
+        Value::Array(elements) => {
+            let elements = elements.iter().map(constant).collect::<Option<Vec<Arithmetic>>>()?;
+            Node::Array(ArrayLiteral::new(elements, span))
+        }
+        Value::Enum(enum_name, name, fields) => {
+            let args = fields.iter().map(constant).collect::<Option<Vec<Arithmetic>>>()?;
+            Node::Variant(Box::new(VariantLiteral::new(enum_name.clone(), name.clone(), args, span)))
+        }
+        Value::Tuple(elements) => {
+            let elements = elements.iter().map(constant).collect::<Option<Vec<Arithmetic>>>()?;
+            Node::Tuple(TupleLiteral::new(elements, span))
+        }
+    };
+
+    Some(Arithmetic::Term(node))
+}
+
+// The REPL prints diagnostics to stdout along with everything else.
+fn renderer(source: &str) -> Renderer<'_> {
+    Renderer::new(FILE, source).with_color(io::stdout().is_terminal())
+}
+
+fn report(renderer: &Renderer, errs: &[Diagnostic], out: &mut dyn Write) -> io::Result<()> {
+    for err in errs {
+        writeln!(out, "{}", renderer.render(err))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,1603 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use super::ast::{
+    Program, StructDecl, EnumDecl, FunctionDecl, Block, Statement, Let, Destructure,
+    Assign, Return, While, For, Iterable, Arithmetic, Node, If, StructLiteral,
+    FieldAccess, ArrayLiteral, TupleLiteral, Index, VariantLiteral, Match, Pattern,
+    Call, Operator, UnaryOperator, TypeName,
+};
+use super::builtins::{ Builtin, RUNTIME_FUNCTIONS };
+use super::diagnostic::Diagnostic;
+use super::tokenizer::Span;
+use super::types::{ Type, IntType, StructType, EnumType, Variant, Field };
+
+mod usefulness;
+
+// Declarations keep where they are as an Option<Span>, which is None once they are
+// from an earlier input of the REPL, whose source diagnostics can no longer show.
+#[derive(Debug, Clone)]
+struct Signature {
+    params: Vec<Option<Type>>,
+    return_type: Option<Type>,
+    span: Option<Span>,
+}
+
+// A variable in scope. var_type is None when its type could not be resolved,
+// which has already been reported.
+#[derive(Debug, Clone)]
+struct Symbol {
+    name: String,
+    var_type: Option<Type>,
+    mutable: bool,
+    span: Option<Span>,
+}
+
+// An integer literal, whose type is only known once its uses have been checked.
+#[derive(Debug, Clone)]
+struct Literal {
+    span: Span,
+    // None when it is too large for any type
+    value: Option<i128>,
+    int_type: IntType,
+    // the type is from its suffix, or else from how it is used
+    suffixed: bool,
+    // the operand of `-`, so it may be one more than the maximum of a signed type
+    negated: bool,
+}
+
+// A struct or an enum, which may hold each other.
+#[derive(Debug, Clone, Copy)]
+enum TypeDecl<'a> {
+    Struct(&'a StructDecl),
+    Enum(&'a EnumDecl),
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct TypeChecker {
+    // the struct and enum types with where they are declared
+    structs: HashMap<String, (Rc<StructType>, Option<Span>)>,
+    enums: HashMap<String, (Rc<EnumType>, Option<Span>)>,
+    functions: HashMap<String, Signature>,
+    variables: Vec<Symbol>,
+    return_type: Option<Type>,
+    // name of the function being checked, None for the top-level statements
+    function: Option<String>,
+    // number of loops around the statement being checked
+    loops: usize,
+    errors: Vec<Diagnostic>,
+    // found along with the errors, but they do not fail a check
+    warnings: Vec<Diagnostic>,
+    // the integer literals of the code being checked
+    literals: Vec<Literal>,
+    // the types of the literals without a suffix that have been checked
+    literal_types: HashMap<Span, IntType>,
+}
+
+/*
+    Types are Option<Type> while checking: None is a type error that has already
+    been reported, so it is compatible with everything and no error cascades from it.
+    A block that never finishes, because it returns or leaves a loop, is None as well,
+    so `if c { 1 } else { return 0; }` is an int.
+
+    An integer literal without a suffix, and arithmetic made only of such literals, is
+    untyped: it is an `int` unless it is used where another integer type is expected,
+    like `let x: u8 = 2 * 100;` or `x + 1` for an x of that type, which it then has.
+    Literals are checked against their types after the code they are in.
+*/
+impl TypeChecker {
+    pub fn new() -> TypeChecker {
+        TypeChecker {
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            functions: HashMap::new(),
+            variables: Vec::new(),
+            return_type: None,
+            function: None,
+            loops: 0usize,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            literals: Vec::new(),
+            literal_types: HashMap::new(),
+        }
+    }
+
+    pub fn check(&mut self, program: &Program) -> Result<(), Vec<Diagnostic>> {
+        // all types and signatures first, so they can use each other in any order
+        self.declare_types(program.get_structs(), program.get_enums());
+        let declared: Vec<&FunctionDecl> = program.get_functions().iter().filter(|function| self.declare_function(function)).collect();
+
+        for function in declared {
+            if function.get_name() == "main" {
+                self.check_main(function, program);
+            }
+            self.check_function(function);
+        }
+
+        // the top-level statements are the body of main
+        self.variables.clear();
+        self.return_type = Some(Type::Int(IntType::I64));
+        self.function = None;
+        for statement in program.get_statements() {
+            self.check_statement(statement);
+        }
+        self.check_literals();
+
+        if !program.has_statements() && !self.functions.contains_key("main") {
+            let error = Diagnostic::error("E0303", "`main` function not found".to_owned())
+                .with_note("a program needs top-level statements or a `fn main()`".to_owned());
+            self.errors.push(error);
+        }
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors.clone())
+        }
+    }
+
+    // main is where the program starts, which passes it nothing and exits with what it returns.
+    fn check_main(&mut self, main: &FunctionDecl, program: &Program) {
+        if program.has_statements() {
+            let error = Diagnostic::error("E0209", "`main` conflicts with the top-level statements".to_owned())
+                .with_label(main.get_span(), "`main` defined here".to_owned())
+                .with_note("the top-level statements are compiled into `main`".to_owned());
+            self.errors.push(error);
+        }
+
+        if let (Some(first), Some(last)) = (main.get_params().first(), main.get_params().last()) {
+            let error = Diagnostic::error("E0229", "`main` takes no parameters".to_owned())
+                .with_label(first.get_span().to(last.get_span()), "remove these parameters".to_owned());
+            self.errors.push(error);
+        }
+
+        let return_type = self.functions["main"].return_type.clone();
+        if let (Some(type_name), Some(return_type)) = (main.get_return_type(), return_type) {
+            if !matches!(return_type, Type::Int(_) | Type::Bool | Type::Char | Type::Unit) {
+                let error = Diagnostic::error("E0230", format!("`main` cannot return `{}`", return_type))
+                    .with_label(type_name.get_span(), "not an exit status".to_owned())
+                    .with_note("`main` returns an integer, a `bool`, a `char` or `()`, which the program exits with".to_owned());
+                self.errors.push(error);
+            }
+        }
+    }
+
+    // Checks one more piece of a program on top of the earlier ones, whose variables
+    // stay in scope like in the top-level statements. This is how the REPL checks
+    // each input; an input with errors may still leave some of its declarations behind.
+    pub fn check_more(&mut self, program: &Program) -> Result<(), Vec<Diagnostic>> {
+        self.forget_spans();
+        self.declare_types(program.get_structs(), program.get_enums());
+        let declared: Vec<&FunctionDecl> = program.get_functions().iter().filter(|function| self.declare_function(function)).collect();
+        for function in declared {
+            self.check_function(function);
+        }
+
+        self.return_type = Some(Type::Int(IntType::I64));
+        self.function = None;
+        for statement in program.get_statements() {
+            self.check_statement(statement);
+        }
+        self.check_literals();
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    // The type of an expression over the variables in scope.
+    pub fn type_of(&mut self, arithmetic: &Arithmetic) -> Result<Type, Vec<Diagnostic>> {
+        self.forget_spans();
+        let arithmetic_type = self.type_of_arithmetic(arithmetic);
+        self.check_literals();
+        match arithmetic_type {
+            Some(arithmetic_type) if self.errors.is_empty() => Ok(arithmetic_type),
+            _ => Err(std::mem::take(&mut self.errors)),
+        }
+    }
+
+    // What is declared so far is from earlier code than the one checked next.
+    fn forget_spans(&mut self) {
+        let structs = self.structs.values_mut().map(|(_, span)| span);
+        for span in structs.chain(self.enums.values_mut().map(|(_, span)| span)) {
+            *span = None;
+        }
+        for signature in self.functions.values_mut() {
+            signature.span = None;
+        }
+        for symbol in self.variables.iter_mut() {
+            symbol.span = None;
+        }
+    }
+
+    // The warnings of the checks so far, which are then forgotten.
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    // The types of the integer literals without a suffix checked so far, by where they
+    // are, which lowering writes out as suffixes. They are then forgotten.
+    pub fn take_literal_types(&mut self) -> HashMap<Span, IntType> {
+        std::mem::take(&mut self.literal_types)
+    }
+
+    fn declare_types(&mut self, structs: &[StructDecl], enums: &[EnumDecl]) {
+        let decls = structs.iter().map(TypeDecl::Struct).chain(enums.iter().map(TypeDecl::Enum));
+        let mut declared: Vec<TypeDecl> = Vec::new();
+        for decl in decls {
+            let previous = self.type_span(decl.get_name())
+                .or_else(|| declared.iter().find(|other| other.get_name() == decl.get_name()).map(|other| Some(other.get_span())));
+            match previous {
+                Some(previous) => {
+                    let error = Diagnostic::error("E0208", format!("the {} `{}` is defined multiple times", decl.kind(), decl.get_name()))
+                        .with_label(decl.get_span(), "redefined here".to_owned());
+                    self.errors.push(declared_at(error, previous, "previous definition here", "the previous definition is in an earlier input"));
+                }
+                None => declared.push(decl),
+            }
+        }
+
+        for decl in declared.iter() {
+            self.declare_type(*decl, &declared, &mut Vec::new());
+        }
+    }
+
+    // Lays out decl after the types its fields hold, which are among declared
+    // when they are not laid out yet. visiting are the types that hold this one.
+    // Returns false when decl holds itself.
+    fn declare_type(&mut self, decl: TypeDecl, declared: &[TypeDecl], visiting: &mut Vec<String>) -> bool {
+        if self.structs.contains_key(decl.get_name()) || self.enums.contains_key(decl.get_name()) {
+            return true;
+        }
+        if let Some(start) = visiting.iter().position(|name| name == decl.get_name()) {
+            let error = Diagnostic::error("E0216", format!("recursive {} `{}` has infinite size", decl.kind(), decl.get_name()))
+                .with_label(decl.get_span(), "recursive without indirection".to_owned())
+                .with_note(format!("the fields hold {} -> {}", visiting[start..].join(" -> "), decl.get_name()));
+            self.errors.push(error);
+            return false;
+        }
+
+        visiting.push(decl.get_name().to_owned());
+        match decl {
+            TypeDecl::Struct(decl) => self.declare_struct(decl, declared, visiting),
+            TypeDecl::Enum(decl) => self.declare_enum(decl, declared, visiting),
+        }
+        visiting.pop();
+
+        true
+    }
+
+    fn declare_struct(&mut self, decl: &StructDecl, declared: &[TypeDecl], visiting: &mut Vec<String>) {
+        let mut fields: Vec<Field> = Vec::new();
+        for field in decl.get_fields() {
+            if fields.iter().any(|other| other.get_name() == field.get_name()) {
+                let error = Diagnostic::error("E0217", format!("field `{}` is already declared", field.get_name()))
+                    .with_label(field.get_span(), "declared again here".to_owned());
+                self.errors.push(error);
+                continue;
+            }
+
+            // a field of an unknown type is left out, which has been reported
+            if let Some(field_type) = self.resolve_held(field.get_type_name(), declared, visiting) {
+                fields.push(Field::new(field.get_name().to_owned(), field_type));
+            }
+        }
+
+        let struct_type = Rc::new(StructType::new(decl.get_name().to_owned(), fields));
+        self.structs.insert(decl.get_name().to_owned(), (struct_type, Some(decl.get_span())));
+    }
+
+    fn declare_enum(&mut self, decl: &EnumDecl, declared: &[TypeDecl], visiting: &mut Vec<String>) {
+        let mut variants: Vec<Variant> = Vec::new();
+        for variant in decl.get_variants() {
+            if variants.iter().any(|other| other.get_name() == variant.get_name()) {
+                let error = Diagnostic::error("E0217", format!("variant `{}` is already declared", variant.get_name()))
+                    .with_label(variant.get_span(), "declared again here".to_owned());
+                self.errors.push(error);
+                continue;
+            }
+
+            let fields = variant.get_fields().iter().filter_map(|type_name| self.resolve_held(type_name, declared, visiting)).collect();
+            variants.push(Variant::new(variant.get_name().to_owned(), fields));
+        }
+
+        let enum_type = Rc::new(EnumType::new(decl.get_name().to_owned(), variants));
+        self.enums.insert(decl.get_name().to_owned(), (enum_type, Some(decl.get_span())));
+    }
+
+    // The type of a field, after laying out the types it holds, like `P` in `[P; 2]`.
+    fn resolve_held(&mut self, type_name: &TypeName, declared: &[TypeDecl], visiting: &mut Vec<String>) -> Option<Type> {
+        let mut held = true;
+        for name in type_name.get_names() {
+            if let Some(decl) = declared.iter().find(|other| other.get_name() == name && Type::primitive(name).is_none()) {
+                held &= self.declare_type(*decl, declared, visiting);
+            }
+        }
+        if !held {
+            return None;
+        }
+
+        self.resolve_type(type_name)
+    }
+
+    // Where the struct or enum name is declared, if it is.
+    fn type_span(&self, name: &str) -> Option<Option<Span>> {
+        self.structs.get(name).map(|(_, span)| *span).or_else(|| self.enums.get(name).map(|(_, span)| *span))
+    }
+
+    // false for a redefinition, whose body is then not checked against the signature
+    // of the first definition.
+    fn declare_function(&mut self, function: &FunctionDecl) -> bool {
+        // its body is still checked, but calls go to the builtin
+        if Builtin::from_name(function.get_name()).is_some() {
+            let error = Diagnostic::error("E0208", format!("the function `{}` is defined multiple times", function.get_name()))
+                .with_label(function.get_span(), "redefined here".to_owned())
+                .with_note(format!("`{}` is a builtin function", function.get_name()));
+            self.errors.push(error);
+        }
+        if RUNTIME_FUNCTIONS.contains(&function.get_name()) {
+            let error = Diagnostic::error("E0231", format!("the name `{}` is reserved", function.get_name()))
+                .with_label(function.get_span(), "reserved for the runtime".to_owned())
+                .with_note(format!("compiled programs call the C library's `{}`, which this would replace", function.get_name()));
+            self.errors.push(error);
+        }
+        if let Some(previous) = self.functions.get(function.get_name()) {
+            let error = Diagnostic::error("E0208", format!("the function `{}` is defined multiple times", function.get_name()))
+                .with_label(function.get_span(), "redefined here".to_owned());
+            self.errors.push(declared_at(error, previous.span, "previous definition here", "the previous definition is in an earlier input"));
+            return false;
+        }
+
+        let params = function.get_params().iter().map(|param| self.resolve_type(param.get_type_name())).collect();
+        let return_type = match function.get_return_type() {
+            Some(type_name) => self.resolve_type(type_name),
+            None => Some(Type::Unit),
+        };
+
+        let signature = Signature {
+            params,
+            return_type,
+            span: Some(function.get_span()),
+        };
+        self.functions.insert(function.get_name().to_owned(), signature);
+        true
+    }
+
+    fn check_function(&mut self, function: &FunctionDecl) {
+        let signature = &self.functions[function.get_name()];
+        self.return_type = signature.return_type.clone();
+        self.function = Some(function.get_name().to_owned());
+
+        // a function only sees its own parameters and locals
+        let params = function.get_params().iter().zip(signature.params.iter()).map(|(param, param_type)| {
+            Symbol {
+                name: param.get_name().to_owned(),
+                var_type: param_type.clone(),
+                mutable: false,
+                span: Some(param.get_span()),
+            }
+        }).collect();
+        let outer = std::mem::replace(&mut self.variables, params);
+        let loops = std::mem::replace(&mut self.loops, 0usize);
+
+        let body = function.get_body();
+        let body_type = self.check_block(body);
+        self.variables = outer;
+        self.loops = loops;
+
+        // the value of the body is returned
+        if let Some(value) = body.get_value() {
+            let body_type = self.infer(value, body_type, &self.return_type.clone());
+            if let Some(error) = mismatch(&self.return_type, &body_type, value.get_span()) {
+                self.errors.push(error.with_secondary(function.get_span(), "expected because of the return type".to_owned()));
+            }
+        } else if !diverges(body) {
+            if let Some(return_type) = self.return_type.clone() {
+                if return_type != Type::Unit {
+                    let error = Diagnostic::error("E0207", format!("function `{}` may end without returning a value", function.get_name()))
+                        .with_label(function.get_span(), format!("expected to return `{}`", return_type))
+                        .with_help("end the body with a `return` statement or a value".to_owned());
+                    self.errors.push(error);
+                }
+            }
+        }
+    }
+
+    // The type of the value of block.
+    fn check_block(&mut self, block: &Block) -> Option<Type> {
+        // variables declared in the block go out of scope at its end
+        let scope = self.variables.len();
+        for statement in block.get_statements() {
+            self.check_statement(statement);
+        }
+        let value_type = match block.get_value() {
+            Some(value) => self.type_of_arithmetic(value),
+            None if diverges(block) => None,
+            None => Some(Type::Unit),
+        };
+        self.variables.truncate(scope);
+
+        value_type
+    }
+
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Arithmetic(arithmetic) => {
+                self.type_of_arithmetic(arithmetic);
+            }
+            Statement::Let(l) => {
+                self.check_let(l);
+            }
+            Statement::Destructure(destructure) => {
+                self.check_destructure(destructure);
+            }
+            Statement::Assign(assign) => {
+                self.check_assign(assign);
+            }
+            Statement::Return(r) => {
+                self.check_return(r);
+            }
+            Statement::While(w) => {
+                self.check_while(w);
+            }
+            Statement::For(f) => {
+                self.check_for(f);
+            }
+            Statement::Break(span) => {
+                self.check_in_loop("break", *span);
+            }
+            Statement::Continue(span) => {
+                self.check_in_loop("continue", *span);
+            }
+        }
+    }
+
+    fn check_let(&mut self, l: &Let) {
+        let value_type = self.type_of_arithmetic(l.get_value());
+
+        let var_type = match l.get_type_name() {
+            Some(type_name) => {
+                let annotated = self.resolve_type(type_name);
+                let value_type = self.infer(l.get_value(), value_type, &annotated);
+                if let Some(error) = mismatch(&annotated, &value_type, l.get_value().get_span()) {
+                    self.errors.push(error.with_secondary(type_name.get_span(), "expected due to this type annotation".to_owned()));
+                }
+                annotated
+            }
+            None => value_type,
+        };
+
+        // declared after the initializer, so `let x = x + 1;` reads the old x
+        self.variables.push(Symbol {
+            name: l.get_name().to_owned(),
+            var_type,
+            mutable: l.is_mutable(),
+            span: Some(l.get_span()),
+        });
+    }
+
+    // Like a let of each binding of the pattern, which the parser only lets be
+    // made of bindings, `_` and tuples, so it matches any value of its type.
+    fn check_destructure(&mut self, destructure: &Destructure) {
+        let value_type = self.type_of_arithmetic(destructure.get_value());
+
+        let var_type = match destructure.get_type_name() {
+            Some(type_name) => {
+                let annotated = self.resolve_type(type_name);
+                let value_type = self.infer(destructure.get_value(), value_type, &annotated);
+                if let Some(error) = mismatch(&annotated, &value_type, destructure.get_value().get_span()) {
+                    self.errors.push(error.with_secondary(type_name.get_span(), "expected due to this type annotation".to_owned()));
+                }
+                annotated
+            }
+            None => value_type,
+        };
+
+        self.check_pattern(destructure.get_pattern(), &var_type, &mut Vec::new());
+    }
+
+    fn check_assign(&mut self, assign: &Assign) {
+        let value_type = self.type_of_arithmetic(assign.get_value());
+
+        // the variable that holds the target
+        let target = assign.get_target();
+        let mut root = target;
+        let name = loop {
+            match root {
+                Node::Variable(variable) => break variable.get_name(),
+                Node::Field(field) => root = field.get_base(),
+                Node::Index(index) => root = index.get_base(),
+                _ => unreachable!("the parser only assigns to variables and their fields and elements"),
+            }
+        };
+
+        let (mutable, declared) = match self.find_variable(name) {
+            Some(symbol) => (symbol.mutable, symbol.span),
+            None => {
+                self.errors.push(not_found("variable", name, assign.get_span()));
+                return;
+            }
+        };
+
+        if !mutable {
+            let earlier = format!("`{}` is declared in an earlier input", name);
+            let error = match target {
+                Node::Variable(_) => {
+                    let error = Diagnostic::error("E0205", format!("cannot assign twice to immutable variable `{}`", name))
+                        .with_label(assign.get_span(), "cannot assign twice to immutable variable".to_owned());
+                    declared_at(error, declared, "first assignment", &earlier)
+                }
+                _ => {
+                    let error = Diagnostic::error("E0205", format!("cannot assign to `{}`, as `{}` is not declared as mutable", place_name(target), name))
+                        .with_label(assign.get_span(), "cannot assign".to_owned());
+                    declared_at(error, declared, &format!("`{}` declared here", name), &earlier)
+                }
+            };
+            self.errors.push(error.with_help(format!("declare it as `let mut {}`", name)));
+        }
+        let target_type = self.type_of_node(target);
+        let value_type = self.infer(assign.get_value(), value_type, &target_type);
+        self.expect_type(&target_type, &value_type, assign.get_value().get_span());
+    }
+
+    fn check_return(&mut self, r: &Return) {
+        let expected = self.return_type.clone();
+        let (actual, span) = match r.get_value() {
+            Some(value) => {
+                let value_type = self.type_of_arithmetic(value);
+                (self.infer(value, value_type, &expected), value.get_span())
+            }
+            None => (Some(Type::Unit), r.get_span()),
+        };
+
+        if let Some(mut error) = mismatch(&expected, &actual, span) {
+            if let Some(function) = &self.function {
+                if let Some(definition) = self.functions[function].span {
+                    error = error.with_secondary(definition, "expected because of the return type".to_owned());
+                }
+            }
+            self.errors.push(error);
+        }
+    }
+
+    fn check_while(&mut self, w: &While) {
+        self.check_condition(w.get_condition());
+
+        self.loops += 1;
+        let body_type = self.check_block(w.get_body());
+        self.loops -= 1;
+
+        if let Some(value) = w.get_body().get_value() {
+            self.expect_type(&Some(Type::Unit), &body_type, value.get_span());
+        }
+    }
+
+    fn check_for(&mut self, f: &For) {
+        let element_type = match f.get_iterable() {
+            Iterable::Range(range) => {
+                // integers of one type, an untyped bound takes the type of the other one
+                let (start, end) = (range.get_start(), range.get_end());
+                let start_type = self.type_of_arithmetic(start);
+                let end_type = self.type_of_arithmetic(end);
+                let start_type = self.infer(start, start_type, &end_type);
+                let end_type = self.infer(end, end_type, &start_type);
+                let int_type = match (&start_type, &end_type) {
+                    (Some(Type::Int(int_type)), _) | (_, Some(Type::Int(int_type))) => *int_type,
+                    _ => IntType::I64,
+                };
+                self.expect_type(&Some(Type::Int(int_type)), &start_type, start.get_span());
+                self.expect_type(&Some(Type::Int(int_type)), &end_type, end.get_span());
+
+                // lowering steps through the range by a literal 1 at the span of the loop
+                self.literal_types.insert(f.get_span(), int_type);
+                Some(Type::Int(int_type))
+            }
+            Iterable::Expression(expression) => {
+                match self.type_of_arithmetic(expression) {
+                    Some(Type::Array(element, _)) => Some(*element),
+                    Some(expression_type) => {
+                        let error = Diagnostic::error("E0212", format!("`{}` is not iterable", expression_type))
+                            .with_label(expression.get_span(), "cannot be iterated over".to_owned())
+                            .with_help("iterate over a range, like `0..n`, or an array".to_owned());
+                        self.errors.push(error);
+                        None
+                    }
+                    None => None,
+                }
+            }
+        };
+
+        // the loop variable is only in scope in the body
+        let scope = self.variables.len();
+        self.variables.push(Symbol {
+            name: f.get_variable().to_owned(),
+            var_type: element_type,
+            mutable: false,
+            span: Some(f.get_span()),
+        });
+        self.loops += 1;
+        let body_type = self.check_block(f.get_body());
+        self.loops -= 1;
+        self.variables.truncate(scope);
+
+        if let Some(value) = f.get_body().get_value() {
+            self.expect_type(&Some(Type::Unit), &body_type, value.get_span());
+        }
+    }
+
+    fn check_in_loop(&mut self, keyword: &str, span: Span) {
+        if self.loops == 0 {
+            let error = Diagnostic::error("E0211", format!("`{}` outside of a loop", keyword))
+                .with_label(span, format!("cannot `{}` outside of a loop", keyword));
+            self.errors.push(error);
+        }
+    }
+
+    fn check_condition(&mut self, condition: &Arithmetic) {
+        let condition_type = self.type_of_arithmetic(condition);
+        self.expect_type(&Some(Type::Bool), &condition_type, condition.get_span());
+    }
+
+    fn type_of_if(&mut self, i: &If) -> Option<Type> {
+        self.check_condition(i.get_condition());
+        let then_type = self.check_block(i.get_then());
+
+        let otherwise = match i.get_otherwise() {
+            Some(otherwise) => otherwise,
+            None => {
+                // the missing else is `()`, so the then block has to be too
+                if let Some(error) = mismatch(&Some(Type::Unit), &then_type, block_span(i.get_then(), i.get_span())) {
+                    self.errors.push(error.with_note("`if` without `else` evaluates to `()`".to_owned()));
+                }
+                return Some(Type::Unit);
+            }
+        };
+        let otherwise_type = self.check_block(otherwise);
+
+        // an untyped branch takes the type of the other one
+        let then_type = self.infer_value(i.get_then(), then_type, &otherwise_type);
+        let otherwise_type = self.infer_value(otherwise, otherwise_type, &then_type);
+
+        match (then_type, otherwise_type) {
+            (Some(then_type), Some(otherwise_type)) => {
+                if then_type != otherwise_type {
+                    let error = Diagnostic::error("E0206", "`if` and `else` have incompatible types".to_owned())
+                        .with_label(block_span(otherwise, i.get_span()), format!("expected `{}`, found `{}`", then_type, otherwise_type))
+                        .with_secondary(block_span(i.get_then(), i.get_span()), "expected because of this".to_owned());
+                    self.errors.push(error);
+                    return None;
+                }
+                Some(then_type)
+            }
+            // a branch that never finishes takes the type of the other one
+            (Some(branch_type), None) | (None, Some(branch_type)) => Some(branch_type),
+            (None, None) => None,
+        }
+    }
+
+    fn type_of_arithmetic(&mut self, arithmetic: &Arithmetic) -> Option<Type> {
+        match arithmetic {
+            Arithmetic::Term(node) => self.type_of_node(node),
+            Arithmetic::MultiTerm(left, op, right) => {
+                // an untyped side takes the type of the other one
+                let left_type = self.type_of_node(left);
+                let right_type = self.type_of_node(right);
+                let left_type = self.infer_node(left, left_type, &right_type);
+                let right_type = self.infer_node(right, right_type, &left_type);
+                let (operand_type, result_type) = match op {
+                    // any two values of the same type that fits in a register
+                    Operator::Equal | Operator::NotEqual => {
+                        self.expect_type(&left_type, &right_type, right.get_span());
+                        if let Some(left_type) = left_type.filter(|left_type| !left_type.is_scalar()) {
+                            let op = if *op == Operator::Equal { "==" } else { "!=" };
+                            let mut error = Diagnostic::error("E0218", format!("binary operation `{}` cannot be applied to type `{}`", op, left_type))
+                                .with_label(arithmetic.get_span(), format!("`{}` cannot be compared", left_type));
+                            match left_type {
+                                Type::Struct(_) | Type::Tuple(_) => {
+                                    error = error.with_help("compare the fields one by one".to_owned());
+                                }
+                                Type::Enum(_) => {
+                                    error = error.with_help("tell the variants apart with `match`".to_owned());
+                                }
+                                _ => {}
+                            }
+                            self.errors.push(error);
+                        }
+                        return Some(Type::Bool);
+                    }
+                    // integers of one type, or chars
+                    Operator::Less | Operator::LessEqual | Operator::Greater | Operator::GreaterEqual => {
+                        match (&left_type, &right_type) {
+                            (Some(operand_type @ (Type::Int(_) | Type::Char)), _) | (_, Some(operand_type @ (Type::Int(_) | Type::Char))) => {
+                                (operand_type.clone(), Type::Bool)
+                            }
+                            _ => (Type::Int(IntType::I64), Type::Bool),
+                        }
+                    }
+                    Operator::And | Operator::Or => {
+                        (Type::Bool, Type::Bool)
+                    }
+                    // integers of one type, which is that of the result
+                    Operator::Plus | Operator::Minus | Operator::Mul | Operator::Div | Operator::Rem => {
+                        match (&left_type, &right_type) {
+                            (Some(operand_type @ Type::Int(_)), _) | (_, Some(operand_type @ Type::Int(_))) => {
+                                (operand_type.clone(), operand_type.clone())
+                            }
+                            _ => (Type::Int(IntType::I64), Type::Int(IntType::I64)),
+                        }
+                    }
+                };
+                self.expect_type(&Some(operand_type.clone()), &left_type, left.get_span());
+                self.expect_type(&Some(operand_type), &right_type, right.get_span());
+
+                Some(result_type)
+            }
+        }
+    }
+
+    fn type_of_node(&mut self, node: &Node) -> Option<Type> {
+        match node {
+            Node::Number(number) => {
+                let int_type = number.get_suffix().unwrap_or(IntType::I64);
+                self.literals.push(Literal {
+                    span: number.get_span(),
+                    value: number.get_value(),
+                    int_type,
+                    suffixed: number.get_suffix().is_some(),
+                    negated: false,
+                });
+                Some(Type::Int(int_type))
+            }
+            Node::Boolean(_) => Some(Type::Bool),
+            Node::Str(_) => Some(Type::Str),
+            Node::Char(_) => Some(Type::Char),
+            Node::Variable(variable) => {
+                match self.find_variable(variable.get_name()) {
+                    Some(symbol) => symbol.var_type.clone(),
+                    None => {
+                        self.errors.push(not_found("variable", variable.get_name(), variable.get_span()));
+                        None
+                    }
+                }
+            }
+            Node::Call(call) => self.type_of_call(call),
+            Node::Unary(unary) => {
+                let operand_type = self.type_of_node(unary.get_operand());
+                match unary.get_op() {
+                    UnaryOperator::Negate => {
+                        if let Node::Number(number) = unary.get_operand() {
+                            self.literal(number.get_span()).negated = true;
+                        }
+                        match operand_type {
+                            Some(Type::Int(int_type)) => {
+                                self.check_negate(int_type, unary.get_span());
+                                Some(Type::Int(int_type))
+                            }
+                            _ => {
+                                self.expect_type(&Some(Type::Int(IntType::I64)), &operand_type, unary.get_operand().get_span());
+                                Some(Type::Int(IntType::I64))
+                            }
+                        }
+                    }
+                    UnaryOperator::Not => {
+                        self.expect_type(&Some(Type::Bool), &operand_type, unary.get_operand().get_span());
+                        Some(Type::Bool)
+                    }
+                }
+            }
+            Node::If(i) => self.type_of_if(i),
+            Node::StructLiteral(literal) => self.type_of_struct_literal(literal),
+            Node::Field(field) => self.type_of_field(field),
+            Node::Array(array) => self.type_of_array(array),
+            Node::Repeat(repeat) => {
+                let value_type = self.type_of_arithmetic(repeat.get_value())?;
+                Some(Type::array(value_type, repeat.get_len()))
+            }
+            Node::Tuple(tuple) => self.type_of_tuple(tuple),
+            Node::Index(index) => self.type_of_index(index),
+            Node::Variant(variant) => self.type_of_variant(variant),
+            Node::Match(m) => self.type_of_match(m),
+            Node::Arithmetic(arithmetic) => self.type_of_arithmetic(arithmetic),
+        }
+    }
+
+    // Only signed integers can be negated.
+    fn check_negate(&mut self, int_type: IntType, span: Span) {
+        if !int_type.is_signed() {
+            let error = Diagnostic::error("E0228", format!("cannot apply unary operator `-` to type `{}`", int_type))
+                .with_label(span, "cannot apply unary operator `-`".to_owned())
+                .with_note("unsigned values cannot be negated".to_owned());
+            self.errors.push(error);
+        }
+    }
+
+    // The latest literal at span.
+    fn literal(&mut self, span: Span) -> &mut Literal {
+        self.literals.iter_mut().rev().find(|literal| literal.span == span).expect("literals are recorded when they are checked")
+    }
+
+    // The type of arithmetic where a value of expected_type is wanted, given its own
+    // actual_type: the untyped parts of it, even inside arrays, tuples and the branches
+    // of an `if` or a `match`, take the integer type expected there.
+    fn infer(&mut self, arithmetic: &Arithmetic, actual_type: Option<Type>, expected_type: &Option<Type>) -> Option<Type> {
+        match (arithmetic, expected_type) {
+            (Arithmetic::Term(node), _) => self.infer_node(node, actual_type, expected_type),
+            (_, Some(Type::Int(int_type))) if is_untyped(arithmetic) => {
+                self.retype(arithmetic, *int_type);
+                Some(Type::Int(*int_type))
+            }
+            _ => actual_type,
+        }
+    }
+
+    fn infer_node(&mut self, node: &Node, actual_type: Option<Type>, expected_type: &Option<Type>) -> Option<Type> {
+        match (node, expected_type, &actual_type) {
+            (Node::Arithmetic(arithmetic), _, _) => self.infer(arithmetic, actual_type, expected_type),
+            (_, Some(Type::Int(int_type)), _) if is_untyped_node(node) => {
+                self.retype_node(node, *int_type);
+                Some(Type::Int(*int_type))
+            }
+            (Node::Array(array), Some(Type::Array(expected, _)), Some(Type::Array(actual, len))) => {
+                let element_types: Vec<Option<Type>> = array.get_elements().iter()
+                    .map(|element| self.infer(element, Some(*actual.clone()), &Some(*expected.clone())))
+                    .collect();
+                match element_types.first() {
+                    Some(Some(first)) if element_types.iter().all(|element_type| element_type.as_ref() == Some(first)) => {
+                        Some(Type::array(first.clone(), *len))
+                    }
+                    _ => actual_type,
+                }
+            }
+            (Node::Repeat(repeat), Some(Type::Array(expected, _)), Some(Type::Array(actual, len))) => {
+                let value_type = self.infer(repeat.get_value(), Some(*actual.clone()), &Some(*expected.clone()));
+                Some(Type::array(value_type?, *len))
+            }
+            (Node::Tuple(tuple), Some(Type::Tuple(expected)), Some(Type::Tuple(actual))) if expected.len() == actual.len() => {
+                let element_types: Vec<Option<Type>> = tuple.get_elements().iter().zip(expected.iter().zip(actual))
+                    .map(|(element, (expected, actual))| self.infer(element, Some(actual.clone()), &Some(expected.clone())))
+                    .collect();
+                Some(Type::Tuple(element_types.into_iter().collect::<Option<Vec<Type>>>()?))
+            }
+            (Node::If(i), Some(_), Some(_)) if i.get_otherwise().is_some() => {
+                let branches = vec![i.get_then(), i.get_otherwise().expect("checked above")];
+                self.infer_branches(&branches, actual_type, expected_type)
+            }
+            (Node::Match(m), Some(_), Some(_)) => {
+                let branches: Vec<&Block> = m.get_arms().iter().map(|arm| arm.get_body()).collect();
+                self.infer_branches(&branches, actual_type, expected_type)
+            }
+            _ => actual_type,
+        }
+    }
+
+    // The type of the value of block, of actual_type by itself, where expected_type is wanted.
+    fn infer_value(&mut self, block: &Block, actual_type: Option<Type>, expected_type: &Option<Type>) -> Option<Type> {
+        match block.get_value() {
+            Some(value) => self.infer(value, actual_type, expected_type),
+            None => actual_type,
+        }
+    }
+
+    // The value of each branch of an `if` or a `match` is inferred on its own, and the
+    // whole takes their type when they agree. Branches that never finish have no value.
+    fn infer_branches(&mut self, branches: &[&Block], actual_type: Option<Type>, expected_type: &Option<Type>) -> Option<Type> {
+        let mut inferred: Option<Type> = None;
+        for branch in branches {
+            let value = match branch.get_value() {
+                Some(value) => value,
+                None => continue,
+            };
+            let value_type = self.infer(value, actual_type.clone(), expected_type);
+            match &inferred {
+                None => inferred = value_type,
+                Some(first) if value_type.as_ref() != Some(first) => return actual_type,
+                Some(_) => {}
+            }
+        }
+
+        inferred.or(actual_type)
+    }
+
+    // Gives the literals of the untyped arithmetic int_type.
+    fn retype(&mut self, arithmetic: &Arithmetic, int_type: IntType) {
+        match arithmetic {
+            Arithmetic::Term(node) => self.retype_node(node, int_type),
+            Arithmetic::MultiTerm(left, _, right) => {
+                self.retype_node(left, int_type);
+                self.retype_node(right, int_type);
+            }
+        }
+    }
+
+    fn retype_node(&mut self, node: &Node, int_type: IntType) {
+        match node {
+            Node::Number(number) => {
+                self.literal(number.get_span()).int_type = int_type;
+            }
+            Node::Unary(unary) => {
+                self.check_negate(int_type, unary.get_span());
+                self.retype_node(unary.get_operand(), int_type);
+            }
+            Node::Arithmetic(arithmetic) => {
+                self.retype(arithmetic, int_type);
+            }
+            _ => unreachable!("untyped nodes are made of integer literals"),
+        }
+    }
+
+    // Each literal checked since the last time has to fit into its type.
+    fn check_literals(&mut self) {
+        for literal in std::mem::take(&mut self.literals) {
+            let int_type = literal.int_type;
+            let fits = match literal.value {
+                Some(value) => int_type.contains(value) || literal.negated && int_type.contains(-value),
+                None => false,
+            };
+            if !fits {
+                let error = Diagnostic::error("E0210", format!("literal out of range for `{}`", int_type))
+                    .with_label(literal.span, format!("does not fit into `{}`", int_type))
+                    .with_note(format!("the range of `{}` is `{}..={}`", int_type, int_type.min(), int_type.max()));
+                self.errors.push(error);
+            }
+            if !literal.suffixed {
+                self.literal_types.insert(literal.span, int_type);
+            }
+        }
+    }
+
+    // Every element has the type of the first one. Untyped ones take the type of the
+    // first element of an integer type, like in `[1, x]`.
+    fn type_of_array(&mut self, array: &ArrayLiteral) -> Option<Type> {
+        let element_types: Vec<Option<Type>> = array.get_elements().iter().map(|element| self.type_of_arithmetic(element)).collect();
+        let typed = array.get_elements().iter().zip(element_types.iter())
+            .position(|(element, element_type)| !is_untyped(element) && matches!(element_type, Some(Type::Int(_))));
+        let leading = match array.get_elements().first() {
+            Some(first) if is_untyped(first) => typed.unwrap_or(0),
+            _ => 0,
+        };
+        let first = match element_types.get(leading) {
+            Some(first) => first.clone(),
+            None => {
+                let error = Diagnostic::error("E0220", "cannot infer the type of an empty array".to_owned())
+                    .with_label(array.get_span(), "the element type is unknown".to_owned())
+                    .with_help("write an empty array as `[value; 0]`".to_owned());
+                self.errors.push(error);
+                return None;
+            }
+        };
+
+        for (element_type, element) in element_types.iter().zip(array.get_elements()) {
+            let element_type = self.infer(element, element_type.clone(), &first);
+            self.expect_type(&first, &element_type, element.get_span());
+        }
+
+        Some(Type::array(first?, element_types.len()))
+    }
+
+    // A constant index is checked against the length here, so the backends
+    // may leave out its runtime check.
+    fn type_of_index(&mut self, index: &Index) -> Option<Type> {
+        let base_type = self.type_of_node(index.get_base());
+        // any integer type indexes
+        let index_type = self.type_of_arithmetic(index.get_index());
+        if !matches!(index_type, Some(Type::Int(_))) {
+            self.expect_type(&Some(Type::Int(IntType::I64)), &index_type, index.get_index().get_span());
+        }
+
+        let (element, len) = match base_type? {
+            Type::Array(element, len) => (*element, len),
+            base_type => {
+                let error = Diagnostic::error("E0221", format!("cannot index into a value of type `{}`", base_type))
+                    .with_label(index.get_base().get_span(), "not an array".to_owned());
+                self.errors.push(error);
+                return None;
+            }
+        };
+
+        if let (Some(Type::Int(_)), Some(i)) = (&index_type, index.get_index().fold()) {
+            if i < 0 || i as u64 >= len as u64 {
+                let error = Diagnostic::error("E0222", format!("index out of bounds: the len is {} but the index is {}", len, i))
+                    .with_label(index.get_span(), "this index would abort at runtime".to_owned());
+                self.errors.push(error);
+            }
+        }
+
+        Some(element)
+    }
+
+    // Every field is initialized exactly once.
+    fn type_of_struct_literal(&mut self, literal: &StructLiteral) -> Option<Type> {
+        let struct_type = match self.structs.get(literal.get_name()) {
+            Some((struct_type, _)) => Some(struct_type.clone()),
+            None => {
+                self.errors.push(not_found("struct", literal.get_name(), literal.get_span()));
+                None
+            }
+        };
+
+        for (i, init) in literal.get_fields().iter().enumerate() {
+            let value_type = self.type_of_arithmetic(init.get_value());
+            let struct_type = match &struct_type {
+                Some(struct_type) => struct_type,
+                None => continue,
+            };
+
+            if let Some(first) = literal.get_fields()[..i].iter().find(|other| other.get_name() == init.get_name()) {
+                let error = Diagnostic::error("E0217", format!("field `{}` specified more than once", init.get_name()))
+                    .with_label(init.get_span(), "used more than once".to_owned())
+                    .with_secondary(first.get_span(), "first use".to_owned());
+                self.errors.push(error);
+                continue;
+            }
+            match struct_type.get_field(init.get_name()) {
+                Some(field) => {
+                    let field_type = Some(field.get_type().clone());
+                    let value_type = self.infer(init.get_value(), value_type, &field_type);
+                    self.expect_type(&field_type, &value_type, init.get_value().get_span());
+                }
+                None => {
+                    let error = Diagnostic::error("E0213", format!("struct `{}` has no field named `{}`", literal.get_name(), init.get_name()))
+                        .with_label(init.get_span(), "unknown field".to_owned());
+                    self.errors.push(error.with_note(fields_note(struct_type)));
+                }
+            }
+        }
+
+        let struct_type = struct_type?;
+        let missing: Vec<String> = struct_type.get_fields().iter()
+            .filter(|field| !literal.get_fields().iter().any(|init| init.get_name() == field.get_name()))
+            .map(|field| format!("`{}`", field.get_name()))
+            .collect();
+        if !missing.is_empty() {
+            let error = Diagnostic::error("E0214", format!("missing field{} {} in initializer of `{}`", plural(missing.len()), missing.join(", "), literal.get_name()))
+                .with_label(literal.get_span(), format!("missing {}", missing.join(", ")));
+            self.errors.push(error);
+        }
+
+        Some(Type::Struct(struct_type))
+    }
+
+    // A tuple is as well typed as its elements.
+    fn type_of_tuple(&mut self, tuple: &TupleLiteral) -> Option<Type> {
+        let element_types: Vec<Option<Type>> = tuple.get_elements().iter().map(|element| self.type_of_arithmetic(element)).collect();
+        let element_types = element_types.into_iter().collect::<Option<Vec<Type>>>()?;
+        Some(Type::Tuple(element_types))
+    }
+
+    // The fields of a tuple are numbered from 0, written without leading zeros.
+    fn type_of_field(&mut self, field: &FieldAccess) -> Option<Type> {
+        let base_type = self.type_of_node(field.get_base())?;
+        match &base_type {
+            Type::Struct(struct_type) => {
+                if let Some(found) = struct_type.get_field(field.get_name()) {
+                    return Some(found.get_type().clone());
+                }
+            }
+            Type::Tuple(elements) => {
+                let index = field.get_name().parse::<usize>().ok().filter(|index| index.to_string() == field.get_name());
+                if let Some(element) = index.and_then(|index| elements.get(index)) {
+                    return Some(element.clone());
+                }
+            }
+            _ => {}
+        }
+
+        let mut error = Diagnostic::error("E0215", format!("no field `{}` on type `{}`", field.get_name(), base_type))
+            .with_label(field.get_span(), "unknown field".to_owned());
+        match &base_type {
+            Type::Struct(struct_type) => {
+                error = error.with_note(fields_note(struct_type));
+            }
+            Type::Tuple(elements) => {
+                error = error.with_note(format!("the fields of `{}` are `0` to `{}`", base_type, elements.len() - 1));
+            }
+            _ => {}
+        }
+        self.errors.push(error);
+
+        None
+    }
+
+    // Each field of the variant gets a value, like the arguments of a call.
+    fn type_of_variant(&mut self, literal: &VariantLiteral) -> Option<Type> {
+        let arg_types: Vec<Option<Type>> = literal.get_args().iter().map(|arg| self.type_of_arithmetic(arg)).collect();
+        let enum_type = self.find_enum(literal.get_enum_name(), literal.get_span())?;
+        let fields = match enum_type.get_variant(literal.get_name()) {
+            Some((_, variant)) => variant.get_fields().to_vec(),
+            None => {
+                self.errors.push(no_variant(&enum_type, literal.get_name(), literal.get_span()));
+                return None;
+            }
+        };
+
+        if fields.len() != arg_types.len() {
+            let path = format!("{}::{}", literal.get_enum_name(), literal.get_name());
+            let error = Diagnostic::error("E0203", format!("variant `{}` takes {} field{} but {} {} supplied",
+                                          path, fields.len(), plural(fields.len()), arg_types.len(), were(arg_types.len())))
+                .with_label(literal.get_span(), format!("expected {} field{}", fields.len(), plural(fields.len())));
+            self.errors.push(error);
+            return Some(Type::Enum(enum_type));
+        }
+
+        for ((field, arg_type), arg) in fields.into_iter().zip(arg_types).zip(literal.get_args()) {
+            let field = Some(field);
+            let arg_type = self.infer(arg, arg_type, &field);
+            self.expect_type(&field, &arg_type, arg.get_span());
+        }
+
+        Some(Type::Enum(enum_type))
+    }
+
+    // Every arm has the type of the first one that finishes. The bindings of
+    // a pattern are in scope in its guard and body.
+    fn type_of_match(&mut self, m: &Match) -> Option<Type> {
+        let value_type = self.type_of_arithmetic(m.get_value());
+
+        let mut body_types = Vec::new();
+        for arm in m.get_arms() {
+            let scope = self.variables.len();
+            self.check_pattern(arm.get_pattern(), &value_type, &mut Vec::new());
+            if let Some(guard) = arm.get_guard() {
+                self.check_condition(guard);
+            }
+            body_types.push(self.check_block(arm.get_body()));
+            self.variables.truncate(scope);
+        }
+
+        // untyped arms take the type of the first arm of an integer type
+        let typed = m.get_arms().iter().zip(body_types.iter())
+            .find(|(arm, body_type)| !arm.get_body().get_value().is_some_and(is_untyped) && matches!(body_type, Some(Type::Int(_))))
+            .and_then(|(_, body_type)| body_type.clone());
+        if typed.is_some() {
+            for (arm, body_type) in m.get_arms().iter().zip(body_types.iter_mut()) {
+                *body_type = self.infer_value(arm.get_body(), body_type.take(), &typed);
+            }
+        }
+
+        let mut first: Option<(Type, Span)> = None;
+        let mut incompatible = false;
+        for (arm, body_type) in m.get_arms().iter().zip(body_types) {
+            let (body_type, span) = match body_type {
+                Some(body_type) => (body_type, block_span(arm.get_body(), arm.get_span())),
+                None => continue,
+            };
+            match &first {
+                None => first = Some((body_type, span)),
+                Some((first_type, first_span)) if *first_type != body_type => {
+                    let error = Diagnostic::error("E0206", "`match` arms have incompatible types".to_owned())
+                        .with_label(span, format!("expected `{}`, found `{}`", first_type, body_type))
+                        .with_secondary(*first_span, format!("this is found to be of type `{}`", first_type));
+                    self.errors.push(error);
+                    incompatible = true;
+                }
+                Some(_) => {}
+            }
+        }
+
+        if let Some(value_type) = &value_type {
+            self.check_usefulness(m, value_type);
+        }
+
+        if incompatible {
+            return None;
+        }
+        first.map(|(match_type, _)| match_type)
+    }
+
+    // Declares the bindings of pattern, which matches values of expected_type.
+    // bound are the names bound so far by the whole pattern.
+    fn check_pattern(&mut self, pattern: &Pattern, expected_type: &Option<Type>, bound: &mut Vec<(String, Span)>) {
+        match pattern {
+            Pattern::Wildcard(_) => {}
+            Pattern::Binding(name, span) => {
+                if let Some((_, first)) = bound.iter().find(|(other, _)| other == name) {
+                    let error = Diagnostic::error("E0225", format!("identifier `{}` is bound more than once in the same pattern", name))
+                        .with_label(*span, "used in a pattern more than once".to_owned())
+                        .with_secondary(*first, "first binding".to_owned());
+                    self.errors.push(error);
+                }
+                bound.push((name.clone(), *span));
+                self.variables.push(Symbol {
+                    name: name.clone(),
+                    var_type: expected_type.clone(),
+                    mutable: false,
+                    span: Some(*span),
+                });
+            }
+            Pattern::Number(number) => {
+                // without a suffix, of the integer type expected
+                let int_type = match (number.get_suffix(), expected_type) {
+                    (Some(suffix), _) => suffix,
+                    (None, Some(Type::Int(int_type))) => *int_type,
+                    (None, _) => IntType::I64,
+                };
+                self.expect_type(expected_type, &Some(Type::Int(int_type)), number.get_span());
+                if !number.get_value().is_some_and(|value| int_type.contains(value)) {
+                    let error = Diagnostic::error("E0210", format!("literal out of range for `{}`", int_type))
+                        .with_label(number.get_span(), format!("does not fit into `{}`", int_type))
+                        .with_note(format!("the range of `{}` is `{}..={}`", int_type, int_type.min(), int_type.max()));
+                    self.errors.push(error);
+                }
+            }
+            Pattern::Boolean(b) => {
+                self.expect_type(expected_type, &Some(Type::Bool), b.get_span());
+            }
+            Pattern::Char(c) => {
+                self.expect_type(expected_type, &Some(Type::Char), c.get_span());
+            }
+            Pattern::Variant(variant) => {
+                // the fields are still checked after an error, for the errors in them
+                let mut field_types = vec![None; variant.get_fields().len()];
+                if let Some(enum_type) = self.find_enum(variant.get_enum_name(), variant.get_span()) {
+                    self.expect_type(expected_type, &Some(Type::Enum(enum_type.clone())), variant.get_span());
+                    match enum_type.get_variant(variant.get_name()) {
+                        Some((_, found)) if found.get_fields().len() == variant.get_fields().len() => {
+                            field_types = found.get_fields().iter().cloned().map(Some).collect();
+                        }
+                        Some((_, found)) => {
+                            let (expected, actual) = (found.get_fields().len(), variant.get_fields().len());
+                            let error = Diagnostic::error("E0226", format!("this pattern has {} field{}, but the corresponding variant has {} field{}",
+                                                                           actual, plural(actual), expected, plural(expected)))
+                                .with_label(variant.get_span(), format!("expected {} field{}, found {}", expected, plural(expected), actual));
+                            self.errors.push(error);
+                        }
+                        None => {
+                            self.errors.push(no_variant(&enum_type, variant.get_name(), variant.get_span()));
+                        }
+                    }
+                }
+
+                for (field, field_type) in variant.get_fields().iter().zip(field_types.iter()) {
+                    self.check_pattern(field, field_type, bound);
+                }
+            }
+            Pattern::Tuple(tuple) => {
+                let mut field_types = vec![None; tuple.get_fields().len()];
+                match expected_type {
+                    Some(Type::Tuple(elements)) if elements.len() == tuple.get_fields().len() => {
+                        field_types = elements.iter().cloned().map(Some).collect();
+                    }
+                    Some(Type::Tuple(elements)) => {
+                        let (expected, actual) = (elements.len(), tuple.get_fields().len());
+                        let error = Diagnostic::error("E0226", format!("this pattern has {} field{}, but the tuple has {} field{}",
+                                                                       actual, plural(actual), expected, plural(expected)))
+                            .with_label(tuple.get_span(), format!("expected {} field{}, found {}", expected, plural(expected), actual));
+                        self.errors.push(error);
+                    }
+                    Some(expected) => {
+                        let error = Diagnostic::error("E0206", "mismatched types".to_owned())
+                            .with_label(tuple.get_span(), format!("expected `{}`, found a tuple", expected));
+                        self.errors.push(error);
+                    }
+                    None => {}
+                }
+
+                for (field, field_type) in tuple.get_fields().iter().zip(field_types.iter()) {
+                    self.check_pattern(field, field_type, bound);
+                }
+            }
+        }
+    }
+
+    // A match has to cover every value of its type, and an arm that only matches values
+    // the arms before it do is probably a mistake. See usefulness for how this is found.
+    fn check_usefulness(&mut self, m: &Match, value_type: &Type) {
+        let arms: Vec<(&Pattern, bool)> = m.get_arms().iter().map(|arm| (arm.get_pattern(), arm.get_guard().is_some())).collect();
+        // a pattern that does not fit has already been reported
+        let usefulness = match usefulness::analyze(&arms, value_type) {
+            Some(usefulness) => usefulness,
+            None => return,
+        };
+
+        for &i in usefulness.get_unreachable() {
+            let mut warning = Diagnostic::warning("W0001", "unreachable pattern".to_owned())
+                .with_label(m.get_arms()[i].get_pattern().get_span(), "no value can reach this".to_owned());
+            let catch_all = m.get_arms()[..i].iter().find(|arm| arm.get_guard().is_none() && arm.get_pattern().is_irrefutable());
+            if let Some(catch_all) = catch_all {
+                warning = warning.with_secondary(catch_all.get_pattern().get_span(), "matches any value".to_owned());
+            }
+            self.warnings.push(warning);
+        }
+
+        let missing = usefulness.get_missing();
+        if missing.is_empty() {
+            return;
+        }
+
+        let (list, noun) = (pattern_list(missing), if missing.len() == 1 { "pattern" } else { "patterns" });
+        let fix = match missing {
+            [only] if only == "_" => "add a `_` arm",
+            [_] => "add an arm for it, or a `_` arm",
+            _ => "add an arm for each of them, or a `_` arm",
+        };
+        let mut error = Diagnostic::error("E0227", format!("non-exhaustive patterns: {} not covered", list))
+            .with_label(m.get_value().get_span(), format!("{} {} not covered", noun, list))
+            .with_help(fix.to_owned());
+        if m.get_arms().iter().any(|arm| arm.get_guard().is_some()) {
+            error = error.with_note("arms with a guard do not count, since the guard may be false".to_owned());
+        }
+        self.errors.push(error);
+    }
+
+    fn type_of_call(&mut self, call: &Call) -> Option<Type> {
+        let arg_types: Vec<Option<Type>> = call.get_args().iter().map(|arg| self.type_of_arithmetic(arg)).collect();
+        if let Some(builtin) = Builtin::from_name(call.get_name()) {
+            return self.type_of_builtin(builtin, call, &arg_types);
+        }
+
+        let (params, return_type, definition) = match self.functions.get(call.get_name()) {
+            Some(signature) => (signature.params.clone(), signature.return_type.clone(), signature.span),
+            None => {
+                self.errors.push(not_found("function", call.get_name(), call.get_span()));
+                return None;
+            }
+        };
+
+        if params.len() != arg_types.len() {
+            let error = Diagnostic::error("E0203", format!("function `{}` takes {} argument{} but {} {} supplied",
+                                          call.get_name(), params.len(), plural(params.len()), arg_types.len(), were(arg_types.len())))
+                .with_label(call.get_span(), format!("expected {} argument{}", params.len(), plural(params.len())));
+            self.errors.push(declared_at(error, definition, "defined here", &format!("`{}` is defined in an earlier input", call.get_name())));
+            return return_type;
+        }
+
+        for ((param, arg_type), arg) in params.iter().zip(arg_types).zip(call.get_args()) {
+            let arg_type = self.infer(arg, arg_type, param);
+            self.expect_type(param, &arg_type, arg.get_span());
+        }
+
+        return_type
+    }
+
+    // print and println take values of any type that has a text form, len an array or a str.
+    fn type_of_builtin(&mut self, builtin: Builtin, call: &Call, arg_types: &[Option<Type>]) -> Option<Type> {
+        let arity = builtin.get_arity();
+        if !arity.contains(&arg_types.len()) {
+            let takes = if arity.start() == arity.end() {
+                format!("{} argument{}", arity.start(), plural(*arity.start()))
+            } else {
+                format!("{} or {} arguments", arity.start(), arity.end())
+            };
+            let error = Diagnostic::error("E0203", format!("function `{}` takes {} but {} {} supplied",
+                                          builtin.get_name(), takes, arg_types.len(), were(arg_types.len())))
+                .with_label(call.get_span(), format!("expected {}", takes))
+                .with_note(format!("`{}` is a builtin function", builtin.get_name()));
+            self.errors.push(error);
+        }
+
+        for (arg_type, arg) in arg_types.iter().zip(call.get_args()) {
+            match (builtin, arg_type) {
+                (_, None) => {}
+                (Builtin::Print | Builtin::Println, Some(Type::Int(_) | Type::Bool | Type::Char | Type::Str)) => {}
+                (Builtin::Print | Builtin::Println, Some(arg_type)) => {
+                    let mut error = Diagnostic::error("E0219", format!("`{}` cannot be printed", arg_type))
+                        .with_label(arg.get_span(), format!("`{}` has no text form", arg_type))
+                        .with_note("only integers and `bool`, `char` and `str` values can be printed".to_owned());
+                    if let Type::Struct(_) | Type::Array(_, _) | Type::Tuple(_) = arg_type {
+                        error = error.with_help("print the parts one by one".to_owned());
+                    }
+                    self.errors.push(error);
+                }
+                (Builtin::Len, Some(Type::Array(_, _) | Type::Str)) => {}
+                (Builtin::Len, Some(arg_type)) => {
+                    let error = Diagnostic::error("E0223", format!("`{}` has no length", arg_type))
+                        .with_label(arg.get_span(), format!("expected an array or a `str`, found `{}`", arg_type));
+                    self.errors.push(error);
+                }
+            }
+        }
+
+        match builtin {
+            Builtin::Print | Builtin::Println => Some(Type::Unit),
+            Builtin::Len => Some(Type::Int(IntType::I64)),
+        }
+    }
+
+    fn resolve_type(&mut self, type_name: &TypeName) -> Option<Type> {
+        let name = match type_name {
+            TypeName::Named(name, _) => name,
+            TypeName::Array(element, len, _) => return Some(Type::array(self.resolve_type(element)?, *len)),
+            TypeName::Tuple(elements, _) if elements.is_empty() => return Some(Type::Unit),
+            TypeName::Tuple(elements, _) => {
+                let element_types: Vec<Option<Type>> = elements.iter().map(|element| self.resolve_type(element)).collect();
+                return Some(Type::Tuple(element_types.into_iter().collect::<Option<Vec<Type>>>()?));
+            }
+        };
+        if let Some(primitive) = Type::primitive(name) {
+            return Some(primitive);
+        }
+
+        if let Some((enum_type, _)) = self.enums.get(name) {
+            return Some(Type::Enum(enum_type.clone()));
+        }
+        match self.structs.get(name) {
+            Some((struct_type, _)) => Some(Type::Struct(struct_type.clone())),
+            None => {
+                self.errors.push(not_found("type", name, type_name.get_span()));
+                None
+            }
+        }
+    }
+
+    fn find_enum(&mut self, name: &str, span: Span) -> Option<Rc<EnumType>> {
+        match self.enums.get(name) {
+            Some((enum_type, _)) => Some(enum_type.clone()),
+            None => {
+                self.errors.push(not_found("enum", name, span));
+                None
+            }
+        }
+    }
+
+    fn expect_type(&mut self, expected: &Option<Type>, actual: &Option<Type>, span: Span) {
+        if let Some(error) = mismatch(expected, actual, span) {
+            self.errors.push(error);
+        }
+    }
+
+    // The latest declaration wins, which is what shadowing needs.
+    fn find_variable(&self, name: &str) -> Option<&Symbol> {
+        self.variables.iter().rev().find(|symbol| symbol.name == name)
+    }
+}
+
+impl TypeDecl<'_> {
+    fn get_name(&self) -> &str {
+        match self {
+            TypeDecl::Struct(decl) => decl.get_name(),
+            TypeDecl::Enum(decl) => decl.get_name(),
+        }
+    }
+
+    fn get_span(&self) -> Span {
+        match self {
+            TypeDecl::Struct(decl) => decl.get_span(),
+            TypeDecl::Enum(decl) => decl.get_span(),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            TypeDecl::Struct(_) => "struct",
+            TypeDecl::Enum(_) => "enum",
+        }
+    }
+}
+
+// An integer literal without a suffix, or arithmetic made only of such literals.
+fn is_untyped(arithmetic: &Arithmetic) -> bool {
+    match arithmetic {
+        Arithmetic::Term(node) => is_untyped_node(node),
+        Arithmetic::MultiTerm(left, op, right) => {
+            matches!(op, Operator::Plus | Operator::Minus | Operator::Mul | Operator::Div | Operator::Rem)
+                && is_untyped_node(left) && is_untyped_node(right)
+        }
+    }
+}
+
+fn is_untyped_node(node: &Node) -> bool {
+    match node {
+        Node::Number(number) => number.get_suffix().is_none(),
+        Node::Unary(unary) => matches!(unary.get_op(), UnaryOperator::Negate) && is_untyped_node(unary.get_operand()),
+        Node::Arithmetic(arithmetic) => is_untyped(arithmetic),
+        _ => false,
+    }
+}
+
+// block never finishes: it ends by returning or leaving a loop, or with an if
+// none of whose branches finish.
+fn diverges(block: &Block) -> bool {
+    if let Some(value) = block.get_value() {
+        return arithmetic_diverges(value);
+    }
+
+    match block.get_statements().last() {
+        Some(Statement::Return(_)) | Some(Statement::Break(_)) | Some(Statement::Continue(_)) => true,
+        Some(Statement::Arithmetic(arithmetic)) => arithmetic_diverges(arithmetic),
+        _ => false,
+    }
+}
+
+fn arithmetic_diverges(arithmetic: &Arithmetic) -> bool {
+    match arithmetic {
+        Arithmetic::Term(Node::If(i)) => match i.get_otherwise() {
+            Some(otherwise) => diverges(i.get_then()) && diverges(otherwise),
+            None => false,
+        },
+        Arithmetic::Term(Node::Match(m)) => m.get_arms().iter().all(|arm| diverges(arm.get_body())),
+        _ => false,
+    }
+}
+
+// Where errors about the value of block point: the value, or the last statement.
+fn block_span(block: &Block, default: Span) -> Span {
+    match (block.get_value(), block.get_statements().last()) {
+        (Some(value), _) => value.get_span(),
+        (None, Some(Statement::Arithmetic(arithmetic))) => arithmetic.get_span(),
+        _ => default,
+    }
+}
+
+// error with a secondary label at a declaration, or with a note when that is in an earlier input.
+fn declared_at(error: Diagnostic, span: Option<Span>, label: &str, note: &str) -> Diagnostic {
+    match span {
+        Some(span) => error.with_secondary(span, label.to_owned()),
+        None => error.with_note(note.to_owned()),
+    }
+}
+
+fn mismatch(expected: &Option<Type>, actual: &Option<Type>, span: Span) -> Option<Diagnostic> {
+    match (expected, actual) {
+        (Some(expected), Some(actual)) if expected != actual => {
+            let error = Diagnostic::error("E0206", "mismatched types".to_owned())
+                .with_label(span, format!("expected `{}`, found `{}`", expected, actual));
+            Some(error)
+        }
+        _ => None,
+    }
+}
+
+// The fields of struct_type, for errors about a field it does not have.
+fn fields_note(struct_type: &StructType) -> String {
+    let fields: Vec<String> = struct_type.get_fields().iter().map(|field| format!("`{}`", field.get_name())).collect();
+    if fields.is_empty() {
+        return format!("`{}` has no fields", struct_type.get_name());
+    }
+
+    format!("available fields are: {}", fields.join(", "))
+}
+
+// The variants of enum_type, for errors about a variant it does not have.
+fn no_variant(enum_type: &EnumType, name: &str, span: Span) -> Diagnostic {
+    let variants: Vec<String> = enum_type.get_variants().iter().map(|variant| format!("`{}`", variant.get_name())).collect();
+    let note = if variants.is_empty() {
+        format!("`{}` has no variants", enum_type.get_name())
+    } else {
+        format!("available variants are: {}", variants.join(", "))
+    };
+
+    Diagnostic::error("E0224", format!("no variant named `{}` in enum `{}`", name, enum_type.get_name()))
+        .with_label(span, format!("variant not found in `{}`", enum_type.get_name()))
+        .with_note(note)
+}
+
+// Like `a`, `b` and `c`, naming at most three patterns.
+fn pattern_list(patterns: &[String]) -> String {
+    let quoted: Vec<String> = patterns.iter().take(3).map(|pattern| format!("`{}`", pattern)).collect();
+    match quoted.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) if patterns.len() <= 3 => format!("{} and {}", rest.join(", "), last),
+        Some(_) => format!("{} and {} more", quoted.join(", "), patterns.len() - 3),
+        None => String::new(),
+    }
+}
+
+fn plural(n: usize) -> &'static str {
+    if n == 1 { "" } else { "s" }
+}
+
+fn were(n: usize) -> &'static str {
+    if n == 1 { "was" } else { "were" }
+}
+
+// The source text of a place, like `p.x` or `a[_]` for any element of a.
+fn place_name(place: &Node) -> String {
+    match place {
+        Node::Variable(variable) => variable.get_name().to_owned(),
+        Node::Field(field) => format!("{}.{}", place_name(field.get_base()), field.get_name()),
+        Node::Index(index) => format!("{}[_]", place_name(index.get_base())),
+        _ => unreachable!("places are variables and their fields and elements"),
+    }
+}
+
+fn not_found(kind: &str, name: &str, span: Span) -> Diagnostic {
+    let code = match kind {
+        "type" | "struct" | "enum" => "E0201",
+        "function" => "E0202",
+        _ => "E0204",
+    };
+
+    Diagnostic::error(code, format!("cannot find {} `{}` in this scope", kind, name))
+        .with_label(span, "not found in this scope".to_owned())
+}
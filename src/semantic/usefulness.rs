@@ -0,0 +1,271 @@
+use crate::ast::Pattern;
+use crate::types::{ Type, tuple };
+
+// What the arms of a match cover, by the usefulness of their patterns as in
+// Maranget's "Warnings for pattern matching": a row of patterns is useful after
+// some rows when a value matches it and none of them. A match is exhaustive when
+// `_` is not useful after its arms, and an arm is unreachable when its pattern is
+// not useful after the arms before it.
+#[derive(Debug)]
+pub struct Usefulness {
+    // patterns for the values no arm matches, like `Shape::Rect(_, _)`
+    missing: Vec<String>,
+    // indexes of the arms whose values all match an arm before them
+    unreachable: Vec<usize>,
+}
+
+// What a pattern is made of, as far as the values it matches go.
+#[derive(Debug, Clone, PartialEq)]
+enum Constructor {
+    Bool(bool),
+    Int(i128),
+    Char(char),
+    // the tag of a variant
+    Variant(usize),
+    // the only constructor of a tuple type
+    Tuple,
+}
+
+// A pattern reduced to what decides the values it matches.
+#[derive(Debug, Clone)]
+enum Pat {
+    // _ or a binding
+    Wild,
+    Constructed(Constructor, Vec<Pat>),
+}
+
+impl Usefulness {
+    pub fn get_missing(&self) -> &[String] {
+        &self.missing
+    }
+
+    pub fn get_unreachable(&self) -> &[usize] {
+        &self.unreachable
+    }
+}
+
+// The usefulness of the patterns of the arms, each with whether it has a guard, which
+// may be false, so a guarded arm covers nothing. None when a pattern does not fit
+// value_type, which the checks of the patterns have already reported.
+pub fn analyze(arms: &[(&Pattern, bool)], value_type: &Type) -> Option<Usefulness> {
+    let patterns = arms.iter().map(|(pattern, _)| reduce(pattern, value_type)).collect::<Option<Vec<Pat>>>()?;
+    let types = [value_type.clone()];
+
+    let mut rows: Vec<Vec<Pat>> = Vec::new();
+    let mut unreachable = Vec::new();
+    for (i, (pattern, (_, guarded))) in patterns.into_iter().zip(arms).enumerate() {
+        let row = vec![pattern];
+        if !is_useful(&rows, &row, &types) {
+            unreachable.push(i);
+        }
+        if !guarded {
+            rows.push(row);
+        }
+    }
+
+    let missing = witnesses(&rows, &types).iter().map(|witness| display(&witness[0], value_type)).collect();
+    Some(Usefulness {
+        missing,
+        unreachable,
+    })
+}
+
+fn reduce(pattern: &Pattern, value_type: &Type) -> Option<Pat> {
+    match (pattern, value_type) {
+        (Pattern::Wildcard(_) | Pattern::Binding(_, _), _) => Some(Pat::Wild),
+        (Pattern::Number(number), Type::Int(_)) => {
+            let n = number.get_value()?;
+            Some(Pat::Constructed(Constructor::Int(n), Vec::new()))
+        }
+        (Pattern::Char(c), Type::Char) => Some(Pat::Constructed(Constructor::Char(c.get_value()), Vec::new())),
+        (Pattern::Boolean(b), Type::Bool) => Some(Pat::Constructed(Constructor::Bool(b.get_value()), Vec::new())),
+        (Pattern::Variant(variant), Type::Enum(enum_type)) if variant.get_enum_name() == enum_type.get_name() => {
+            let (tag, found) = enum_type.get_variant(variant.get_name())?;
+            if found.get_fields().len() != variant.get_fields().len() {
+                return None;
+            }
+            let fields = variant.get_fields().iter().zip(found.get_fields())
+                .map(|(field, field_type)| reduce(field, field_type))
+                .collect::<Option<Vec<Pat>>>()?;
+            Some(Pat::Constructed(Constructor::Variant(tag), fields))
+        }
+        (Pattern::Tuple(pattern), Type::Tuple(elements)) if pattern.get_fields().len() == elements.len() => {
+            let fields = pattern.get_fields().iter().zip(elements)
+                .map(|(field, element)| reduce(field, element))
+                .collect::<Option<Vec<Pat>>>()?;
+            Some(Pat::Constructed(Constructor::Tuple, fields))
+        }
+        _ => None,
+    }
+}
+
+// Every constructor of value_type, None when there are too many to list, like for ints and chars,
+// or none to match on, like for structs.
+fn all_constructors(value_type: &Type) -> Option<Vec<Constructor>> {
+    match value_type {
+        Type::Bool => Some(vec![Constructor::Bool(true), Constructor::Bool(false)]),
+        Type::Enum(enum_type) => Some((0..enum_type.get_variants().len()).map(Constructor::Variant).collect()),
+        Type::Tuple(_) => Some(vec![Constructor::Tuple]),
+        _ => None,
+    }
+}
+
+fn field_types(constructor: &Constructor, value_type: &Type) -> Vec<Type> {
+    match (constructor, value_type) {
+        (Constructor::Variant(tag), Type::Enum(enum_type)) => enum_type.get_variants()[*tag].get_fields().to_vec(),
+        (Constructor::Tuple, Type::Tuple(elements)) => elements.clone(),
+        _ => Vec::new(),
+    }
+}
+
+// The types of the columns once the first is replaced by the fields of constructor.
+fn expand(constructor: &Constructor, types: &[Type]) -> Vec<Type> {
+    let mut expanded = field_types(constructor, &types[0]);
+    expanded.extend_from_slice(&types[1..]);
+    expanded
+}
+
+// The distinct constructors the rows start with.
+fn heads(rows: &[Vec<Pat>]) -> Vec<Constructor> {
+    let mut heads = Vec::new();
+    for row in rows {
+        if let Pat::Constructed(constructor, _) = &row[0] {
+            if !heads.contains(constructor) {
+                heads.push(constructor.clone());
+            }
+        }
+    }
+
+    heads
+}
+
+// The rows matching values made by constructor, with its fields in place of the first column.
+fn specialize(rows: &[Vec<Pat>], constructor: &Constructor, arity: usize) -> Vec<Vec<Pat>> {
+    let mut specialized = Vec::new();
+    for row in rows {
+        let mut fields = match &row[0] {
+            Pat::Wild => vec![Pat::Wild; arity],
+            Pat::Constructed(head, fields) if head == constructor => fields.clone(),
+            Pat::Constructed(_, _) => continue,
+        };
+        fields.extend_from_slice(&row[1..]);
+        specialized.push(fields);
+    }
+
+    specialized
+}
+
+// The rows matching any value in the first column, without it.
+fn default(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    rows.iter().filter(|row| matches!(row[0], Pat::Wild)).map(|row| row[1..].to_vec()).collect()
+}
+
+// Whether some values match row and none of rows. types are the types of the columns.
+fn is_useful(rows: &[Vec<Pat>], row: &[Pat], types: &[Type]) -> bool {
+    let (head, rest) = match row.split_first() {
+        Some(split) => split,
+        None => return rows.is_empty(),
+    };
+
+    match head {
+        Pat::Constructed(constructor, fields) => {
+            let mut next = fields.clone();
+            next.extend_from_slice(rest);
+            is_useful(&specialize(rows, constructor, fields.len()), &next, &expand(constructor, types))
+        }
+        Pat::Wild => {
+            let heads = heads(rows);
+            match all_constructors(&types[0]) {
+                // the rows start with every constructor, so _ is useful when it is for one of them
+                Some(all) if all.iter().all(|constructor| heads.contains(constructor)) => all.iter().any(|constructor| {
+                    let arity = field_types(constructor, &types[0]).len();
+                    let mut next = vec![Pat::Wild; arity];
+                    next.extend_from_slice(rest);
+                    is_useful(&specialize(rows, constructor, arity), &next, &expand(constructor, types))
+                }),
+                // a constructor none of them starts with only matches the rows starting with _
+                _ => is_useful(&default(rows), rest, &types[1..]),
+            }
+        }
+    }
+}
+
+// Rows of patterns for the values no row matches, the same as is_useful of a row of _
+// but with an example of each kind of value it is useful for.
+fn witnesses(rows: &[Vec<Pat>], types: &[Type]) -> Vec<Vec<Pat>> {
+    let value_type = match types.first() {
+        Some(value_type) => value_type,
+        None if rows.is_empty() => return vec![Vec::new()],
+        None => return Vec::new(),
+    };
+
+    let heads = heads(rows);
+    let mut found = Vec::new();
+    let firsts = match all_constructors(value_type) {
+        Some(all) => {
+            // a value made by a constructor some rows start with is missing in its fields, or further on
+            for constructor in all.iter().filter(|constructor| heads.contains(constructor)) {
+                let arity = field_types(constructor, value_type).len();
+                for mut witness in witnesses(&specialize(rows, constructor, arity), &expand(constructor, types)) {
+                    let rest = witness.split_off(arity);
+                    let mut row = vec![Pat::Constructed(constructor.clone(), witness)];
+                    row.extend(rest);
+                    found.push(row);
+                }
+            }
+
+            let others: Vec<Constructor> = all.into_iter().filter(|constructor| !heads.contains(constructor)).collect();
+            if others.is_empty() {
+                return found;
+            } else if heads.is_empty() {
+                vec![Pat::Wild]
+            } else {
+                others.into_iter().map(|constructor| {
+                    let arity = field_types(&constructor, value_type).len();
+                    Pat::Constructed(constructor, vec![Pat::Wild; arity])
+                }).collect()
+            }
+        }
+        None => vec![Pat::Wild],
+    };
+
+    // a value made by any other constructor only matches the rows starting with _
+    for witness in witnesses(&default(rows), &types[1..]) {
+        for first in firsts.iter() {
+            let mut row = vec![first.clone()];
+            row.extend(witness.iter().cloned());
+            found.push(row);
+        }
+    }
+
+    found
+}
+
+// The source text of a pattern, like `Shape::Rect(_, 0)`.
+fn display(pattern: &Pat, value_type: &Type) -> String {
+    match pattern {
+        Pat::Wild => "_".to_owned(),
+        Pat::Constructed(Constructor::Bool(b), _) => b.to_string(),
+        Pat::Constructed(Constructor::Int(n), _) => n.to_string(),
+        Pat::Constructed(Constructor::Char(c), _) => format!("{:?}", c),
+        Pat::Constructed(Constructor::Variant(tag), fields) => {
+            let enum_type = match value_type {
+                Type::Enum(enum_type) => enum_type,
+                _ => unreachable!("variants are only made for enums"),
+            };
+            let variant = &enum_type.get_variants()[*tag];
+            if fields.is_empty() {
+                return format!("{}::{}", enum_type.get_name(), variant.get_name());
+            }
+
+            let fields: Vec<String> = fields.iter().zip(variant.get_fields()).map(|(field, field_type)| display(field, field_type)).collect();
+            format!("{}::{}({})", enum_type.get_name(), variant.get_name(), fields.join(", "))
+        }
+        Pat::Constructed(Constructor::Tuple, fields) => {
+            let fields: Vec<String> = fields.iter().zip(field_types(&Constructor::Tuple, value_type))
+                .map(|(field, field_type)| display(field, &field_type))
+                .collect();
+            tuple(&fields)
+        }
+    }
+}
@@ -1,9 +1,14 @@
 use std::cmp::PartialEq;
+use std::fmt;
+use super::diagnostic::Diagnostic;
+use super::types::IntType;
 
 #[derive(Debug)]
 pub struct Tokenizer {
     code: Vec<char>,
     len: usize,
+    // byte offset and position of every char, plus one for the end of the code
+    positions: Vec<(usize, Position)>,
 }
 
 #[derive(Debug)]
@@ -14,65 +19,151 @@ pub enum TokenizeState {
     Minus,
     Asterisk,
     Slash,
+    Percent,
     NewLine,
     LeftParenthesis,
     RightParenthesis,
     Semicolon,
+    Colon,
+    Comma,
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    Equal,
+    Bang,
+    Less,
+    Greater,
+    Ampersand,
+    Pipe,
+    Dot,
+    DotDot,
+    Str,
+    Char,
+    Identifier,
 }
 
 #[derive(Debug, Clone)]
 pub struct Token {
     t_type: TokenType,
-    pos: Position,
+    span: Span,
     inner: String,
 }
 
-#[derive(Debug, Clone)]
+// 1-based column (x) and line (y)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub struct Position {
     x: u32,
     y: u32,
 }
 
+// Byte range [start, end) of the source, and the position it starts at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    start: usize,
+    end: usize,
+    pos: Position,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenType {
     Plus,
     Minus,
     Asterisk,
     Slash,
+    Percent,
     Number,
     Dot,
+    DotDot,
+    DotDotEqual,
     Equal,
+    EqualEqual,
+    Bang,
+    BangEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    AndAnd,
+    OrOr,
     Str,
+    Char,
+    Comment,
     NewLine,
     LeftParenthesis,
     RightParenthesis,
     Semicolon,
+    Colon,
+    ColonColon,
+    Comma,
+    Arrow,
+    FatArrow,
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    Identifier,
+    Let,
+    Mut,
+    Fn,
+    Return,
+    If,
+    Else,
+    While,
+    Break,
+    Continue,
+    For,
+    In,
+    True,
+    False,
+    Struct,
+    Enum,
+    Match,
     EOF,
 }
 
 impl Tokenizer {
     pub fn new(code: Vec<char>) -> Tokenizer {
         let len = code.len();
+
+        let mut positions = Vec::with_capacity(len + 1);
+        let mut offset = 0usize;
+        let mut pos = Position::new(1u32, 1u32);
+        for c in code.iter() {
+            positions.push((offset, pos));
+            offset += c.len_utf8();
+            if *c == '\n' {
+                pos = Position::new(1u32, pos.y + 1);
+            } else {
+                pos = Position::new(pos.x + 1, pos.y);
+            }
+        }
+        positions.push((offset, pos));
+
         Tokenizer {
-            code: code,
-            len: len,
+            code,
+            len,
+            positions,
         }
     }
 
-    pub fn tokenize(&self) -> Result<Vec<Token>, String> {
+    pub fn tokenize(&self) -> Result<Vec<Token>, Vec<Diagnostic>> {
         let mut state  = TokenizeState::Normal;
-        let mut line = 1u32;
+        let mut start = 0usize;
         let mut tmp_contents = String::new();
         let mut tokens: Vec<Token> = Vec::new();
+        let mut errors: Vec<Diagnostic> = Vec::new();
 
         let code = &self.code;
         let mut  i = 0;
-        while i < self.len {
-            let c = code[i];
+        // one more round past the end, with '\0', flushes the last token
+        while i <= self.len {
+            let c = if i < self.len { code[i] } else { '\0' };
             match state {
                 TokenizeState::Normal => {
+                    start = i;
                     match c {
-                        '0'...'9' => {
+                        '0'..='9' => {
                             state = TokenizeState::Number;
                             tmp_contents.push(c);
                         }
@@ -92,6 +183,10 @@ impl Tokenizer {
                             state = TokenizeState::Slash;
                             tmp_contents.push(c);
                         }
+                        '%' => {
+                            state = TokenizeState::Percent;
+                            tmp_contents.push(c);
+                        }
                         '\n' => {
                             state = TokenizeState::NewLine;
                         }
@@ -104,137 +199,544 @@ impl Tokenizer {
                             tmp_contents.push(c);
                         }
                         ';' => {
-                            state =TokenizeState::Semicolon;
+                            state = TokenizeState::Semicolon;
+                            tmp_contents.push(c);
+                        }
+                        ':' => {
+                            state = TokenizeState::Colon;
+                            tmp_contents.push(c);
+                        }
+                        ',' => {
+                            state = TokenizeState::Comma;
+                            tmp_contents.push(c);
+                        }
+                        '{' => {
+                            state = TokenizeState::LeftBrace;
+                            tmp_contents.push(c);
+                        }
+                        '}' => {
+                            state = TokenizeState::RightBrace;
+                            tmp_contents.push(c);
+                        }
+                        '[' => {
+                            state = TokenizeState::LeftBracket;
+                            tmp_contents.push(c);
+                        }
+                        ']' => {
+                            state = TokenizeState::RightBracket;
                             tmp_contents.push(c);
                         }
-                        _ => {}
+                        '=' => {
+                            state = TokenizeState::Equal;
+                            tmp_contents.push(c);
+                        }
+                        '!' => {
+                            state = TokenizeState::Bang;
+                            tmp_contents.push(c);
+                        }
+                        '<' => {
+                            state = TokenizeState::Less;
+                            tmp_contents.push(c);
+                        }
+                        '>' => {
+                            state = TokenizeState::Greater;
+                            tmp_contents.push(c);
+                        }
+                        '&' => {
+                            state = TokenizeState::Ampersand;
+                            tmp_contents.push(c);
+                        }
+                        '|' => {
+                            state = TokenizeState::Pipe;
+                            tmp_contents.push(c);
+                        }
+                        '.' => {
+                            state = TokenizeState::Dot;
+                            tmp_contents.push(c);
+                        }
+                        '"' => {
+                            state = TokenizeState::Str;
+                        }
+                        '\'' => {
+                            state = TokenizeState::Char;
+                        }
+                        'a'..='z' | 'A'..='Z' | '_' => {
+                            state = TokenizeState::Identifier;
+                            tmp_contents.push(c);
+                        }
+                        ' ' | '\t' | '\r' | '\0' => {}
+                        _ => {
+                            let span = self.span(i, i + 1);
+                            let error = Diagnostic::error("E0001", format!("unknown character `{}`", c))
+                                .with_label(span, "not a valid token".to_owned());
+                            errors.push(error);
+                        }
                     }
                 }
                 TokenizeState::Number => {
+                    // the prefix, the digits and the suffix, taken apart once the whole is scanned
                     match c {
-                        '0'...'9' => {
+                        'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => {
                             tmp_contents.push(c);
                         }
                         _ => {
+                            errors.extend(self.check_number(start, &tmp_contents));
+                            tokens.push(self.create_token(TokenType::Number, start, i, &mut tmp_contents));
                             i -= 1;
-                            let token = Token::new(TokenType::Number, Position::new(0u32, line), tmp_contents.to_owned());
-                            tmp_contents.clear();
-                            tokens.push(token);
                             state = TokenizeState::Normal;
                         }
                     }
                 }
                 TokenizeState::Plus => {
+                    tokens.push(self.create_token(TokenType::Plus, start, i, &mut tmp_contents));
                     i -= 1;
-                    let token = Token::new(TokenType::Plus, Position::new(0u32, line), tmp_contents.to_owned());
-                    tmp_contents.clear();
-                    tokens.push(token);
                     state = TokenizeState::Normal;
                 }
                 TokenizeState::Minus => {
-                    i -= 1;
-                    let token = Token::new(TokenType::Minus,  Position::new(0u32, line), tmp_contents.to_owned());
-                    tmp_contents.clear();
-                    tokens.push(token);
-                    state = TokenizeState::Normal;
+                    match c {
+                        '>' => {
+                            tmp_contents.push(c);
+                            tokens.push(self.create_token(TokenType::Arrow, start, i + 1, &mut tmp_contents));
+                            state = TokenizeState::Normal;
+                        }
+                        _ => {
+                            tokens.push(self.create_token(TokenType::Minus, start, i, &mut tmp_contents));
+                            i -= 1;
+                            state = TokenizeState::Normal;
+                        }
+                    }
                 }
                 TokenizeState::Asterisk => {
+                    tokens.push(self.create_token(TokenType::Asterisk, start, i, &mut tmp_contents));
                     i -= 1;
-                    let token = Token::new(TokenType::Asterisk,  Position::new(0u32, line), tmp_contents.to_owned());
-                    tmp_contents.clear();
-                    tokens.push(token);
                     state = TokenizeState::Normal;
                 }
                 TokenizeState::Slash => {
                     match c {
                         '/' => {
-                            line += 1;
-                            while code[i] != '\n' && i < self.len {
+                            // a comment runs until the end of the line, which is tokenized as usual
+                            while i < self.len && code[i] != '\n' {
+                                tmp_contents.push(code[i]);
                                 i += 1;
                             }
-                            tmp_contents.clear();
+                            tokens.push(self.create_token(TokenType::Comment, start, i, &mut tmp_contents));
+                            i -= 1;
                             state = TokenizeState::Normal;
                         }
                         _ => {
+                            tokens.push(self.create_token(TokenType::Slash, start, i, &mut tmp_contents));
                             i -= 1;
-                            let token = Token::new(TokenType::Slash, Position::new(0u32, line), tmp_contents.to_owned());
-                            tmp_contents.clear();
-                            tokens.push(token);
                             state = TokenizeState::Normal;
                         }
                     }
                 }
+                TokenizeState::Percent => {
+                    tokens.push(self.create_token(TokenType::Percent, start, i, &mut tmp_contents));
+                    i -= 1;
+                    state = TokenizeState::Normal;
+                }
                 TokenizeState::NewLine => {
+                    tokens.push(self.create_token(TokenType::NewLine, start, i, &mut tmp_contents));
                     i -= 1;
-                    line += 1;
-                    let token = Token::new(TokenType::NewLine, Position::new(0u32, line), tmp_contents.to_owned());
-                    tmp_contents.clear();
-                    tokens.push(token);
                     state = TokenizeState::Normal;
                 }
                 TokenizeState::LeftParenthesis => {
+                    tokens.push(self.create_token(TokenType::LeftParenthesis, start, i, &mut tmp_contents));
                     i -= 1;
-                    let token = Token::new(TokenType::LeftParenthesis, Position::new(0u32, line), tmp_contents.to_owned());
-                    tmp_contents.clear();
-                    tokens.push(token);
                     state = TokenizeState::Normal;
                 }
                 TokenizeState::RightParenthesis => {
+                    tokens.push(self.create_token(TokenType::RightParenthesis, start, i, &mut tmp_contents));
                     i -= 1;
-                    let token = Token::new(TokenType::RightParenthesis, Position::new(0u32, line), tmp_contents.to_owned());
-                    tmp_contents.clear();
-                    tokens.push(token);
                     state = TokenizeState::Normal;
                 }
                 TokenizeState::Semicolon => {
+                    tokens.push(self.create_token(TokenType::Semicolon, start, i, &mut tmp_contents));
+                    i -= 1;
+                    state = TokenizeState::Normal;
+                }
+                TokenizeState::Colon => {
+                    match c {
+                        ':' => {
+                            tmp_contents.push(c);
+                            tokens.push(self.create_token(TokenType::ColonColon, start, i + 1, &mut tmp_contents));
+                        }
+                        _ => {
+                            tokens.push(self.create_token(TokenType::Colon, start, i, &mut tmp_contents));
+                            i -= 1;
+                        }
+                    }
+                    state = TokenizeState::Normal;
+                }
+                TokenizeState::Comma => {
+                    tokens.push(self.create_token(TokenType::Comma, start, i, &mut tmp_contents));
+                    i -= 1;
+                    state = TokenizeState::Normal;
+                }
+                TokenizeState::LeftBrace => {
+                    tokens.push(self.create_token(TokenType::LeftBrace, start, i, &mut tmp_contents));
                     i -= 1;
-                    let token = Token::new(TokenType::Semicolon, Position::new(0u32, line), tmp_contents.to_owned());
-                    tmp_contents.clear();
-                    tokens.push(token);
                     state = TokenizeState::Normal;
                 }
+                TokenizeState::RightBrace => {
+                    tokens.push(self.create_token(TokenType::RightBrace, start, i, &mut tmp_contents));
+                    i -= 1;
+                    state = TokenizeState::Normal;
+                }
+                TokenizeState::LeftBracket => {
+                    tokens.push(self.create_token(TokenType::LeftBracket, start, i, &mut tmp_contents));
+                    i -= 1;
+                    state = TokenizeState::Normal;
+                }
+                TokenizeState::RightBracket => {
+                    tokens.push(self.create_token(TokenType::RightBracket, start, i, &mut tmp_contents));
+                    i -= 1;
+                    state = TokenizeState::Normal;
+                }
+                TokenizeState::Equal => {
+                    match c {
+                        '>' => {
+                            tmp_contents.push(c);
+                            tokens.push(self.create_token(TokenType::FatArrow, start, i + 1, &mut tmp_contents));
+                        }
+                        _ => {
+                            self.push_operator(&mut tokens, c, TokenType::Equal, TokenType::EqualEqual, start, &mut i, &mut tmp_contents);
+                        }
+                    }
+                    state = TokenizeState::Normal;
+                }
+                TokenizeState::Bang => {
+                    self.push_operator(&mut tokens, c, TokenType::Bang, TokenType::BangEqual, start, &mut i, &mut tmp_contents);
+                    state = TokenizeState::Normal;
+                }
+                TokenizeState::Less => {
+                    self.push_operator(&mut tokens, c, TokenType::Less, TokenType::LessEqual, start, &mut i, &mut tmp_contents);
+                    state = TokenizeState::Normal;
+                }
+                TokenizeState::Greater => {
+                    self.push_operator(&mut tokens, c, TokenType::Greater, TokenType::GreaterEqual, start, &mut i, &mut tmp_contents);
+                    state = TokenizeState::Normal;
+                }
+                TokenizeState::Ampersand | TokenizeState::Pipe => {
+                    // only doubled, as && and ||
+                    let first = code[start];
+                    if c == first {
+                        tmp_contents.push(c);
+                        let t_type = if first == '&' { TokenType::AndAnd } else { TokenType::OrOr };
+                        tokens.push(self.create_token(t_type, start, i + 1, &mut tmp_contents));
+                    } else {
+                        let error = Diagnostic::error("E0001", format!("unknown character `{}`", first))
+                            .with_label(self.span(start, start + 1), "not a valid token".to_owned())
+                            .with_help(format!("use `{}{}` for the logical operator", first, first));
+                        errors.push(error);
+                        tmp_contents.clear();
+                        i -= 1;
+                    }
+                    state = TokenizeState::Normal;
+                }
+                TokenizeState::Dot => {
+                    if c == '.' {
+                        tmp_contents.push(c);
+                        state = TokenizeState::DotDot;
+                    } else {
+                        tokens.push(self.create_token(TokenType::Dot, start, i, &mut tmp_contents));
+                        i -= 1;
+                        state = TokenizeState::Normal;
+                    }
+                }
+                TokenizeState::DotDot => {
+                    self.push_operator(&mut tokens, c, TokenType::DotDot, TokenType::DotDotEqual, start, &mut i, &mut tmp_contents);
+                    state = TokenizeState::Normal;
+                }
+                TokenizeState::Str => {
+                    // the whole string at once, its contents unescaped
+                    if self.scan_string(start, &mut i, &mut tmp_contents, &mut errors) {
+                        tokens.push(self.create_token(TokenType::Str, start, i + 1, &mut tmp_contents));
+                    } else {
+                        tmp_contents.clear();
+                    }
+                    state = TokenizeState::Normal;
+                }
+                TokenizeState::Char => {
+                    // the whole literal at once, like a string
+                    if self.scan_char(start, &mut i, &mut tmp_contents, &mut errors) {
+                        tokens.push(self.create_token(TokenType::Char, start, i + 1, &mut tmp_contents));
+                    } else {
+                        tmp_contents.clear();
+                    }
+                    state = TokenizeState::Normal;
+                }
+                TokenizeState::Identifier => {
+                    match c {
+                        'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => {
+                            tmp_contents.push(c);
+                        }
+                        _ => {
+                            let t_type = keyword_or_identifier(&tmp_contents);
+                            tokens.push(self.create_token(t_type, start, i, &mut tmp_contents));
+                            i -= 1;
+                            state = TokenizeState::Normal;
+                        }
+                    }
+                }
             }
 
             i += 1;
         }
 
-        match state {
-            TokenizeState::Normal | TokenizeState::NewLine => {}
-            TokenizeState::Number => {
-                let token = Token::new(TokenType::Number, Position::new(0u32, line), tmp_contents.to_owned());
-                tokens.push(token);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        tokens.push(self.create_token(TokenType::EOF, self.len, self.len, &mut String::new()));
+
+        Ok(tokens)
+    }
+
+    // An operator, or the longer one if it goes on with `=`, like `<` and `<=`.
+    #[allow(clippy::too_many_arguments)]
+    fn push_operator(&self, tokens: &mut Vec<Token>, c: char, single: TokenType, with_equal: TokenType,
+                     start: usize, i: &mut usize, contents: &mut String) {
+        if c == '=' {
+            contents.push(c);
+            tokens.push(self.create_token(with_equal, start, *i + 1, contents));
+        } else {
+            tokens.push(self.create_token(single, start, *i, contents));
+            *i -= 1;
+        }
+    }
+
+    // Unescapes the string whose opening quote is at start into contents, from i on.
+    // Leaves i at the closing quote and returns whether there is one.
+    fn scan_string(&self, start: usize, i: &mut usize, contents: &mut String, errors: &mut Vec<Diagnostic>) -> bool {
+        while *i < self.len {
+            match self.code[*i] {
+                '"' => return true,
+                '\\' if *i + 1 < self.len => {
+                    match self.escape(i) {
+                        Ok(c) => contents.push(c),
+                        Err(error) => errors.push(error),
+                    }
+                }
+                c => contents.push(c),
             }
-            TokenizeState::Semicolon => {
-                let token = Token::new(TokenType::Semicolon, Position::new(0u32, line), tmp_contents.to_owned());
-                tokens.push(token);
+            *i += 1;
+        }
+
+        let error = Diagnostic::error("E0002", "unterminated double quote string".to_owned())
+            .with_label(self.span(start, start + 1), "the string starts here".to_owned());
+        errors.push(error);
+        false
+    }
+
+    // Unescapes the char literal whose opening quote is at start into contents, from i on.
+    // Leaves i at the closing quote and returns whether the literal is one char with one.
+    fn scan_char(&self, start: usize, i: &mut usize, contents: &mut String, errors: &mut Vec<Diagnostic>) -> bool {
+        match self.code.get(*i) {
+            Some('\'') => {
+                let error = Diagnostic::error("E0008", "empty character literal".to_owned())
+                    .with_label(self.span(start, *i + 1), "empty character literal".to_owned());
+                errors.push(error);
+                return false;
             }
-            _ => {
-                return Err(format!("{:?} Tokenize state is incorrect.", state));
+            Some('\\') if *i + 1 < self.len => {
+                match self.escape(i) {
+                    Ok(c) => contents.push(c),
+                    Err(error) => {
+                        errors.push(error);
+                        return false;
+                    }
+                }
             }
+            Some(c) if *c != '\n' => contents.push(*c),
+            _ => {}
+        }
+        *i += 1;
+        if self.code.get(*i) == Some(&'\'') {
+            return !contents.is_empty();
         }
 
-        let token = Token::new(TokenType::EOF, Position::new(0u32, line), String::new());
-        tokens.push(token);
+        // more chars before a closing quote on the same line are meant as a string
+        let mut end = *i;
+        while end < self.len && self.code[end] != '\'' && self.code[end] != '\n' {
+            end += 1;
+        }
+        let error = if end < self.len && self.code[end] == '\'' {
+            *i = end;
+            Diagnostic::error("E0008", "character literal may only contain one codepoint".to_owned())
+                .with_label(self.span(start, end + 1), "more than one char".to_owned())
+                .with_help("write a string literal with double quotes".to_owned())
+        } else {
+            *i -= 1;
+            Diagnostic::error("E0008", "unterminated character literal".to_owned())
+                .with_label(self.span(start, start + 1), "the literal starts here".to_owned())
+        };
+        errors.push(error);
+        false
+    }
 
-        Ok(tokens)
+    // The errors of the number literal text starting at start: digits its base does not
+    // have, none at all, or a suffix that is not the name of an integer type.
+    fn check_number(&self, start: usize, text: &str) -> Vec<Diagnostic> {
+        let mut errors = Vec::new();
+        let (radix, digits, suffix) = split_number(text);
+        let digits_start = start + text.len() - suffix.len() - digits.len();
+        let end = start + text.len();
+
+        for (i, c) in digits.chars().enumerate() {
+            if c != '_' && !c.is_digit(radix) {
+                let error = Diagnostic::error("E0006", format!("invalid digit for a base {} literal", radix))
+                    .with_label(self.span(digits_start + i, digits_start + i + 1), format!("not a base {} digit", radix));
+                errors.push(error);
+            }
+        }
+        if !digits.chars().any(|c| c != '_') {
+            let error = Diagnostic::error("E0007", "no valid digits found for number".to_owned())
+                .with_label(self.span(start, end), "expected digits".to_owned());
+            errors.push(error);
+        }
+        if !suffix.is_empty() && IntType::from_suffix(suffix).is_none() {
+            let error = Diagnostic::error("E0005", format!("invalid suffix `{}` for number literal", suffix))
+                .with_label(self.span(end - suffix.len(), end), format!("invalid suffix `{}`", suffix))
+                .with_help("the suffixes are `i8`, `i16`, `i32`, `i64`, `u8`, `u16`, `u32` and `u64`".to_owned());
+            errors.push(error);
+        }
+
+        errors
+    }
+
+    // The char of the escape sequence whose backslash is at i, leaving i at its last char.
+    fn escape(&self, i: &mut usize) -> Result<char, Diagnostic> {
+        let start = *i;
+        *i += 1;
+        let c = match self.code[*i] {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\'' => '\'',
+            '"' => '"',
+            '\\' => '\\',
+            'u' => return self.unicode_escape(start, i),
+            c => {
+                let error = Diagnostic::error("E0003", format!("unknown character escape `{}`", c))
+                    .with_label(self.span(start, *i + 1), "unknown character escape".to_owned())
+                    .with_help("the escapes are `\\n`, `\\t`, `\\r`, `\\0`, `\\'`, `\\\"`, `\\\\` and `\\u{...}`".to_owned());
+                return Err(error);
+            }
+        };
+
+        Ok(c)
+    }
+
+    // \u{...} with one to six hex digits, i at the `u`.
+    fn unicode_escape(&self, start: usize, i: &mut usize) -> Result<char, Diagnostic> {
+        if *i + 1 >= self.len || self.code[*i + 1] != '{' {
+            let error = Diagnostic::error("E0004", "incorrect unicode escape sequence".to_owned())
+                .with_label(self.span(start, *i + 1), "incorrect unicode escape sequence".to_owned())
+                .with_help("the format of unicode escapes is `\\u{...}`".to_owned());
+            return Err(error);
+        }
+
+        let digits = *i + 2;
+        let mut end = digits;
+        while end < self.len && self.code[end].is_ascii_hexdigit() {
+            end += 1;
+        }
+        if end >= self.len || self.code[end] != '}' {
+            *i = end - 1;
+            let error = Diagnostic::error("E0004", "unterminated unicode escape".to_owned())
+                .with_label(self.span(start, end), "missing a closing `}`".to_owned())
+                .with_help("the format of unicode escapes is `\\u{...}`".to_owned());
+            return Err(error);
+        }
+        *i = end;
+
+        let hex: String = self.code[digits..end].iter().collect();
+        let span = self.span(start, end + 1);
+        if hex.is_empty() || hex.len() > 6 {
+            let error = Diagnostic::error("E0004", "invalid unicode escape".to_owned())
+                .with_label(span, "expected one to six hex digits".to_owned());
+            return Err(error);
+        }
+        match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+            Some(c) => Ok(c),
+            None => {
+                let error = Diagnostic::error("E0004", "invalid unicode character escape".to_owned())
+                    .with_label(span, "not a unicode scalar value".to_owned());
+                Err(error)
+            }
+        }
+    }
+
+    // Token of code[start..end], taking over the scanned contents.
+    fn create_token(&self, t_type: TokenType, start: usize, end: usize, contents: &mut String) -> Token {
+        Token::new(t_type, self.span(start, end), std::mem::take(contents))
+    }
+
+    // Span of code[start..end]
+    fn span(&self, start: usize, end: usize) -> Span {
+        let (start_offset, pos) = self.positions[start];
+        let (end_offset, _) = self.positions[end];
+
+        Span::new(start_offset, end_offset, pos)
+    }
+}
+
+// The parts of a number literal as written: the radix of its prefix, `0x`, `0o` or `0b`,
+// the digits with their `_` separators, and the suffix after them.
+pub fn split_number(text: &str) -> (u32, &str, &str) {
+    let (radix, rest) = match text.get(..2) {
+        Some("0x") => (16, &text[2..]),
+        Some("0o") => (8, &text[2..]),
+        Some("0b") => (2, &text[2..]),
+        _ => (10, text),
+    };
+    // binary and octal digits past their base are still digits, just wrong ones
+    let digit_radix = if radix == 16 { 16 } else { 10 };
+    let end = rest.find(|c: char| c != '_' && !c.is_digit(digit_radix)).unwrap_or(rest.len());
+
+    (radix, &rest[..end], &rest[end..])
+}
+
+// Keywords are scanned as identifiers and then looked up here.
+fn keyword_or_identifier(word: &str) -> TokenType {
+    match word {
+        "let" => TokenType::Let,
+        "mut" => TokenType::Mut,
+        "fn" => TokenType::Fn,
+        "return" => TokenType::Return,
+        "if" => TokenType::If,
+        "else" => TokenType::Else,
+        "while" => TokenType::While,
+        "break" => TokenType::Break,
+        "continue" => TokenType::Continue,
+        "for" => TokenType::For,
+        "in" => TokenType::In,
+        "true" => TokenType::True,
+        "false" => TokenType::False,
+        "struct" => TokenType::Struct,
+        "enum" => TokenType::Enum,
+        "match" => TokenType::Match,
+        _ => TokenType::Identifier,
     }
 }
 
 impl Token {
-    fn new(t_type: TokenType, pos: Position, inner: String) -> Token {
+    fn new(t_type: TokenType, span: Span, inner: String) -> Token {
         Token {
-            t_type: t_type,
-            pos: pos,
-            inner: inner,
+            t_type,
+            span,
+            inner,
         }
     }
 
     pub fn get_line(&self) -> u32 {
-        self.pos.y
+        self.span.pos.y
     }
 
     pub fn get_x(&self) -> u32 {
-        self.pos.x
+        self.span.pos.x
     }
 
     pub fn get_inner(&self) -> String {
@@ -244,13 +746,70 @@ impl Token {
     pub fn get_t_type(&self) -> TokenType {
         self.t_type
     }
+
+    pub fn get_pos(&self) -> Position {
+        self.span.pos
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+// Identifier       "x" at 1:5
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let t_type = format!("{:?}", self.t_type);
+        write!(f, "{:<16} {:?} at {}:{}", t_type, self.inner, self.get_line(), self.get_x())
+    }
 }
 
 impl Position {
     fn new(x: u32, y: u32) -> Position {
         Position {
-            x: x,
-            y: y,
+            x,
+            y,
         }
     }
-}
\ No newline at end of file
+
+    pub fn get_line(&self) -> u32 {
+        self.y
+    }
+
+    pub fn get_x(&self) -> u32 {
+        self.x
+    }
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, pos: Position) -> Span {
+        Span {
+            start,
+            end,
+            pos,
+        }
+    }
+
+    // The empty span right after self, where something is missing.
+    pub fn end_point(&self) -> Span {
+        let pos = Position::new(self.pos.x + (self.end - self.start) as u32, self.pos.y);
+        Span::new(self.end, self.end, pos)
+    }
+
+    // From the start of self to the end of other.
+    pub fn to(&self, other: Span) -> Span {
+        Span::new(self.start, other.end, self.pos)
+    }
+
+    pub fn get_start(&self) -> usize {
+        self.start
+    }
+
+    pub fn get_end(&self) -> usize {
+        self.end
+    }
+
+    pub fn get_pos(&self) -> Position {
+        self.pos
+    }
+}
@@ -0,0 +1,454 @@
+use std::fmt;
+use std::rc::Rc;
+
+// Types of mylang values, shared by the semantic pass and codegen.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Int(IntType),
+    Bool,
+    Char,
+    Str,
+    Unit,
+    Struct(Rc<StructType>),
+    Enum(Rc<EnumType>),
+    Reference {
+        target: Box<Type>,
+        mutable: bool,
+    },
+    Array(Box<Type>, usize),
+    // laid out like a struct of the elements
+    Tuple(Vec<Type>),
+    Function(FunctionType),
+}
+
+// The integer types by signedness and width. `int` is another name for i64,
+// and the type of integer literals nothing else gives a type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntType {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+// Structs are nominal: two struct types are equal when their names are.
+#[derive(Debug)]
+pub struct StructType {
+    name: String,
+    fields: Vec<Field>,
+}
+
+// Enums are nominal too. A value is the index of its variant, the tag, in the
+// first 8 bytes, followed by the fields of the variant laid out like a struct.
+#[derive(Debug)]
+pub struct EnumType {
+    name: String,
+    variants: Vec<Variant>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant {
+    name: String,
+    fields: Vec<Type>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    name: String,
+    field_type: Type,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionType {
+    params: Vec<Type>,
+    return_type: Box<Type>,
+}
+
+impl Type {
+    // Types that are written as a plain name without any declaration.
+    pub fn primitive(name: &str) -> Option<Type> {
+        match name {
+            "int" => Some(Type::Int(IntType::I64)),
+            "bool" => Some(Type::Bool),
+            "char" => Some(Type::Char),
+            "str" => Some(Type::Str),
+            _ => IntType::from_suffix(name).map(Type::Int),
+        }
+    }
+
+    pub fn reference(target: Type, mutable: bool) -> Type {
+        Type::Reference {
+            target: Box::new(target),
+            mutable,
+        }
+    }
+
+    pub fn array(element: Type, len: usize) -> Type {
+        Type::Array(Box::new(element), len)
+    }
+
+    pub fn function(params: Vec<Type>, return_type: Type) -> Type {
+        Type::Function(FunctionType::new(params, return_type))
+    }
+
+    // Size in bytes, a multiple of the alignment as in C.
+    pub fn size(&self) -> usize {
+        match self {
+            Type::Int(int_type) => int_type.get_bits() as usize / 8,
+            Type::Bool => 1,
+            Type::Char => 4,
+            // pointer and length
+            Type::Str => 16,
+            Type::Unit => 0,
+            Type::Struct(struct_type) => struct_type.size(),
+            Type::Enum(enum_type) => enum_type.size(),
+            Type::Reference { .. } => 8,
+            Type::Array(element, len) => element.size() * len,
+            Type::Tuple(elements) => {
+                let size = elements.iter().fold(0usize, |size, element| align_to(size, element.align()) + element.size());
+                align_to(size, self.align())
+            }
+            // a function value is its address
+            Type::Function(_) => 8,
+        }
+    }
+
+    pub fn align(&self) -> usize {
+        match self {
+            Type::Int(int_type) => int_type.get_bits() as usize / 8,
+            Type::Bool => 1,
+            Type::Char => 4,
+            Type::Str => 8,
+            Type::Unit => 1,
+            Type::Struct(struct_type) => struct_type.align(),
+            Type::Enum(enum_type) => enum_type.align(),
+            Type::Reference { .. } => 8,
+            Type::Array(element, _) => element.align(),
+            Type::Tuple(elements) => elements.iter().map(Type::align).max().unwrap_or(1),
+            Type::Function(_) => 8,
+        }
+    }
+
+    // Whether values of the type fit in a general purpose register.
+    pub fn is_scalar(&self) -> bool {
+        match self {
+            Type::Int(_) | Type::Bool | Type::Char | Type::Unit => true,
+            Type::Reference { .. } | Type::Function(_) => true,
+            Type::Str | Type::Struct(_) | Type::Enum(_) | Type::Array(_, _) | Type::Tuple(_) => false,
+        }
+    }
+
+    // Byte offset of the index-th element of a tuple.
+    pub fn offset_of(&self, index: usize) -> usize {
+        let elements = match self {
+            Type::Tuple(elements) => elements,
+            _ => unreachable!("only tuples have numbered fields"),
+        };
+
+        let mut offset = 0usize;
+        for element in elements[..index].iter() {
+            offset = align_to(offset, element.align()) + element.size();
+        }
+
+        align_to(offset, elements[index].align())
+    }
+}
+
+impl StructType {
+    pub fn new(name: String, fields: Vec<Field>) -> StructType {
+        StructType {
+            name,
+            fields,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    pub fn get_field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    // Byte offset of the field, laid out in declaration order like a C struct.
+    pub fn offset_of(&self, name: &str) -> Option<usize> {
+        let mut offset = 0usize;
+        for field in self.fields.iter() {
+            offset = align_to(offset, field.field_type.align());
+            if field.name == name {
+                return Some(offset);
+            }
+            offset += field.field_type.size();
+        }
+
+        None
+    }
+
+    pub fn size(&self) -> usize {
+        let mut size = 0usize;
+        for field in self.fields.iter() {
+            size = align_to(size, field.field_type.align()) + field.field_type.size();
+        }
+
+        align_to(size, self.align())
+    }
+
+    pub fn align(&self) -> usize {
+        self.fields.iter().map(|field| field.field_type.align()).max().unwrap_or(1)
+    }
+}
+
+impl PartialEq for StructType {
+    fn eq(&self, other: &StructType) -> bool {
+        self.name == other.name
+    }
+}
+
+impl EnumType {
+    pub fn new(name: String, variants: Vec<Variant>) -> EnumType {
+        EnumType {
+            name,
+            variants,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_variants(&self) -> &[Variant] {
+        &self.variants
+    }
+
+    // The variant with its tag.
+    pub fn get_variant(&self, name: &str) -> Option<(usize, &Variant)> {
+        self.variants.iter().enumerate().find(|(_, variant)| variant.name == name)
+    }
+
+    // Byte offset of the field-th field of the variant with the tag.
+    pub fn offset_of(&self, tag: usize, field: usize) -> usize {
+        let mut offset = 8usize;
+        for (i, field_type) in self.variants[tag].fields.iter().enumerate() {
+            offset = align_to(offset, field_type.align());
+            if i == field {
+                break;
+            }
+            offset += field_type.size();
+        }
+
+        offset
+    }
+
+    // The tag and the largest variant.
+    pub fn size(&self) -> usize {
+        let mut size = 8usize;
+        for variant in self.variants.iter() {
+            let mut end = 8usize;
+            for field_type in variant.fields.iter() {
+                end = align_to(end, field_type.align()) + field_type.size();
+            }
+            size = size.max(end);
+        }
+
+        align_to(size, self.align())
+    }
+
+    pub fn align(&self) -> usize {
+        let fields = self.variants.iter().flat_map(|variant| variant.fields.iter());
+        fields.map(|field_type| field_type.align()).max().unwrap_or(8).max(8)
+    }
+}
+
+impl PartialEq for EnumType {
+    fn eq(&self, other: &EnumType) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Variant {
+    pub fn new(name: String, fields: Vec<Type>) -> Variant {
+        Variant {
+            name,
+            fields,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_fields(&self) -> &[Type] {
+        &self.fields
+    }
+}
+
+impl Field {
+    pub fn new(name: String, field_type: Type) -> Field {
+        Field {
+            name,
+            field_type,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_type(&self) -> &Type {
+        &self.field_type
+    }
+}
+
+impl FunctionType {
+    pub fn new(params: Vec<Type>, return_type: Type) -> FunctionType {
+        FunctionType {
+            params,
+            return_type: Box::new(return_type),
+        }
+    }
+
+    pub fn get_params(&self) -> &[Type] {
+        &self.params
+    }
+
+    pub fn get_return_type(&self) -> &Type {
+        &self.return_type
+    }
+}
+
+pub fn align_to(n: usize, align: usize) -> usize {
+    n.div_ceil(align) * align
+}
+
+// The elements in parentheses, with a `,` after an only one so it is not just parenthesized.
+pub fn tuple(elements: &[String]) -> String {
+    match elements {
+        [only] => format!("({},)", only),
+        _ => format!("({})", elements.join(", ")),
+    }
+}
+
+impl IntType {
+    // The type of a literal suffix like the `u8` of `10u8`, which is also the name of the type.
+    pub fn from_suffix(suffix: &str) -> Option<IntType> {
+        match suffix {
+            "i8" => Some(IntType::I8),
+            "i16" => Some(IntType::I16),
+            "i32" => Some(IntType::I32),
+            "i64" => Some(IntType::I64),
+            "u8" => Some(IntType::U8),
+            "u16" => Some(IntType::U16),
+            "u32" => Some(IntType::U32),
+            "u64" => Some(IntType::U64),
+            _ => None,
+        }
+    }
+
+    pub fn get_suffix(&self) -> &'static str {
+        match self {
+            IntType::I8 => "i8",
+            IntType::I16 => "i16",
+            IntType::I32 => "i32",
+            IntType::I64 => "i64",
+            IntType::U8 => "u8",
+            IntType::U16 => "u16",
+            IntType::U32 => "u32",
+            IntType::U64 => "u64",
+        }
+    }
+
+    pub fn get_bits(&self) -> u32 {
+        match self {
+            IntType::I8 | IntType::U8 => 8,
+            IntType::I16 | IntType::U16 => 16,
+            IntType::I32 | IntType::U32 => 32,
+            IntType::I64 | IntType::U64 => 64,
+        }
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(self, IntType::I8 | IntType::I16 | IntType::I32 | IntType::I64)
+    }
+
+    pub fn min(&self) -> i128 {
+        if self.is_signed() {
+            -(1i128 << (self.get_bits() - 1))
+        } else {
+            0
+        }
+    }
+
+    pub fn max(&self) -> i128 {
+        if self.is_signed() {
+            (1i128 << (self.get_bits() - 1)) - 1
+        } else {
+            (1i128 << self.get_bits()) - 1
+        }
+    }
+
+    pub fn contains(&self, n: i128) -> bool {
+        self.min() <= n && n <= self.max()
+    }
+
+    // n modulo 2 to the bits, in the range of the type, which is how the machine wraps
+    pub fn wrap(&self, n: i128) -> i128 {
+        let modulus = 1i128 << self.get_bits();
+        let n = n.rem_euclid(modulus);
+        if n > self.max() {
+            n - modulus
+        } else {
+            n
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Int(int_type) => write!(f, "{}", int_type),
+            Type::Bool => write!(f, "bool"),
+            Type::Char => write!(f, "char"),
+            Type::Str => write!(f, "str"),
+            Type::Unit => write!(f, "()"),
+            Type::Struct(struct_type) => write!(f, "{}", struct_type.name),
+            Type::Enum(enum_type) => write!(f, "{}", enum_type.name),
+            Type::Reference { target, mutable: true } => write!(f, "&mut {}", target),
+            Type::Reference { target, mutable: false } => write!(f, "&{}", target),
+            Type::Array(element, len) => write!(f, "[{}; {}]", element, len),
+            Type::Tuple(elements) => {
+                let elements: Vec<String> = elements.iter().map(|element| element.to_string()).collect();
+                write!(f, "{}", tuple(&elements))
+            }
+            Type::Function(function) => write!(f, "{}", function),
+        }
+    }
+}
+
+impl fmt::Display for FunctionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params: Vec<String> = self.params.iter().map(|param| param.to_string()).collect();
+        write!(f, "fn({})", params.join(", "))?;
+        if *self.return_type != Type::Unit {
+            write!(f, " -> {}", self.return_type)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for IntType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntType::I64 => write!(f, "int"),
+            int_type => write!(f, "{}", int_type.get_suffix()),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{ self, Command, Stdio };
+
+// A scratch path for one test, which may run alongside the others.
+fn scratch(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("my_lang-{}-{}", process::id(), name))
+}
+
+fn compile(name: &str, code: &str, options: &[&str]) -> PathBuf {
+    let source = scratch(name).with_extension("ml");
+    let exe = scratch(name);
+    fs::write(&source, code).expect("writes the source");
+    let built = Command::new(env!("CARGO_BIN_EXE_my_lang"))
+        .arg("build").args(options).arg(&source).arg("-o").arg(&exe)
+        .output().expect("runs my_lang");
+    assert!(built.status.success(), "build failed: {}", String::from_utf8_lossy(&built.stderr));
+    fs::remove_file(&source).expect("removes the source");
+
+    exe
+}
+
+// Builds the code with the options, runs it and returns its stdout and exit status.
+fn build_and_run(name: &str, code: &str, options: &[&str]) -> (String, i32) {
+    let exe = compile(name, code, options);
+    let ran = Command::new(&exe).output().expect("runs the program");
+    fs::remove_file(&exe).expect("removes the program");
+
+    (String::from_utf8_lossy(&ran.stdout).into_owned(), ran.status.code().expect("exits"))
+}
+
+fn assembly(name: &str, code: &str, options: &[&str]) -> String {
+    let mut options = options.to_vec();
+    options.push("--emit=asm");
+    let asm = compile(name, code, &options);
+    let text = fs::read_to_string(&asm).expect("reads the assembly");
+    fs::remove_file(&asm).expect("removes the assembly");
+
+    text
+}
+
+#[test]
+fn functions_are_global_symbols() {
+    let code = "fn add(a: int, b: int) -> int { a + b }\nfn main() -> int { add(2, 3) }";
+    let assembly = assembly("global", code, &[]);
+    assert!(assembly.contains(".global add\nadd:") && assembly.contains("call add"));
+    assert_eq!(build_and_run("global", code, &[]), (String::new(), 5));
+}
+
+// The body of the function name in the assembly, up to its return label.
+fn function<'a>(assembly: &'a str, name: &str) -> &'a str {
+    let start = assembly.find(&format!("\n{}:", name)).expect("the function is generated");
+    let end = assembly[start..].find(&format!(".L.return.{}:", name)).expect("the function returns");
+    &assembly[start..start + end]
+}
+
+#[test]
+fn tuples_are_returned_in_registers_or_memory() {
+    let code = "
+        fn small(a: i8, b: u16) -> (i8, u16) { (a, b) }
+        fn large(a: int, b: int, c: int, d: int, e: int, f: int) -> (int, int, int) { (a + b, c + d, e * f) }
+        fn many(a: int, b: int, c: int, d: int, e: int, f: int, g: int, h: int) -> int {
+            a + b * 2 + c * 3 + d * 4 + e * 5 + f * 6 + g * 7 + h * 8
+        }
+        let (x, y) = small(-3, 65535);
+        let (p, q, r) = large(1, 2, 3, 4, 5, 6);
+        println(x);
+        println(y);
+        println(p + q + r);
+        println(many(1, 1, 1, 1, 1, 1, 1, 10));
+        let t = large(p, 0, 0, 0, 7, many(0, 0, 0, 0, 0, 0, 0, 1));
+        t.0 + t.2;
+    ";
+    let assembly = assembly("tuples", code, &[]);
+    // (i8, u16) comes back in %rax and %rdx
+    assert!(function(&assembly, "small").contains("%rdx"));
+    // (int, int, int) is copied to the slot the hidden first argument points to
+    assert!(function(&assembly, "large").contains("mov %rdi, %rax"));
+    assert_eq!(build_and_run("tuples", code, &[]), ("-3\n65535\n40\n108\n".to_owned(), 59));
+    assert_eq!(build_and_run("tuples", code, &["-O1"]), ("-3\n65535\n40\n108\n".to_owned(), 59));
+}
+
+#[test]
+fn narrow_integers_wrap_or_trap_at_their_width() {
+    let code = "
+        fn id(x: i8) -> i8 { x }
+        let a: u8 = 200;
+        let b: i16 = 32767;
+        let c = id(-128);
+        println(a + 100);
+        println(b + 1);
+        println(c / id(-1));
+        println(0xffff_ffffu32 * 2);
+        println('é');
+        a * 2;
+    ";
+    let assembly = assembly("narrow", code, &[]);
+    // loads extend by the signedness of the type, stores write only its bytes
+    assert!(assembly.contains("movzbq -8(%rbp), %rax") && assembly.contains("movswq -16(%rbp), %rax"));
+    assert!(assembly.contains("mov %al, -8(%rbp)") && assembly.contains("mov %ax, -16(%rbp)"));
+    for options in [&[][..], &["-O1"]] {
+        assert_eq!(build_and_run("narrow", code, options), ("44\n-32768\n-128\n4294967294\né\n".to_owned(), 144));
+    }
+
+    let trap = |code: &str| build_and_run("trap", code, &["--overflow-checks"]).1;
+    assert_eq!(trap("let a: u8 = 200;\na + 100;"), 101);
+    assert_eq!(trap("let b: i16 = -32768;\nb - 1;"), 101);
+    assert_eq!(trap("let c: i8 = -128;\nlet d: i8 = -1;\nc / d;"), 101);
+    assert_eq!(trap("let e: u64 = 0;\ne - 1;"), 101);
+    assert_eq!(trap("let a: u8 = 200;\na - 100;"), 100);
+}
+
+#[test]
+fn only_levels_0_and_1_optimize() {
+    let status = |level: &str| {
+        Command::new(env!("CARGO_BIN_EXE_my_lang")).args(["build", level, "--emit=tokens", "-"])
+            .stdin(Stdio::null()).output().expect("runs my_lang").status.code()
+    };
+    assert_eq!(status("-O"), Some(0));
+    assert_eq!(status("-O1"), Some(0));
+    assert_eq!(status("-O2"), Some(2));
+    assert_eq!(status("-O3"), Some(2));
+}
+
+#[test]
+fn binaries_are_not_written_to_stdout() {
+    let status = |options: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_my_lang")).arg("build").args(options).args(["-o", "-", "-"])
+            .stdin(Stdio::null()).output().expect("runs my_lang").status.code()
+    };
+    assert_eq!(status(&["--emit=obj"]), Some(2));
+    assert_eq!(status(&[]), Some(2));
+    assert_eq!(status(&["--emit=tokens"]), Some(0));
+}
+
+#[test]
+fn objects_may_be_named_like_assembly() {
+    let source = scratch("object").with_extension("ml");
+    let object = scratch("object").with_extension("s");
+    fs::write(&source, "1;").expect("writes the source");
+    let built = Command::new(env!("CARGO_BIN_EXE_my_lang"))
+        .arg("build").arg("--emit=obj").arg(&source).arg("-o").arg(&object)
+        .output().expect("runs my_lang");
+    assert!(built.status.success(), "build failed: {}", String::from_utf8_lossy(&built.stderr));
+    let contents = fs::read(&object).expect("reads the object");
+    fs::remove_file(&source).expect("removes the source");
+    fs::remove_file(&object).expect("removes the object");
+    assert_eq!(contents[..4], *b"\x7fELF");
+}
+
+#[test]
+fn negative_literals_are_constants() {
+    let code = "let x: int = -9223372036854775808;\nlet y = -128i8;\nprintln(x);\nprintln(y);\n-y;";
+    let assembly = assembly("negative", code, &["--overflow-checks"]);
+    assert!(assembly.contains("mov $-9223372036854775808, %rax"));
+    for options in [&["--overflow-checks"][..], &["--overflow-checks", "-O1"]] {
+        assert_eq!(build_and_run("negative", code, options), ("-9223372036854775808\n-128\n".to_owned(), 101));
+    }
+}
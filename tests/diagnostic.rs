@@ -0,0 +1,95 @@
+extern crate my_lang;
+
+use my_lang::diagnostic::{ Diagnostic, Renderer };
+use my_lang::tokenizer::{ Tokenizer, TokenType, Span };
+
+// The span of the nth token of source that reads text.
+fn span(source: &str, text: &str, nth: usize) -> Span {
+    let tokens = Tokenizer::new(source.chars().collect()).tokenize().expect("tokenizes");
+    tokens.iter().filter(|token| token.get_t_type() != TokenType::EOF && token.get_inner() == text)
+        .nth(nth).expect("the token is in the source").get_span()
+}
+
+fn render(source: &str, diagnostic: &Diagnostic) -> String {
+    Renderer::new("test.ml", source).with_color(false).render(diagnostic)
+}
+
+#[test]
+fn labels_show_their_source_lines() {
+    let source = "fn f() -> int {\n    return g();\n}";
+    let error = Diagnostic::error("E0206", "mismatched types".to_owned())
+        .with_secondary(span(source, "f", 0), "expected `int` because of the return type".to_owned())
+        .with_label(span(source, "g", 0).to(span(source, ")", 1)), "expected `int`, found `()`".to_owned())
+        .with_note("a note".to_owned())
+        .with_help("a help".to_owned());
+    assert_eq!(render(source, &error), "\
+error[E0206]: mismatched types
+ --> test.ml:2:12
+  |
+1 | fn f() -> int {
+  |    - expected `int` because of the return type
+2 |     return g();
+  |            ^^^ expected `int`, found `()`
+  |
+  = note: a note
+  = help: a help
+");
+}
+
+#[test]
+fn labels_on_one_line_share_it_primary_first() {
+    let source = "let x = a + b;";
+    let error = Diagnostic::error("E0201", "mismatched types".to_owned())
+        .with_secondary(span(source, "a", 0), "this is `int`".to_owned())
+        .with_label(span(source, "b", 0), "found `bool`".to_owned());
+    assert_eq!(render(source, &error), "\
+error[E0201]: mismatched types
+ --> test.ml:1:13
+  |
+1 | let x = a + b;
+  |             ^ found `bool`
+  |         - this is `int`
+");
+}
+
+#[test]
+fn lines_between_labels_are_left_out() {
+    let source = "let u = x;\n1;\n2;\n3;\n4;\n5;\n6;\n7;\n8;\nlet y = u;";
+    let warning = Diagnostic::warning("W0001", "unused".to_owned())
+        .with_label(span(source, "x", 0), "here".to_owned())
+        .with_secondary(span(source, "u", 1), "there".to_owned());
+    assert_eq!(render(source, &warning), "\
+warning[W0001]: unused
+  --> test.ml:1:9
+   |
+ 1 | let u = x;
+   |         ^ here
+...
+10 | let y = u;
+   |         - there
+");
+}
+
+#[test]
+fn columns_count_chars_and_keep_tabs() {
+    let source = "\tlet s = \"é\"; let u = x;";
+    let error = Diagnostic::error("E0202", "cannot find variable `x`".to_owned())
+        .with_label(span(source, "x", 0), "not found".to_owned());
+    assert_eq!(render(source, &error), "\
+error[E0202]: cannot find variable `x`
+ --> test.ml:1:23
+  |
+1 | \tlet s = \"é\"; let u = x;
+  | \t                     ^ not found
+");
+
+    let source = "let s = '\t';\tlet t = \"日本\"; ü";
+    let errs = Tokenizer::new(source.chars().collect()).tokenize().expect_err("does not tokenize");
+    assert_eq!(render(source, &errs[0]), "\
+error[E0001]: unknown character `ü`
+ --> test.ml:1:28
+  |
+1 | let s = '\t';\tlet t = \"日本\"; ü
+  |          \t  \t              ^ not a valid token
+");
+}
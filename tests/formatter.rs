@@ -0,0 +1,49 @@
+extern crate my_lang;
+
+use my_lang::tokenizer::Tokenizer;
+use my_lang::parser::Parser;
+use my_lang::formatter::Formatter;
+
+// code in the canonical layout, with its comments
+fn format(code: &str) -> String {
+    let tokens = Tokenizer::new(code.chars().collect()).tokenize().expect("tokenizes");
+    let program = Parser::new(tokens.clone()).parse().expect("parses");
+
+    Formatter::new().with_comments(&tokens).format(&program)
+}
+
+#[test]
+fn declarations_come_before_statements() {
+    let code = "let x = (1+2)*3;fn f(a:int)->int{if a>0{1}else{2}}\nstruct P{x:int}";
+    assert_eq!(format(code), "struct P {\n    x: int,\n}\n\nfn f(a: int) -> int {\n    if a > 0 { 1 } else { 2 }\n}\n\nlet x = (1 + 2) * 3;\n");
+}
+
+#[test]
+fn comments_stay_with_their_lines() {
+    let code = "\
+// counts down
+fn f(n: int) -> int { // n > 0
+    let mut i = n; // left
+    while i > 0 {
+        i = i - 1;
+        // one fewer
+    }
+    match i {
+        // only zero is left
+        0 => 1,
+        _ => 2, // unreachable
+    }
+}
+
+f(3);
+// done
+";
+    assert_eq!(format(code), code);
+}
+
+#[test]
+fn a_value_with_comments_gets_its_own_line() {
+    let code = "let c = true;\nlet v = if c { 1 } else { 2 // two\n};";
+    assert_eq!(format(code), "let c = true;\nlet v = if c { 1 } else {\n    2 // two\n};\n");
+    assert_eq!(format(&format(code)), format(code));
+}
@@ -0,0 +1,264 @@
+extern crate my_lang;
+
+use my_lang::tokenizer::Tokenizer;
+use my_lang::parser::Parser;
+use my_lang::semantic::TypeChecker;
+use my_lang::lower::lower;
+use my_lang::interp::{ Interpreter, Value };
+use my_lang::types::IntType;
+
+fn run(code: &str) -> Result<Value, String> {
+    run_with(Interpreter::new(), code)
+}
+
+fn run_with(mut interpreter: Interpreter, code: &str) -> Result<Value, String> {
+    let tokens = Tokenizer::new(code.chars().collect()).tokenize().expect("tokenizes");
+    let program = Parser::new(tokens).parse().expect("parses");
+    let mut checker = TypeChecker::new();
+    checker.check(&program).expect("type checks");
+
+    interpreter.run(&lower(&program, &checker.take_literal_types())).map_err(|err| err.get_message().to_owned())
+}
+
+// What code prints.
+fn output(code: &str) -> String {
+    let tokens = Tokenizer::new(code.chars().collect()).tokenize().expect("tokenizes");
+    let program = Parser::new(tokens).parse().expect("parses");
+    let mut checker = TypeChecker::new();
+    checker.check(&program).expect("type checks");
+
+    let mut interpreter = Interpreter::new().with_captured_output(true);
+    interpreter.run(&lower(&program, &checker.take_literal_types())).expect("runs");
+    interpreter.take_output()
+}
+
+#[test]
+fn value_of_the_last_statement() {
+    assert_eq!(run("1 + 2 * 3;"), Ok(Value::Int(7, IntType::I64)));
+    assert_eq!(run("let x = 4;\nlet mut y = x * x;\ny = y - 1;\n"), Ok(Value::Int(15, IntType::I64)));
+}
+
+#[test]
+fn functions_and_shadowing() {
+    let code = "
+        fn sq(x: int) -> int {
+            let x = x * x;
+            return x;
+        }
+        let x = 3;
+        sq(x) + x;
+    ";
+    assert_eq!(run(code), Ok(Value::Int(12, IntType::I64)));
+}
+
+#[test]
+fn main_without_value() {
+    assert_eq!(run("fn main() {\n}\n"), Ok(Value::Unit));
+}
+
+#[test]
+fn signed_division() {
+    assert_eq!(run("-7 / 2;"), Ok(Value::Int(-3, IntType::I64)));
+    assert_eq!(run("-7 % 2;"), Ok(Value::Int(-1, IntType::I64)));
+    assert_eq!(run("let x = 0;\n1 / x;\n"), Err("attempt to divide by zero".to_owned()));
+}
+
+#[test]
+fn deep_recursion_is_an_error() {
+    let code = "
+        fn f(x: int) -> int {
+            return f(x + 1);
+        }
+        f(0);
+    ";
+    assert!(run(code).unwrap_err().starts_with("stack overflow"));
+}
+
+#[test]
+fn if_is_an_expression() {
+    let code = "
+        fn sign(x: int) -> int {
+            if x < 0 { -1 } else if x == 0 { 0 } else { 1 }
+        }
+        sign(-5) * 100 + sign(0) * 10 + sign(7);
+    ";
+    assert_eq!(run(code), Ok(Value::Int(-99, IntType::I64)));
+    assert_eq!(run("let x = if 2 > 1 { 3 } else { return 4; };\nx;\n"), Ok(Value::Int(3, IntType::I64)));
+}
+
+#[test]
+fn while_with_break_and_continue() {
+    let code = "
+        let mut i = 0;
+        let mut sum = 0;
+        while true {
+            i = i + 1;
+            if i > 10 { break; }
+            if i % 2 == 0 { continue; }
+            sum = sum + i;
+        }
+        sum;
+    ";
+    assert_eq!(run(code), Ok(Value::Int(25, IntType::I64)));
+}
+
+#[test]
+fn logic_short_circuits() {
+    assert_eq!(run("let x = 0;\nx != 0 && 1 / x > 0;\n"), Ok(Value::Bool(false)));
+    assert_eq!(run("let x = 0;\nx == 0 || 1 / x > 0;\n"), Ok(Value::Bool(true)));
+    assert_eq!(run("!(1 < 2) || true == !false;"), Ok(Value::Bool(true)));
+}
+
+#[test]
+fn for_over_ranges() {
+    let code = "
+        let mut sum = 0;
+        for i in 0..10 {
+            if i == 3 { continue; }
+            for j in 1..=i {
+                sum = sum + j;
+            }
+        }
+        sum;
+    ";
+    assert_eq!(run(code), Ok(Value::Int(159, IntType::I64)));
+    assert_eq!(run("let mut n = 0;\nfor i in 5..5 { n = n + 1; }\nfor i in 5..=5 { n = n + 10; }\nn;\n"), Ok(Value::Int(10, IntType::I64)));
+}
+
+#[test]
+fn structs_are_copied() {
+    let code = "
+        struct Point { x: int, y: int }
+        struct Line { from: Point, to: Point }
+        fn moved(p: Point, by: int) -> Point {
+            let mut p = p;
+            p.x = p.x + by;
+            p
+        }
+        let mut line = Line { from: Point { x: 1, y: 2 }, to: Point { x: 3, y: 4 } };
+        let start = line.from;
+        line.from.x = 10;
+        line.to = moved(line.to, 5);
+        start.x * 1000 + line.from.x * 100 + line.to.x;
+    ";
+    assert_eq!(run(code), Ok(Value::Int(2008, IntType::I64)));
+}
+
+#[test]
+fn print_writes_values() {
+    let code = r#"
+        struct Greeting { text: str }
+        fn hello() -> Greeting {
+            Greeting { text: "hello\tworld" }
+        }
+        print(hello().text);
+        println("!");
+        print(-42);
+        print(" ");
+        println(1 < 2);
+        println();
+        println(-9223372036854775807 - 1);
+    "#;
+    assert_eq!(output(code), "hello\tworld!\n-42 true\n\n-9223372036854775808\n");
+    assert_eq!(run("let s = \"a\\u{1F600}\";\ns;"), Ok(Value::Str("a\u{1F600}".to_owned())));
+}
+
+#[test]
+fn arrays_are_copied() {
+    let code = "
+        struct Grid { cells: [[int; 3]; 2] }
+        fn total(row: [int; 3]) -> int {
+            let mut sum = 0;
+            for cell in row {
+                sum = sum + cell;
+            }
+            sum
+        }
+        let mut grid = Grid { cells: [[1, 2, 3]; 2] };
+        let first = grid.cells[0];
+        grid.cells[0][1] = 20;
+        grid.cells[1] = [len(first), 5, 6];
+        total(first) * 1000 + total(grid.cells[0]) * 10 + grid.cells[1][0];
+    ";
+    assert_eq!(run(code), Ok(Value::Int(6243, IntType::I64)));
+    assert_eq!(run("let a = [true; 2];
+a;"), Ok(Value::Array(vec![Value::Bool(true), Value::Bool(true)])));
+}
+
+#[test]
+fn indexes_are_bounds_checked() {
+    assert_eq!(run("let a = [1, 2, 3];
+let i = 3;
+a[i];
+"), Err("index out of bounds: the len is 3".to_owned()));
+    assert_eq!(run("let mut a = [0; 2];
+let i = -1;
+a[i] = 1;
+"), Err("index out of bounds: the len is 2".to_owned()));
+    assert_eq!(run("len(\"h\u{e9}\");"), Ok(Value::Int(3, IntType::I64)));
+}
+
+#[test]
+fn match_tries_arms_in_order() {
+    let code = "
+        enum Shape { Circle(int), Rect(int, int), Empty }
+        fn area(s: Shape) -> int {
+            match s {
+                Shape::Circle(r) if r < 0 => 0,
+                Shape::Circle(r) => 3 * r * r,
+                Shape::Rect(w, 0) => w,
+                Shape::Rect(w, h) => w * h,
+                Shape::Empty => 0,
+            }
+        }
+        let n = match area(Shape::Rect(2, 3)) { 6 => 100, _ => 0 };
+        n + area(Shape::Circle(-1)) + area(Shape::Circle(2)) * 10 + area(Shape::Rect(5, 0));
+    ";
+    assert_eq!(run(code), Ok(Value::Int(225, IntType::I64)));
+    assert_eq!(run("enum E { A(bool), B }\nE::A(true);"), Ok(Value::Enum("E".to_owned(), "A".to_owned(), vec![Value::Bool(true)])));
+}
+
+#[test]
+fn tuples_destructure() {
+    let code = "
+        fn div(a: int, b: int) -> (int, int) { (a / b, a % b) }
+        fn wide(x: int) -> (int, (bool, int), int) { (x, (x > 0, x * 2), x * 3) }
+        let (q, r) = div(17, 5);
+        let (a, (pos, b), _) = wide(4);
+        let mut t = (q, r);
+        t = (t.1, t.0);
+        t.0 = t.0 * 10;
+        let bonus = match (pos, b) { (false, _) => 0, (true, 8) => 100, (true, n) => n };
+        t.0 + t.1 + a * 1000 + bonus;
+    ";
+    assert_eq!(run(code), Ok(Value::Int(4123, IntType::I64)));
+    assert_eq!(run("(1, (true,));"), Ok(Value::Tuple(vec![Value::Int(1, IntType::I64), Value::Tuple(vec![Value::Bool(true)])])));
+}
+
+#[test]
+fn sized_integers_wrap_at_their_width() {
+    let code = "
+        let mut a: u8 = 250;
+        a = a + 10;
+        let b: i8 = -128;
+        let c = 18446744073709551615u64;
+        println(a);
+        println(b - 1);
+        println(c + 2);
+        println(0x7fff_i16 + 1);
+        println('z' > 'a');
+        println('é');
+    ";
+    assert_eq!(output(code), "4\n127\n1\n-32768\ntrue\né\n");
+    assert_eq!(run("let x: u16 = 3;\nx * 2;"), Ok(Value::Int(6, IntType::U16)));
+    assert_eq!(run("let mut s: u8 = 0;\nfor i in 250..=255u8 { s = s + i; }\ns;"), Ok(Value::Int(235, IntType::U8)));
+    assert_eq!(run("'q';"), Ok(Value::Char('q')));
+}
+
+#[test]
+fn negative_literals_do_not_overflow() {
+    let checked = || Interpreter::new().with_overflow_checks(true);
+    assert_eq!(run_with(checked(), "let x: int = -9223372036854775808;\nx;"), Ok(Value::Int(i64::MIN as i128, IntType::I64)));
+    assert_eq!(run_with(checked(), "-128i8;"), Ok(Value::Int(-128, IntType::I8)));
+    assert_eq!(run_with(checked(), "let y = -128i8;\n-y;"), Err("attempt to negate with overflow".to_owned()));
+}
@@ -0,0 +1,212 @@
+extern crate my_lang;
+
+use my_lang::tokenizer::{ Tokenizer, TokenType };
+use my_lang::parser::Parser;
+use my_lang::ast::{ Statement, Arithmetic, Node, Operator, UnaryOperator };
+
+// The expression statement in code, fully parenthesized.
+fn parse(code: &str) -> String {
+    let tokens = Tokenizer::new(code.chars().collect()).tokenize().expect("tokenizes");
+    let program = Parser::new(tokens).parse().expect("parses");
+
+    match &program.get_statements()[0] {
+        Statement::Arithmetic(arithmetic) => arithmetic_to_string(arithmetic),
+        statement => panic!("not an expression: {:?}", statement),
+    }
+}
+
+fn arithmetic_to_string(arithmetic: &Arithmetic) -> String {
+    match arithmetic {
+        Arithmetic::Term(node) => node_to_string(node),
+        Arithmetic::MultiTerm(left, op, right) => {
+            let op = match op {
+                Operator::Plus => "+",
+                Operator::Minus => "-",
+                Operator::Mul => "*",
+                Operator::Div => "/",
+                Operator::Rem => "%",
+                Operator::Equal => "==",
+                Operator::NotEqual => "!=",
+                Operator::Less => "<",
+                Operator::LessEqual => "<=",
+                Operator::Greater => ">",
+                Operator::GreaterEqual => ">=",
+                Operator::And => "&&",
+                Operator::Or => "||",
+            };
+            format!("({} {} {})", node_to_string(left), op, node_to_string(right))
+        }
+    }
+}
+
+fn node_to_string(node: &Node) -> String {
+    match node {
+        Node::Number(number) => number.get_inner().to_owned(),
+        Node::Boolean(b) => b.get_value().to_string(),
+        Node::Str(s) => format!("{:?}", s.get_value()),
+        Node::Char(c) => format!("{:?}", c.get_value()),
+        Node::Variable(variable) => variable.get_name().to_owned(),
+        Node::Call(call) => {
+            let args: Vec<String> = call.get_args().iter().map(arithmetic_to_string).collect();
+            format!("{}({})", call.get_name(), args.join(", "))
+        }
+        Node::Unary(unary) => match unary.get_op() {
+            UnaryOperator::Negate => format!("-{}", node_to_string(unary.get_operand())),
+            UnaryOperator::Not => format!("!{}", node_to_string(unary.get_operand())),
+        },
+        Node::If(i) => format!("if {}", arithmetic_to_string(i.get_condition())),
+        Node::StructLiteral(literal) => {
+            let fields: Vec<String> = literal.get_fields().iter().map(|init| {
+                format!("{}: {}", init.get_name(), arithmetic_to_string(init.get_value()))
+            }).collect();
+            format!("{} {{ {} }}", literal.get_name(), fields.join(", "))
+        }
+        Node::Field(field) => format!("{}.{}", node_to_string(field.get_base()), field.get_name()),
+        Node::Array(array) => {
+            let elements: Vec<String> = array.get_elements().iter().map(arithmetic_to_string).collect();
+            format!("[{}]", elements.join(", "))
+        }
+        Node::Tuple(t) => {
+            let elements: Vec<String> = t.get_elements().iter().map(arithmetic_to_string).collect();
+            format!("({},)", elements.join(", "))
+        }
+        Node::Repeat(repeat) => format!("[{}; {}]", arithmetic_to_string(repeat.get_value()), repeat.get_len()),
+        Node::Index(index) => format!("{}[{}]", node_to_string(index.get_base()), arithmetic_to_string(index.get_index())),
+        Node::Variant(variant) => {
+            let args: Vec<String> = variant.get_args().iter().map(arithmetic_to_string).collect();
+            format!("{}::{}({})", variant.get_enum_name(), variant.get_name(), args.join(", "))
+        }
+        Node::Match(m) => {
+            let arms: Vec<String> = m.get_arms().iter().map(|arm| match arm.get_guard() {
+                Some(guard) => format!("{} if {}", arm.get_pattern(), arithmetic_to_string(guard)),
+                None => arm.get_pattern().to_string(),
+            }).collect();
+            format!("match {} {{ {} }}", arithmetic_to_string(m.get_value()), arms.join(", "))
+        }
+        Node::Arithmetic(arithmetic) => arithmetic_to_string(arithmetic),
+    }
+}
+
+#[test]
+fn subtraction_is_left_associative() {
+    assert_eq!(parse("1 - 2 - 3;"), "((1 - 2) - 3)");
+    assert_eq!(parse("1 - 2 + 3 - 4;"), "(((1 - 2) + 3) - 4)");
+}
+
+#[test]
+fn division_is_left_associative() {
+    assert_eq!(parse("8 / 4 / 2;"), "((8 / 4) / 2)");
+    assert_eq!(parse("2 * 3 / 4 * 5;"), "(((2 * 3) / 4) * 5)");
+    assert_eq!(parse("7 % 3 * 2 - 1;"), "(((7 % 3) * 2) - 1)");
+}
+
+#[test]
+fn multiplication_binds_tighter() {
+    assert_eq!(parse("a - b * c * d;"), "(a - ((b * c) * d))");
+    assert_eq!(parse("a * b - c * d;"), "((a * b) - (c * d))");
+    assert_eq!(parse("2 * 3 * 4 - 1 * 2;"), "(((2 * 3) * 4) - (1 * 2))");
+}
+
+#[test]
+fn parentheses_and_unary_minus() {
+    assert_eq!(parse("(1 - 2) * 3;"), "((1 - 2) * 3)");
+    assert_eq!(parse("1 - (2 - 3);"), "(1 - (2 - 3))");
+    assert_eq!(parse("-a * b;"), "(-a * b)");
+    assert_eq!(parse("f(1 - 2 - 3, x) / 2;"), "(f(((1 - 2) - 3), x) / 2)");
+}
+
+#[test]
+fn comparisons_bind_looser_than_arithmetic() {
+    assert_eq!(parse("a + 1 < b * 2;"), "((a + 1) < (b * 2))");
+    assert_eq!(parse("a < b == c >= d;"), "(((a < b) == c) >= d)");
+}
+
+#[test]
+fn and_binds_tighter_than_or() {
+    assert_eq!(parse("a || b && c;"), "(a || (b && c))");
+    assert_eq!(parse("a == 1 && b != 2 || c;"), "(((a == 1) && (b != 2)) || c)");
+    assert_eq!(parse("1 + if a || b { 1 } else { 2 };"), "(1 + if (a || b))");
+}
+
+#[test]
+fn field_access_binds_tighter_than_unary() {
+    assert_eq!(parse("-p.x * q.a.b;"), "(-p.x * q.a.b)");
+    assert_eq!(parse("P { x: 1, y: f(2) }.y;"), "P { x: 1, y: f(2) }.y");
+}
+
+#[test]
+fn conditions_take_struct_literals_only_in_parentheses() {
+    assert_eq!(parse("if p == q { 1 } else { 2 };"), "if (p == q)");
+    assert_eq!(parse("if p == (Q { a: 1 }) { 1 } else { 2 };"), "if (p == Q { a: 1 })");
+}
+
+#[test]
+fn strings_are_unescaped() {
+    assert_eq!(parse(r#"f("a\tb\n", "\"\\", "\u{48}\u{e9}");"#), r#"f("a\tb\n", "\"\\", "Hé")"#);
+    assert_eq!(parse(r#""// not a comment";"#), r#""// not a comment""#);
+}
+
+#[test]
+fn bad_strings_do_not_tokenize() {
+    let codes = |code: &str| -> Vec<String> {
+        let errs = Tokenizer::new(code.chars().collect()).tokenize().expect_err("does not tokenize");
+        errs.iter().map(|err| err.get_code().to_owned()).collect()
+    };
+    assert_eq!(codes(r#""a\q";"#), ["E0003"]);
+    assert_eq!(codes(r#""\u{d800}\u{}\u41";"#), ["E0004", "E0004", "E0004"]);
+    assert_eq!(codes("\"open;\n"), ["E0002"]);
+}
+
+#[test]
+fn indexing_chains_with_fields() {
+    assert_eq!(parse("a[i + 1][j].x * 2;"), "(a[(i + 1)][j].x * 2)");
+    assert_eq!(parse("-[1, 2 * 3][0];"), "-[1, (2 * 3)][0]");
+    assert_eq!(parse("[[0; 3]; 2];"), "[[0; 3]; 2]");
+}
+
+#[test]
+fn match_arms_take_patterns_and_guards() {
+    assert_eq!(parse("1 + match s { E::A(x, _) if x > 0 => x, E::B => 0, -1 => { 2 } _ => 3 };"),
+        "(1 + match s { E::A(x, _) if (x > 0), E::B, -1, _ })");
+    assert_eq!(parse("E::A(1 + 2, E::B).x;"), "E::A((1 + 2), E::B()).x");
+}
+
+#[test]
+fn tuples_need_a_comma() {
+    assert_eq!(parse("(1 + 2) * 3;"), "((1 + 2) * 3)");
+    assert_eq!(parse("(1,).0 + (a, b * 2).1;"), "((1,).0 + (a, (b * 2),).1)");
+    assert_eq!(parse("t.0.1 * -u.2;"), "(t.0.1 * -u.2)");
+}
+
+#[test]
+fn numbers_take_bases_and_suffixes() {
+    assert_eq!(parse("0xff_u8 + 0o17 * 0b1010i64;"), "(0xff_u8 + (0o17 * 0b1010i64))");
+    assert_eq!(parse("'a' == '\\n' || '\\'' == '\\u{e9}';"), "(('a' == '\\n') || ('\\'' == 'é'))");
+    let codes = |code: &str| -> Vec<String> {
+        let errs = Tokenizer::new(code.chars().collect()).tokenize().expect_err("does not tokenize");
+        errs.iter().map(|err| err.get_code().to_owned()).collect()
+    };
+    assert_eq!(codes("1u3 + 0x + 0b102;"), ["E0005", "E0007", "E0006"]);
+    assert_eq!(codes("'' + 'ab';"), ["E0008", "E0008"]);
+}
+
+#[test]
+fn spans_count_chars_in_columns_and_bytes_in_offsets() {
+    let code = "let s = \"é\"; // ü\n\t'日' + x";
+    let tokens = Tokenizer::new(code.chars().collect()).tokenize().expect("tokenizes");
+    let spans: Vec<(String, u32, u32, usize, usize)> = tokens.iter()
+        .filter(|token| !matches!(token.get_t_type(), TokenType::NewLine | TokenType::EOF))
+        .map(|token| {
+            let span = token.get_span();
+            (token.get_inner(), span.get_pos().get_line(), span.get_pos().get_x(), span.get_start(), span.get_end())
+        })
+        .collect();
+    let expected = [
+        ("let", 1, 1, 0, 3), ("s", 1, 5, 4, 5), ("=", 1, 7, 6, 7), ("é", 1, 9, 8, 12), (";", 1, 12, 12, 13),
+        ("// ü", 1, 14, 14, 19), ("日", 2, 2, 21, 26), ("+", 2, 6, 27, 28), ("x", 2, 8, 29, 30),
+    ];
+    assert_eq!(spans, expected.map(|(inner, line, x, start, end)| (inner.to_owned(), line, x, start, end)));
+    for (inner, _, _, start, end) in spans.iter().filter(|(inner, ..)| inner != "é" && inner != "日") {
+        assert_eq!(&code[*start..*end], inner);
+    }
+}
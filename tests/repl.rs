@@ -0,0 +1,127 @@
+extern crate my_lang;
+
+use my_lang::repl::Repl;
+
+// What the REPL prints for each input, one after another.
+fn session(inputs: &[&str]) -> Vec<String> {
+    let mut repl = Repl::new();
+    inputs.iter().map(|input| {
+        let mut out = Vec::new();
+        repl.eval(input, &mut out).expect("writes to memory");
+        String::from_utf8(out).expect("utf-8 output")
+    }).collect()
+}
+
+#[test]
+fn keeps_variables_and_functions() {
+    let outputs = session(&[
+        "let x = 5",
+        "fn sq(a: int) -> int { return a * a; }",
+        "sq(x) + 1; x",
+    ]);
+    assert_eq!(outputs, ["", "", "26\n5\n"]);
+}
+
+#[test]
+fn failed_input_leaves_nothing_behind() {
+    let outputs = session(&[
+        "let mut x = 1",
+        "x = 2; let y = 1 / 0;",
+        ":type y",
+        "x",
+    ]);
+    assert!(outputs[1].contains("attempt to divide by zero"));
+    assert!(outputs[2].contains("cannot find variable `y`"));
+    assert_eq!(outputs[3], "1\n");
+}
+
+#[test]
+fn meta_commands() {
+    let outputs = session(&[
+        "let x = 5",
+        ":type x * 2",
+        ":tokens x",
+        ":asm x + 1",
+    ]);
+    assert_eq!(outputs[1], "int\n");
+    assert_eq!(outputs[2], "Identifier       \"x\" at 1:1\n");
+    assert!(outputs[3].contains("push $5"));
+}
+
+#[test]
+fn prints_before_the_value() {
+    let outputs = session(&[
+        r#"let s = "a\"b""#,
+        "s",
+        "println(s); 1",
+    ]);
+    assert_eq!(outputs, ["", "\"a\\\"b\"\n", "a\"b\n1\n"]);
+}
+
+#[test]
+fn arrays_are_kept_as_values() {
+    let outputs = session(&[
+        "let mut a = [1,\n2, 3]",
+        "a[0] = 7",
+        "a",
+        ":asm a[1]",
+    ]);
+    assert_eq!(outputs[..3], ["", "", "[7, 2, 3]\n"]);
+    assert!(outputs[3].contains("push $7"));
+}
+
+#[test]
+fn enums_print_their_variants() {
+    let outputs = session(&[
+        "enum Shape { Circle(int), Empty }",
+        "let s = Shape::Circle(3)",
+        "s",
+        "match s { Shape::Circle(r) => r, Shape::Empty => 0 }",
+    ]);
+    assert_eq!(outputs, ["", "", "Shape::Circle(3)\n", "3\n"]);
+}
+
+#[test]
+fn tuples_print_in_parentheses() {
+    let outputs = session(&[
+        "let t = (1, (true,))",
+        "t",
+        "let (a, _) = t",
+        "a + t.0",
+    ]);
+    assert_eq!(outputs, ["", "(1, (true,))\n", "", "2\n"]);
+}
+
+#[test]
+fn sized_values_keep_their_type() {
+    let outputs = session(&[
+        "let a: u8 = 255",
+        "a + 1",
+        "let c = 'x'",
+        "c",
+        "a",
+    ]);
+    assert_eq!(outputs, ["", "0\n", "", "'x'\n", "255\n"]);
+}
+
+#[test]
+fn earlier_inputs_are_pointed_at_by_notes() {
+    let outputs = session(&[
+        "let abc = 1; fn f() -> int { 1 }",
+        "let s = \"ééééé\"; fn f() -> bool { true }",
+        "abc = 3",
+        "f(1)",
+        ":asm fn f() -> int { 2 }",
+    ]);
+    assert!(outputs[1].contains("^ redefined here") && outputs[1].contains("note: the previous definition is in an earlier input"));
+    assert!(!outputs[1].contains("mismatched types"));
+    assert!(outputs[2].contains("note: `abc` is declared in an earlier input"));
+    assert!(outputs[3].contains("note: `f` is defined in an earlier input"));
+    assert!(outputs[4].contains("note: the previous definition is in an earlier input"));
+}
+
+#[test]
+fn declarations_alone_have_assembly() {
+    let outputs = session(&[":asm fn g() -> int { 1 }"]);
+    assert!(outputs[0].contains("push %rbp") && !outputs[0].contains("E0303"));
+}
@@ -0,0 +1,187 @@
+extern crate my_lang;
+
+use my_lang::tokenizer::Tokenizer;
+use my_lang::parser::Parser;
+use my_lang::semantic::TypeChecker;
+
+// The labels of the errors in code, one per error, or the message of one without a label.
+fn errors(code: &str) -> Vec<String> {
+    let tokens = Tokenizer::new(code.chars().collect()).tokenize().expect("tokenizes");
+    let program = Parser::new(tokens).parse().expect("parses");
+
+    match TypeChecker::new().check(&program) {
+        Ok(()) => Vec::new(),
+        Err(errs) => errs.iter().map(|err| err.get_primary().map_or(err.get_message(), |label| label.get_message()).to_owned()).collect(),
+    }
+}
+
+// The labels of the warnings in code, which has no errors.
+fn warnings(code: &str) -> Vec<String> {
+    let tokens = Tokenizer::new(code.chars().collect()).tokenize().expect("tokenizes");
+    let program = Parser::new(tokens).parse().expect("parses");
+
+    let mut checker = TypeChecker::new();
+    checker.check(&program).expect("type checks");
+    checker.take_warnings().iter().map(|warning| warning.get_primary().expect("warnings have a label").get_message().to_owned()).collect()
+}
+
+#[test]
+fn conditions_are_bools() {
+    assert_eq!(errors("if 1 { }"), ["expected `bool`, found `int`"]);
+    assert_eq!(errors("while 0 { }"), ["expected `bool`, found `int`"]);
+    assert!(errors("let x = 1; if x > 0 && !false { }").is_empty());
+}
+
+#[test]
+fn bools_are_not_numbers() {
+    assert_eq!(errors("true + 3;"), ["expected `int`, found `bool`"]);
+    assert_eq!(errors("-true;"), ["expected `int`, found `bool`"]);
+    assert_eq!(errors("!1;"), ["expected `bool`, found `int`"]);
+    assert_eq!(errors("1 == true;"), ["expected `int`, found `bool`"]);
+}
+
+#[test]
+fn struct_literals_initialize_every_field() {
+    let decl = "struct P { x: int, y: bool }\n";
+    assert_eq!(errors(&format!("{}P {{ x: 1 }};", decl)), ["missing `y`"]);
+    assert_eq!(errors(&format!("{}P {{ x: 1, y: true, z: 2 }};", decl)), ["unknown field"]);
+    assert_eq!(errors(&format!("{}P {{ x: true, y: true }};", decl)), ["expected `int`, found `bool`"]);
+    assert_eq!(errors(&format!("{}let p = P {{ y: true, x: 1 }};\np.z;", decl)), ["unknown field"]);
+}
+
+#[test]
+fn structs_cannot_hold_themselves() {
+    assert_eq!(errors("struct A { b: B }\nstruct B { a: A }\n0;"), ["recursive without indirection"]);
+}
+
+#[test]
+fn only_integers_bools_chars_and_strs_print() {
+    assert!(errors(r#"print(1); print(2u8); print(true); print('c'); println("x"); println();"#).is_empty());
+    assert_eq!(errors("struct P { }\nprint(P {});"), ["`P` has no text form"]);
+    assert_eq!(errors("print(1, 2);"), ["expected 1 argument"]);
+    assert_eq!(errors("fn f(a: int, b: int) {}\nf(1);"), ["expected 2 arguments"]);
+    assert_eq!(errors(r#"let s: int = "x";"#), ["expected `int`, found `str`"]);
+}
+
+#[test]
+fn arrays_are_typed_by_their_elements() {
+    assert_eq!(errors("let a = [];"), ["the element type is unknown"]);
+    assert_eq!(errors("let a: [int; 2] = [1, true];"), ["expected `int`, found `bool`"]);
+    assert_eq!(errors("let x = 1;\nx[0];"), ["not an array"]);
+    assert_eq!(errors("let a = [0; 4];\na[2 + 2];"), ["this index would abort at runtime"]);
+    assert_eq!(errors("len(1);"), ["expected an array or a `str`, found `int`"]);
+    assert!(errors("let a: [bool; 0] = [true; 0];\nlen(a);").is_empty());
+}
+
+#[test]
+fn matches_cover_every_variant() {
+    let decl = "enum Shape { Circle(int), Rect(int, int), Empty }\nlet s = Shape::Empty;\n";
+    assert_eq!(errors(&format!("{}match s {{ Shape::Circle(_) => 1, Shape::Rect(1, _) => 2 }};", decl)),
+        ["patterns `Shape::Rect(_, _)` and `Shape::Empty` not covered"]);
+    assert!(errors(&format!("{}match s {{ Shape::Circle(r) if r > 0 => r, _ => 0 }};", decl)).is_empty());
+    assert_eq!(errors(&format!("{}match true {{ true => 1 }};", decl)), ["pattern `false` not covered"]);
+    assert_eq!(errors(&format!("{}Shape::Rect(1);", decl)), ["expected 2 fields"]);
+    assert_eq!(errors(&format!("{}Shape::Square;", decl)), ["variant not found in `Shape`"]);
+    assert_eq!(errors(&format!("{}match s {{ Shape::Rect(a, a) => a, _ => 0 }};", decl)), ["used in a pattern more than once"]);
+}
+
+#[test]
+fn missing_patterns_are_named() {
+    let decl = "enum Shape { Circle(int), Rect(int, int), Empty }\nenum Opt { Some(Shape), Nothing }\nlet o = Opt::Nothing;\n";
+    assert_eq!(errors(&format!("{}match o {{ Opt::Some(Shape::Circle(_)) => 1, Opt::Some(Shape::Rect(1, _)) => 2, Opt::Nothing => 3 }};", decl)),
+        ["patterns `Opt::Some(Shape::Rect(_, _))` and `Opt::Some(Shape::Empty)` not covered"]);
+    assert_eq!(errors(&format!("{}match o {{ Opt::Nothing => 1 }};", decl)), ["pattern `Opt::Some(_)` not covered"]);
+    assert_eq!(errors("enum E { A(bool, bool), B, C, D }\nmatch E::B { E::A(true, _) => 1, E::A(_, true) => 2 };"),
+        ["patterns `E::A(false, false)`, `E::B`, `E::C` and 1 more not covered"]);
+    assert_eq!(errors("let n = 1;\nmatch n { 0 => 0, x if x > 0 => 1 };"), ["pattern `_` not covered"]);
+    assert!(errors("enum Never { }\nfn f(n: Never) -> int { match n { } }\n0;").is_empty());
+}
+
+#[test]
+fn unreachable_arms_are_warned_about() {
+    let decl = "enum Shape { Circle(int), Rect(int, int), Empty }\nlet s = Shape::Empty;\n";
+    assert_eq!(warnings(&format!("{}match s {{ Shape::Circle(_) => 1, Shape::Circle(3) => 2, _ => 3 }};", decl)), ["no value can reach this"]);
+    assert_eq!(warnings("let n = 1;\nmatch n { x => x, 5 => 5, _ => 0 };"), ["no value can reach this", "no value can reach this"]);
+    assert!(warnings(&format!("{}match s {{ Shape::Rect(w, _) if w > 0 => w, Shape::Rect(1, _) => 1, _ => 0 }};", decl)).is_empty());
+    assert!(warnings("match true { true => 1, false => 0 };").is_empty());
+}
+
+#[test]
+fn tuples_are_checked_by_field() {
+    assert_eq!(errors("let (a, b) = (1, 2, 3);"), ["expected 3 fields, found 2"]);
+    assert_eq!(errors("let t = (1, true);\nt.2;"), ["unknown field"]);
+    assert_eq!(errors("let t: (int, bool) = (true, 1);"), ["expected `(int, bool)`, found `(bool, int)`"]);
+    assert_eq!(errors("match (true, 1) { (true, _) => 1, (_, 0) => 2 };"), ["pattern `(false, _)` not covered"]);
+    assert!(errors("fn f(t: (bool, bool)) -> int { match t { (true, _) => 1, (false, true) => 2, (_, false) => 3 } }\n0;").is_empty());
+}
+
+#[test]
+fn literals_fit_their_type() {
+    assert_eq!(errors("let a: u8 = 256;\nlet b = 128i8;\nlet c: i8 = -128;"), ["does not fit into `u8`", "does not fit into `i8`"]);
+    assert_eq!(errors("let a: u32 = 1;\n-a;"), ["cannot apply unary operator `-`"]);
+    assert_eq!(errors("let x: u8 = 1;\nlet y: i32 = 2;\nx + y;"), ["expected `u8`, found `i32`"]);
+    assert_eq!(errors("let c: char = 1;"), ["expected `char`, found `int`"]);
+    assert!(errors("let x: u64 = 1;\nlet y = x * 2 + 0xff;\nlet z: [i16; 2] = [1, -2];").is_empty());
+}
+
+#[test]
+fn literals_are_inferred_through_branches() {
+    assert!(errors("let c = true;\nlet x: u8 = if c { 1 } else { 2 };\nlet y: u8 = match c { true => 3, false => { 4 } };").is_empty());
+    assert!(errors("fn f(c: bool) -> (i16, u8) { if c { (-1, 2) } else { match c { _ => (300, 4) } } }\n0;").is_empty());
+    assert_eq!(errors("let c = true;\nlet x: u8 = if c { 256 } else { 0 };"), ["does not fit into `u8`"]);
+    assert_eq!(errors("let c = true;\nlet x: u8 = match c { true => 1, false => -1 };"), ["cannot apply unary operator `-`"]);
+}
+
+#[test]
+fn untyped_branches_take_the_type_of_their_siblings() {
+    assert!(errors("let c = true;\nlet x = if c { 1u8 } else { 2 };\nlet y: u8 = x;").is_empty());
+    assert!(errors("let c = true;\nlet x = if c { 1 } else { 2u8 };\nlet y: u8 = x;").is_empty());
+    assert!(errors("let v = 3;\nlet x = match v { 0 => 1u8, _ => 2 };\nlet y: u8 = x;").is_empty());
+    assert!(errors("let v = 3;\nlet x = match v { 0 => 1, 1 => { 2 }, _ => 3u16 };\nlet y: u16 = x;").is_empty());
+    assert_eq!(errors("let c = true;\nlet x = if c { 300 } else { 2u8 };"), ["does not fit into `u8`"]);
+    assert_eq!(errors("let v = 3;\nlet x = match v { 0 => 1u8, _ => 2i8 };"), ["expected `u8`, found `i8`"]);
+}
+
+#[test]
+fn range_bounds_share_an_integer_type() {
+    assert!(errors("let n: u8 = 3u8;\nfor i in 0..n { let x: u8 = i; }\nfor i in -3i8..=3 { let y: i8 = i; }").is_empty());
+    assert_eq!(errors("let n: u8 = 3;\nlet m: i16 = 2;\nfor i in m..n {}"), ["expected `i16`, found `u8`"]);
+    assert_eq!(errors("for i in 0..3 { let x: u8 = i; }"), ["expected `u8`, found `int`"]);
+    assert_eq!(errors("for i in true..2 {}"), ["expected `int`, found `bool`"]);
+}
+
+#[test]
+fn unit_is_written_as_empty_parentheses() {
+    assert!(errors("fn f(u: ()) -> () { return; }\nfn g() -> ( ) {}\nlet x: () = g();\nlet t: (int, ()) = (1, f(x));").is_empty());
+    assert_eq!(errors("fn f() -> () { 1 }\nf();"), ["expected `()`, found `int`"]);
+    assert_eq!(errors("let x: () = 1;"), ["expected `()`, found `int`"]);
+}
+
+#[test]
+fn main_takes_nothing_and_returns_a_status() {
+    assert_eq!(errors("fn main(a: int, b: int) -> int { a }"), ["remove these parameters"]);
+    assert_eq!(errors("struct P { x: int }\nfn main() -> P { P { x: 3 } }"), ["not an exit status"]);
+    assert_eq!(errors("fn main() -> (int, int) { (1, 2) }"), ["not an exit status"]);
+    assert!(errors("fn main() -> u8 { 3 }").is_empty());
+    assert!(errors("fn main() -> bool { true }").is_empty());
+    assert_eq!(errors("fn f() {}\nstruct P { }"), ["`main` function not found"]);
+}
+
+#[test]
+fn runtime_functions_are_reserved() {
+    assert_eq!(errors("fn exit(code: int) -> int { 7 }\nexit(0);"), ["reserved for the runtime"]);
+    assert_eq!(errors("fn write() {}\nwrite();"), ["reserved for the runtime"]);
+    assert!(errors("fn exits() {}\nexits();").is_empty());
+}
+
+#[test]
+fn annotations_are_pointed_at_by_their_type() {
+    for code in ["let x: bool = 1;", "let (a, b): (int, bool) = (1, 2);"] {
+        let tokens = Tokenizer::new(code.chars().collect()).tokenize().expect("tokenizes");
+        let program = Parser::new(tokens).parse().expect("parses");
+        let errs = TypeChecker::new().check(&program).expect_err("has a mismatch");
+        let annotation = errs[0].get_labels().iter().find(|label| !label.is_primary()).expect("points at the annotation");
+        let (start, end) = (annotation.get_span().get_start(), annotation.get_span().get_end());
+        assert_eq!(&code[start..end], code.split_once(": ").unwrap().1.split_once(" =").unwrap().0);
+    }
+}